@@ -17,8 +17,13 @@
 extern crate ansi_term;
 extern crate ethcore;
 extern crate ethcore_io as io;
+extern crate ethcore_transaction as transaction;
+extern crate ethereum_types;
+extern crate futures;
+extern crate journaldb;
 extern crate kvdb;
 extern crate kvdb_rocksdb;
+extern crate rlp;
 extern crate stop_guard;
 
 #[macro_use]
@@ -27,6 +32,16 @@ extern crate log;
 #[cfg(test)]
 extern crate tempdir;
 
+mod log_stream;
+mod log_throttle;
+mod private_tx;
 mod service;
-
-pub use service::ClientService;
+mod throttled_notify;
+mod tx_propagation;
+mod watchdog;
+
+pub use service::{BlockTraces, ClientService, ConfigSummary, Features, ForkSchedule, Health, ImportSummary, SelftestReport, TransactionStatus, UncleStats, VerificationResult};
+pub use log_stream::{Log, LogFilter};
+pub use private_tx::{Encryptor, PrivateTxProvider, PrivateTxService, QuarantinedTransaction};
+pub use tx_propagation::PropagationStats;
+pub use kvdb_rocksdb::{MergeOperands, MergeOperator};
@@ -0,0 +1,121 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Streams of logs matching a filter, pushed as blocks are imported.
+//!
+//! Logs of retracted blocks are sent again marked as removed, before the
+//! logs of the blocks enacted in their place.
+
+use std::sync::{Arc, Mutex, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use ethcore::client::{BlockId, ChainNotify, Client};
+use ethcore::filter::Filter;
+use ethcore::log_entry::LocalizedLogEntry;
+use ethereum_types::{Address, H256};
+
+/// Which logs `ClientService::subscribe_logs` delivers.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LogFilter {
+	/// Addresses one of which must have produced the log. `None` or empty matches any.
+	pub address: Option<Vec<Address>>,
+	/// Topics by position, one of which the log must have there. `None` or empty matches any.
+	pub topics: Vec<Option<Vec<H256>>>,
+}
+
+/// Log delivered by `ClientService::subscribe_logs`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Log {
+	/// The log and where it was created.
+	pub entry: LocalizedLogEntry,
+	/// Whether the block containing the log was retracted by a reorganisation.
+	/// The log was delivered before, when the block was enacted.
+	pub removed: bool,
+}
+
+/// Sends the matching logs of enacted and retracted blocks to a receiver.
+pub struct LogStream {
+	client: Weak<Client>,
+	filter: Filter,
+	sender: Mutex<Sender<Log>>,
+	// cleared once the receiver is gone.
+	alive: AtomicBool,
+}
+
+impl LogStream {
+	/// Create a stream of the logs matching `filter`, read from `client`, along
+	/// with the receiving end. `client` isn't kept alive by the stream.
+	pub fn new(client: Weak<Client>, filter: LogFilter) -> (LogStream, Receiver<Log>) {
+		let (sender, receiver) = mpsc::channel();
+		let stream = LogStream {
+			client: client,
+			filter: Filter {
+				from_block: BlockId::Latest,
+				to_block: BlockId::Latest,
+				address: filter.address,
+				topics: filter.topics,
+				limit: None,
+			},
+			sender: Mutex::new(sender),
+			alive: AtomicBool::new(true),
+		};
+		(stream, receiver)
+	}
+
+	/// Whether the receiver is still around, as of the last log sent.
+	pub fn is_alive(&self) -> bool {
+		self.alive.load(Ordering::SeqCst)
+	}
+
+	// send the matching logs of the given blocks, until the receiver is gone.
+	fn send(&self, client: &Client, blocks: &[H256], removed: bool) {
+		let sender = self.sender.lock().expect("log stream lock is never poisoned; qed");
+		for hash in blocks {
+			for entry in client.block_logs(hash, |entry| self.filter.matches(entry)) {
+				if sender.send(Log { entry: entry, removed: removed }).is_err() {
+					self.alive.store(false, Ordering::SeqCst);
+					return;
+				}
+			}
+		}
+	}
+}
+
+impl ChainNotify for LogStream {
+	fn new_blocks(
+		&self,
+		_imported: Vec<H256>,
+		_invalid: Vec<H256>,
+		enacted: Vec<H256>,
+		retracted: Vec<H256>,
+		_sealed: Vec<H256>,
+		_proposed: Vec<Vec<u8>>,
+		_duration: u64,
+	) {
+		let client = match self.client.upgrade() {
+			Some(client) => client,
+			None => return,
+		};
+
+		if self.is_alive() {
+			self.send(&client, &retracted, true);
+		}
+		if self.is_alive() {
+			self.send(&client, &enacted, false);
+		}
+	}
+}
@@ -0,0 +1,111 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Rate limiting for repeated identical warnings.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Entry {
+	logged_at: Instant,
+	suppressed: usize,
+}
+
+/// Logs each distinct warning at most once per interval and reports how
+/// many identical warnings were suppressed in between.
+pub struct LogThrottle {
+	interval: Duration,
+	entries: Mutex<HashMap<(&'static str, String), Entry>>,
+}
+
+impl LogThrottle {
+	/// Create a throttle letting every warning through once per `interval`.
+	pub fn new(interval: Duration) -> Self {
+		LogThrottle {
+			interval: interval,
+			entries: Mutex::new(HashMap::new()),
+		}
+	}
+
+	/// Log a warning under `target` unless it was already logged within the interval.
+	pub fn warn(&self, target: &'static str, message: String) {
+		if let Some(suppressed) = self.check(target, &message) {
+			if suppressed > 0 {
+				warn!(target: target, "{} identical warnings suppressed", suppressed);
+			}
+			warn!(target: target, "{}", message);
+		}
+	}
+
+	/// Report suppressed warnings whose interval has passed and forget about them.
+	/// Should be called periodically so that counts aren't held back indefinitely.
+	pub fn flush(&self) {
+		let mut entries = self.entries.lock().expect("log throttle lock is never poisoned; qed");
+		let interval = self.interval;
+		entries.retain(|&(target, ref message), entry| {
+			if entry.logged_at.elapsed() < interval {
+				return true;
+			}
+			if entry.suppressed > 0 {
+				warn!(target: target, "{} identical warnings suppressed: {}", entry.suppressed, message);
+			}
+			false
+		});
+	}
+
+	// `Some` with the number of suppressed repeats if the message should be logged now.
+	fn check(&self, target: &'static str, message: &str) -> Option<usize> {
+		let mut entries = self.entries.lock().expect("log throttle lock is never poisoned; qed");
+		let now = Instant::now();
+		let key = (target, message.to_owned());
+
+		if let Some(entry) = entries.get_mut(&key) {
+			if now.duration_since(entry.logged_at) < self.interval {
+				entry.suppressed += 1;
+				return None;
+			}
+
+			let suppressed = entry.suppressed;
+			entry.logged_at = now;
+			entry.suppressed = 0;
+			return Some(suppressed);
+		}
+
+		entries.insert(key, Entry { logged_at: now, suppressed: 0 });
+		Some(0)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::thread;
+	use std::time::Duration;
+	use super::LogThrottle;
+
+	#[test]
+	fn suppresses_repeats_within_interval() {
+		let throttle = LogThrottle::new(Duration::from_millis(50));
+
+		assert_eq!(throttle.check("service", "failed"), Some(0));
+		assert_eq!(throttle.check("service", "failed"), None);
+		assert_eq!(throttle.check("service", "failed"), None);
+		assert_eq!(throttle.check("service", "other"), Some(0));
+
+		thread::sleep(Duration::from_millis(100));
+		assert_eq!(throttle.check("service", "failed"), Some(2));
+	}
+}
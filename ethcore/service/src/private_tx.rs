@@ -0,0 +1,341 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Private transactions, handed over to a pluggable provider.
+//!
+//! This build doesn't include a private transaction provider itself; one is
+//! plugged in with `ClientService::enable_private_tx`. Queued transactions are
+//! retried on every tick while the provider fails them. Once a transaction failed
+//! `max_failures` times it's quarantined instead, so that a poison transaction
+//! can't clog the provider, until an operator releases it.
+//!
+//! Private transactions can't be handled without the encryptor. If it can't be
+//! reached on start the service starts degraded instead, and keeps reconnecting
+//! on every tick. Transactions are held, and not failed, until it's reachable.
+
+use std::collections::VecDeque;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use ethcore::client::ClientIoMessage;
+use ethereum_types::H256;
+use io::{IoContext, IoHandler, TimerToken};
+use transaction::UnverifiedTransaction;
+
+const RETRY_TIMER: TimerToken = 0;
+const RETRY_MS: u64 = 5000;
+
+/// Processes private transactions.
+pub trait PrivateTxProvider: Send + Sync {
+	/// Handle a queued private transaction. Failed transactions are retried later.
+	fn on_private_transaction_queued(&self, transaction: &UnverifiedTransaction) -> Result<(), String>;
+}
+
+/// Encrypts and decrypts private state, usually through a remote key server.
+pub trait Encryptor: Send + Sync {
+	/// Connect to the encryptor.
+	fn connect(&self) -> Result<(), String>;
+}
+
+/// A private transaction which isn't retried anymore, as returned by `PrivateTxService::quarantined`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuarantinedTransaction {
+	/// The transaction.
+	pub transaction: UnverifiedTransaction,
+	/// Number of times the provider failed it.
+	pub failures: usize,
+	/// Error of the last failure.
+	pub last_error: String,
+}
+
+struct Pending {
+	transaction: UnverifiedTransaction,
+	failures: usize,
+}
+
+#[derive(Default)]
+struct State {
+	pending: VecDeque<Pending>,
+	quarantined: Vec<QuarantinedTransaction>,
+}
+
+/// Hands private transactions to a provider, retrying and quarantining failed ones.
+pub struct PrivateTxService {
+	provider: Arc<PrivateTxProvider>,
+	encryptor: Arc<Encryptor>,
+	encryptor_ready: AtomicBool,
+	max_failures: usize,
+	state: Mutex<State>,
+}
+
+impl PrivateTxService {
+	/// Create a service handing transactions to `provider`, which quarantines
+	/// transactions after `max_failures` failures. Zero quarantines on the first one.
+	///
+	/// Doesn't fail if the `encryptor` can't be reached; the service starts degraded
+	/// and reconnects in the background.
+	pub fn new(provider: Arc<PrivateTxProvider>, encryptor: Arc<Encryptor>, max_failures: usize) -> Self {
+		let ready = match encryptor.connect() {
+			Ok(()) => true,
+			Err(e) => {
+				warn!(target: "privatetx", "Encryptor unavailable, private transactions are held until it's reachable: {}", e);
+				false
+			}
+		};
+
+		PrivateTxService {
+			provider: provider,
+			encryptor: encryptor,
+			encryptor_ready: AtomicBool::new(ready),
+			max_failures: max_failures,
+			state: Mutex::new(State::default()),
+		}
+	}
+
+	/// Whether the encryptor is connected. Private transactions aren't handled before it is.
+	pub fn encryptor_ready(&self) -> bool {
+		self.encryptor_ready.load(Ordering::SeqCst)
+	}
+
+	// connect to the encryptor unless already connected. only the initial failure is
+	// warned about, so that an unreachable encryptor doesn't flood the log.
+	fn ensure_encryptor(&self) -> bool {
+		if self.encryptor_ready() { return true }
+
+		match self.encryptor.connect() {
+			Ok(()) => {
+				info!(target: "privatetx", "Encryptor connected, handling private transactions");
+				self.encryptor_ready.store(true, Ordering::SeqCst);
+				true
+			}
+			Err(e) => {
+				trace!(target: "privatetx", "Encryptor still unavailable: {}", e);
+				false
+			}
+		}
+	}
+
+	/// Queue a private transaction and hand it to the provider right away.
+	pub fn queue(&self, transaction: UnverifiedTransaction) {
+		self.state.lock().expect("private tx lock is never poisoned; qed").pending.push_back(Pending {
+			transaction: transaction,
+			failures: 0,
+		});
+		self.process();
+	}
+
+	/// Number of transactions waiting to be retried.
+	pub fn pending(&self) -> usize {
+		self.state.lock().expect("private tx lock is never poisoned; qed").pending.len()
+	}
+
+	/// Transactions which failed too often to be retried.
+	pub fn quarantined(&self) -> Vec<QuarantinedTransaction> {
+		self.state.lock().expect("private tx lock is never poisoned; qed").quarantined.clone()
+	}
+
+	/// Release the quarantined transaction with the given hash, so that it's retried with
+	/// its failures forgiven. Returns whether such a transaction was quarantined.
+	pub fn release(&self, hash: &H256) -> bool {
+		{
+			let mut state = self.state.lock().expect("private tx lock is never poisoned; qed");
+			let index = match state.quarantined.iter().position(|q| &q.transaction.hash() == hash) {
+				Some(index) => index,
+				None => return false,
+			};
+
+			let released = state.quarantined.remove(index);
+			state.pending.push_back(Pending {
+				transaction: released.transaction,
+				failures: 0,
+			});
+		}
+		self.process();
+		true
+	}
+
+	// hand the pending transactions to the provider. the provider is called without
+	// holding the lock, so that it may queue transactions itself. while the encryptor
+	// isn't connected the transactions are kept, and their failures aren't counted.
+	fn process(&self) {
+		if !self.ensure_encryptor() { return }
+
+		let pending = {
+			let mut state = self.state.lock().expect("private tx lock is never poisoned; qed");
+			mem::replace(&mut state.pending, VecDeque::new())
+		};
+		if pending.is_empty() { return }
+
+		let mut retry = VecDeque::new();
+		let mut quarantine = Vec::new();
+		for mut entry in pending {
+			let error = match self.provider.on_private_transaction_queued(&entry.transaction) {
+				Ok(()) => continue,
+				Err(e) => e,
+			};
+
+			entry.failures += 1;
+			if entry.failures >= self.max_failures {
+				warn!(target: "privatetx", "Quarantining private transaction {:?} after {} failures: {}", entry.transaction.hash(), entry.failures, error);
+				quarantine.push(QuarantinedTransaction {
+					transaction: entry.transaction,
+					failures: entry.failures,
+					last_error: error,
+				});
+			} else {
+				trace!(target: "privatetx", "Private transaction {:?} failed, retrying later: {}", entry.transaction.hash(), error);
+				retry.push_back(entry);
+			}
+		}
+
+		let mut state = self.state.lock().expect("private tx lock is never poisoned; qed");
+		// transactions queued in the meantime go after the retried ones.
+		retry.extend(state.pending.drain(..));
+		state.pending = retry;
+		state.quarantined.extend(quarantine);
+	}
+}
+
+impl IoHandler<ClientIoMessage> for PrivateTxService {
+	fn initialize(&self, io: &IoContext<ClientIoMessage>) {
+		io.register_unique_timer(RETRY_TIMER, RETRY_MS).expect("Error registering private transaction timer");
+	}
+
+	fn timeout(&self, _io: &IoContext<ClientIoMessage>, timer: TimerToken) {
+		if timer == RETRY_TIMER {
+			self.process();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::{Arc, Mutex};
+
+	use ethereum_types::Address;
+	use transaction::{Action, Transaction, UnverifiedTransaction};
+	use super::{Encryptor, PrivateTxProvider, PrivateTxService};
+
+	// fails every transaction while `failing` is set.
+	#[derive(Default)]
+	struct Provider {
+		failing: Mutex<bool>,
+		handled: Mutex<usize>,
+	}
+
+	impl PrivateTxProvider for Provider {
+		fn on_private_transaction_queued(&self, _transaction: &UnverifiedTransaction) -> Result<(), String> {
+			if *self.failing.lock().unwrap() {
+				return Err("provider unavailable".into());
+			}
+			*self.handled.lock().unwrap() += 1;
+			Ok(())
+		}
+	}
+
+	// unreachable while `offline` is set.
+	#[derive(Default)]
+	struct KeyServer {
+		offline: Mutex<bool>,
+	}
+
+	impl Encryptor for KeyServer {
+		fn connect(&self) -> Result<(), String> {
+			if *self.offline.lock().unwrap() {
+				return Err("connection refused".into());
+			}
+			Ok(())
+		}
+	}
+
+	fn transaction() -> UnverifiedTransaction {
+		(*Transaction {
+			nonce: 0.into(),
+			gas_price: 0.into(),
+			gas: 21000.into(),
+			action: Action::Call(Address::default()),
+			value: 0.into(),
+			data: Vec::new(),
+		}.fake_sign(Address::from(1))).clone()
+	}
+
+	#[test]
+	fn quarantines_failing_transactions() {
+		let provider = Arc::new(Provider::default());
+		*provider.failing.lock().unwrap() = true;
+		let service = PrivateTxService::new(provider.clone(), Arc::new(KeyServer::default()), 3);
+
+		service.queue(transaction());
+		service.process();
+		assert_eq!(service.pending(), 1);
+		assert!(service.quarantined().is_empty());
+
+		service.process();
+		assert_eq!(service.pending(), 0);
+		let quarantined = service.quarantined();
+		assert_eq!(quarantined.len(), 1);
+		assert_eq!(quarantined[0].failures, 3);
+		assert_eq!(quarantined[0].last_error, "provider unavailable");
+
+		// not retried anymore.
+		service.process();
+		assert_eq!(service.quarantined()[0].failures, 3);
+	}
+
+	#[test]
+	fn retries_released_transactions() {
+		let provider = Arc::new(Provider::default());
+		*provider.failing.lock().unwrap() = true;
+		let service = PrivateTxService::new(provider.clone(), Arc::new(KeyServer::default()), 1);
+
+		let transaction = transaction();
+		service.queue(transaction.clone());
+		assert_eq!(service.quarantined().len(), 1);
+
+		*provider.failing.lock().unwrap() = false;
+		assert!(service.release(&transaction.hash()));
+		assert!(service.quarantined().is_empty());
+		assert_eq!(service.pending(), 0);
+		assert_eq!(*provider.handled.lock().unwrap(), 1);
+
+		assert!(!service.release(&transaction.hash()));
+	}
+
+	#[test]
+	fn starts_degraded_without_encryptor() {
+		let provider = Arc::new(Provider::default());
+		*provider.failing.lock().unwrap() = true;
+		let encryptor = Arc::new(KeyServer::default());
+		*encryptor.offline.lock().unwrap() = true;
+		let service = PrivateTxService::new(provider.clone(), encryptor.clone(), 1);
+		assert!(!service.encryptor_ready());
+
+		// held without counting failures while the encryptor is unreachable.
+		service.queue(transaction());
+		service.process();
+		assert!(!service.encryptor_ready());
+		assert_eq!(service.pending(), 1);
+		assert!(service.quarantined().is_empty());
+
+		*provider.failing.lock().unwrap() = false;
+		*encryptor.offline.lock().unwrap() = false;
+		service.process();
+		assert!(service.encryptor_ready());
+		assert_eq!(service.pending(), 0);
+		assert_eq!(*provider.handled.lock().unwrap(), 1);
+	}
+}
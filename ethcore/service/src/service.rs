@@ -15,91 +15,566 @@
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
 //! Creates and registers client and network services.
+//!
+//! Logging uses the `service`, `snapshot` and `import` targets, with
+//! consensus messages under `poa`.
 
-use std::sync::Arc;
-use std::path::Path;
+use std::cmp;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex, Weak};
+use std::sync::mpsc::Receiver;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use ansi_term::Colour;
-use io::{IoContext, TimerToken, IoHandler, IoService, IoError};
+use ethereum_types::{H256, U256, Address};
+use futures::{future, Future};
+use futures::sync::oneshot;
+use io::{IoContext, TimerToken, TimerInfo, IoHandler, IoService, IoError};
+use journaldb::Algorithm;
 use kvdb::KeyValueDB;
-use kvdb_rocksdb::{Database, DatabaseConfig};
+use kvdb_rocksdb::{self, Database, DatabaseConfig, MergeOperator};
+use rlp::{PayloadInfo, RlpStream, UntrustedRlp};
+use log_stream::{Log, LogFilter, LogStream};
+use log_throttle::LogThrottle;
+use private_tx::PrivateTxService;
 use stop_guard::StopGuard;
+use throttled_notify::ThrottledNotify;
+use tx_propagation::{PropagationStats, TxPropagation};
+use transaction::{PendingTransaction, UnverifiedTransaction};
+use watchdog::Watchdog;
 
-use ethcore::client::{self, Client, ClientConfig, ChainNotify, ClientIoMessage};
+use ethcore::account_provider::AccountProvider;
+use ethcore::client::{self, BlockId, BlockReceipts, BlockTimings, BlockInfo, BlockChainClient, CallAnalytics, ChainInfo, Client, ClientConfig, ChainNotify, ClientIoMessage, ImportBlock, ImportRejection, KnownBlockStats, QueueMemory, StateProof, TransactionId, TxTypeStats, LOCAL_PEER_ID};
 use ethcore::db;
-use ethcore::error::Error;
-use ethcore::miner::Miner;
-use ethcore::snapshot::service::{Service as SnapshotService, ServiceParams as SnapServiceParams};
-use ethcore::snapshot::{RestorationStatus};
+use ethcore::engines::EngineError;
+use ethcore::error::{BlockImportError, CallError, Error, ImportError};
+use ethcore::executed::Executed;
+use ethcore::header::Header;
+use ethcore::miner::{Miner, MinerService, PoolGcReport};
+use ethcore::snapshot::service::{Service as SnapshotService, ServiceParams as SnapServiceParams, LocalSnapshot, ManifestSigner};
+use ethcore::snapshot::{self, BasicAccount, ManifestData, RestorationStatus};
+use ethcore::snapshot::io::{FlatChunkStore, LooseReader, LooseWriter, PackedReader, PackedWriter, SnapshotReader};
 use ethcore::spec::Spec;
 
+/// Subsystems which were enabled when the `ClientService` was started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Features {
+	/// Whether the engine supports taking and restoring snapshots.
+	pub snapshots: bool,
+	/// Whether private transactions are handled, by a provider plugged in with `enable_private_tx`.
+	pub private_tx: bool,
+	/// Whether the node may seal blocks, either internally or through work notifications.
+	pub mining: bool,
+}
+
+/// How the `ClientService` was set up, as returned by `config_summary`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigSummary {
+	/// Name of the chain spec.
+	pub chain: String,
+	/// Current pruning algorithm of the state database.
+	pub pruning: Algorithm,
+	/// Whether the database keeps a write-ahead log.
+	pub wal: bool,
+	/// Whether the previous run didn't shut down cleanly, so that the database had to
+	/// recover on open, from its write-ahead log if it keeps one. RocksDB doesn't report
+	/// whether it actually replayed its log, so this is told by a marker file next to the
+	/// database which only a clean shutdown removes.
+	pub unclean_shutdown_recovered: bool,
+}
+
+/// Activation blocks of the hard forks known to the spec, as returned by `fork_schedule`.
+/// `None` means the fork never activates on this chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForkSchedule {
+	/// Homestead rules. Only known for Ethash chains.
+	pub homestead: Option<u64>,
+	/// EIP-150 gas cost changes. Only known for Ethash chains.
+	pub eip150: Option<u64>,
+	/// EIP-155 replay protection.
+	pub eip155: Option<u64>,
+	/// EIP-160 EXP cost increase. Only known for Ethash chains.
+	pub eip160: Option<u64>,
+	/// EIP-161 state trie clearing, parts a to c. Only known for Ethash chains.
+	pub eip161abc: Option<u64>,
+	/// EIP-161 state trie clearing, part d. Only known for Ethash chains.
+	pub eip161d: Option<u64>,
+	/// EIP-98 receipts without intermediate state roots.
+	pub eip98: Option<u64>,
+	/// EIP-86 account abstraction.
+	pub eip86: Option<u64>,
+	/// EIP-140 REVERT.
+	pub eip140: Option<u64>,
+	/// EIP-210 BLOCKHASH contract.
+	pub eip210: Option<u64>,
+	/// EIP-211 RETURNDATASIZE and RETURNDATACOPY.
+	pub eip211: Option<u64>,
+	/// EIP-214 STATICCALL.
+	pub eip214: Option<u64>,
+	/// EIP-658 status codes in receipts.
+	pub eip658: Option<u64>,
+	/// WASM contracts.
+	pub wasm: Option<u64>,
+}
+
+impl ForkSchedule {
+	fn from_spec(spec: &Spec) -> Self {
+		// specs leave a fork out by scheduling it for the last possible block.
+		fn block(transition: u64) -> Option<u64> {
+			match transition == u64::max_value() {
+				true => None,
+				false => Some(transition),
+			}
+		}
+
+		let params = spec.engine.params();
+		let ethash = spec.engine.machine().ethash_extensions();
+		ForkSchedule {
+			homestead: ethash.and_then(|e| block(e.homestead_transition)),
+			eip150: ethash.and_then(|e| block(e.eip150_transition)),
+			eip155: block(params.eip155_transition),
+			eip160: ethash.and_then(|e| block(e.eip160_transition)),
+			eip161abc: ethash.and_then(|e| block(e.eip161abc_transition)),
+			eip161d: ethash.and_then(|e| block(e.eip161d_transition)),
+			eip98: block(params.eip98_transition),
+			eip86: block(params.eip86_transition),
+			eip140: block(params.eip140_transition),
+			eip210: block(params.eip210_transition),
+			eip211: block(params.eip211_transition),
+			eip214: block(params.eip214_transition),
+			eip658: block(params.eip658_transition),
+			wasm: block(params.wasm_activation_transition),
+		}
+	}
+}
+
+/// Uncles included by recent canonical blocks, as returned by `ClientService::uncle_stats`.
+/// A high uncle rate suggests that blocks propagate slowly through the network.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct UncleStats {
+	/// Uncles included by the sampled blocks.
+	pub total_uncles: u64,
+	/// Number of blocks sampled.
+	pub blocks_sampled: u64,
+	/// Average number of uncles per sampled block.
+	pub uncle_rate: f64,
+}
+
+/// Operational health of the node, as returned by `ClientService::health`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Health {
+	/// How far the local clock appears to be behind the chain, judging by recent block
+	/// timestamps. Blocks are held back or rejected as future blocks while this is large.
+	pub clock_skew: Duration,
+}
+
+/// Outcome of `verify_block`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerificationResult {
+	/// The block passed every check and could be imported.
+	Valid,
+	/// The block failed verification for the given reason.
+	Invalid(ImportRejection),
+}
+
+/// Outcome of a snapshot round-trip self-test.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelftestReport {
+	/// Block number the snapshot was taken at.
+	pub block_number: u64,
+	/// Number of state chunks produced.
+	pub state_chunks: usize,
+	/// Number of block chunks produced.
+	pub block_chunks: usize,
+	/// Total size of the compressed chunks in bytes.
+	pub bytes: usize,
+	/// Time spent taking the snapshot.
+	pub snapshot_time: Duration,
+	/// Time spent restoring and verifying the snapshot.
+	pub restore_time: Duration,
+}
+
+/// Outcome of a bulk block import.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ImportSummary {
+	/// Blocks which were verified and imported.
+	pub imported: usize,
+	/// Blocks which were already in the chain or queue.
+	pub skipped: usize,
+	/// Blocks which were rejected by verification or import.
+	pub failed: usize,
+}
+
+/// Whether a transaction is known to the node, as told by `ClientService::transaction_status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionStatus {
+	/// Neither in the transaction pool nor in the canonical chain.
+	Unknown,
+	/// Waiting in the transaction pool.
+	Pending,
+	/// Included in the canonical chain.
+	Mined {
+		/// Number of the block containing the transaction.
+		block: u64,
+		/// Index of the transaction within the block.
+		index: usize,
+	},
+}
+
+/// Replayed transactions of a block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockTraces {
+	/// Number of the replayed block.
+	pub block_number: u64,
+	/// Outcome of every transaction in block order, including its traces.
+	pub transactions: Vec<Executed>,
+}
+
+/// Time spent in each phase of `ClientService::shutdown`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ShutdownReport {
+	/// Stopping the IO service and its handlers.
+	pub io: Duration,
+	/// Waiting for a snapshot in progress to be written, and taking the final
+	/// snapshot if `snapshot_on_shutdown` is set.
+	pub snapshot: Duration,
+	/// Flushing the database.
+	pub flush: Duration,
+	/// Dropping the client and closing the database.
+	pub close: Duration,
+}
+
+impl ShutdownReport {
+	/// Total time the shutdown took.
+	pub fn total(&self) -> Duration {
+		self.io + self.snapshot + self.flush + self.close
+	}
+}
+
 /// Client service setup. Creates and registers client and network services with the IO subsystem.
 pub struct ClientService {
 	io_service: Arc<IoService<ClientIoMessage>>,
 	client: Arc<Client>,
 	snapshot: Arc<SnapshotService>,
 	database: Arc<Database>,
+	features: Features,
+	// where the most recently used accounts are saved on drop.
+	hot_accounts_path: Option<PathBuf>,
+	// removed on drop, to tell a clean shutdown on the next start.
+	running_marker: PathBuf,
+	summary: ConfigSummary,
+	fork_schedule: ForkSchedule,
+	snapshot_on_shutdown: bool,
+	shutdown_snapshot_timeout: Option<Duration>,
+	// the client only holds weak references to these.
+	throttled_notify: Mutex<Vec<Arc<ThrottledNotify>>>,
+	log_streams: Mutex<Vec<Arc<LogStream>>>,
+	tx_propagation: Arc<TxPropagation>,
+	private_tx: Mutex<Option<Arc<PrivateTxService>>>,
 	_stop_guard: StopGuard,
 }
 
 impl ClientService {
 	/// Start the `ClientService`.
 	pub fn start(
+		config: ClientConfig,
+		spec: &Spec,
+		client_path: &Path,
+		snapshot_path: &Path,
+		ipc_path: &Path,
+		miner: Arc<Miner>,
+		) -> Result<ClientService, Error>
+	{
+		Self::start_with_merge_operators(config, spec, client_path, snapshot_path, ipc_path, miner, HashMap::new())
+	}
+
+	/// Start the `ClientService` with merge operators for the given columns of the
+	/// client database, so that values in them can be updated with `merge`.
+	pub fn start_with_merge_operators(
 		config: ClientConfig,
 		spec: &Spec,
 		client_path: &Path,
 		snapshot_path: &Path,
 		_ipc_path: &Path,
 		miner: Arc<Miner>,
+		merge_operators: HashMap<u32, MergeOperator>,
 		) -> Result<ClientService, Error>
 	{
 		let io_service = IoService::<ClientIoMessage>::start()?;
+		Self::start_on(io_service, config, spec, client_path, snapshot_path, miner, merge_operators)
+	}
+
+	// start the service on the given IO service, which is stopped again on failure.
+	fn start_on(
+		io_service: IoService<ClientIoMessage>,
+		config: ClientConfig,
+		spec: &Spec,
+		client_path: &Path,
+		snapshot_path: &Path,
+		miner: Arc<Miner>,
+		merge_operators: HashMap<u32, MergeOperator>,
+		) -> Result<ClientService, Error>
+	{
+		info!(target: "service", "Configured for {} using {} engine", Colour::White.bold().paint(spec.name.clone()), Colour::Yellow.bold().paint(spec.engine.name()));
+
+		Self::check_miner(spec, &miner)?;
+
+		if config.low_memory {
+			info!(target: "service", "Low-memory mode is active: caches and queues are reduced.");
+		}
 
-		info!("Configured for {} using {} engine", Colour::White.bold().paint(spec.name.clone()), Colour::Yellow.bold().paint(spec.engine.name()));
+		// RocksDB doesn't report whether it replayed its write-ahead log on open, so tell
+		// unclean shutdowns by a marker file which only a clean shutdown removes. It's kept
+		// next to the database, since restoring a snapshot replaces the database directory.
+		let running_marker = running_marker_path(client_path);
+		let unclean_shutdown = running_marker.exists();
+		if unclean_shutdown {
+			match config.db_wal {
+				true => warn!(target: "service", "The last shutdown wasn't clean; recovering the database from its write-ahead log."),
+				false => warn!(target: "service", "The last shutdown wasn't clean and the write-ahead log is disabled; recent changes may be lost."),
+			}
+		}
 
 		let mut db_config = DatabaseConfig::with_columns(db::NUM_COLUMNS);
 
 		db_config.memory_budget = config.db_cache_size;
 		db_config.compaction = config.db_compaction.compaction_profile(client_path);
 		db_config.wal = config.db_wal;
+		db_config.merge_operators = merge_operators;
+
+		let db_path = client_path.to_str().expect("DB path could not be converted to string.");
+		let (db, recovered) = match Database::open(&db_config, db_path) {
+			Ok(db) => (db, false),
+			Err(ref e) if config.auto_recover_db && kvdb_rocksdb::is_corruption(e) => {
+				warn!(target: "service", "Database is corrupt: {}; attempting a repair", e);
+				match Database::repair(db_path).and_then(|_| Database::open(&db_config, db_path)) {
+					Ok(db) => {
+						warn!(target: "service", "Repaired the database; data in corrupt files may have been dropped");
+						(db, false)
+					},
+					Err(e) => {
+						let moved = move_aside(client_path)?;
+						warn!(target: "service", "Database is corrupt beyond repair: {}", e);
+						warn!(target: "service", "Moved the corrupt database to {} and starting over with a fresh one", moved.display());
+						(Database::open(&db_config, db_path).map_err(client::Error::Database)?, true)
+					},
+				}
+			},
+			Err(e) => return Err(client::Error::Database(e).into()),
+		};
+		let db = Arc::new(db);
+		fs::File::create(&running_marker)?;
+
+		let features = Features {
+			snapshots: spec.engine.snapshot_components().is_some(),
+			private_tx: false,
+			mining: miner.forced_sealing() || spec.engine.seals_internally().is_some(),
+		};
+
+		let summary = ConfigSummary {
+			chain: spec.name.clone(),
+			pruning: config.pruning,
+			wal: config.db_wal,
+			unclean_shutdown_recovered: unclean_shutdown,
+		};
+
+		let hot_accounts_path = match config.warm_cache_on_start {
+			true => Some(client_path.join(HOT_ACCOUNTS_FILE)),
+			false => None,
+		};
+
+		let snapshot_on_shutdown = config.snapshot_on_shutdown;
+		let shutdown_snapshot_timeout = config.shutdown_snapshot_timeout;
+
+		let started = Self::start_subsystems(config, spec, client_path, snapshot_path, &db, &db_config, miner, &io_service);
+		let (client, snapshot) = match started {
+			Ok(started) => started,
+			Err(e) => {
+				warn!(target: "service", "Failed to start client service: {}", e);
+				// stopping the IO service drops any registered handlers, so that
+				// nothing is left holding the database open for the next attempt.
+				drop(io_service);
+				drop(db);
+				if let Err(e) = fs::remove_file(&running_marker) {
+					warn!(target: "service", "Failed to remove {}: {}", running_marker.display(), e);
+				}
+				return Err(e);
+			}
+		};
+
+		if let Some(ref path) = hot_accounts_path {
+			warm_caches(Arc::downgrade(&client), path.clone());
+		}
+
+		let tx_propagation = Arc::new(TxPropagation::new(Arc::downgrade(&client)));
+		client.add_notify(tx_propagation.clone());
+
+		let stop_guard = StopGuard::new();
+
+		let service = ClientService {
+			io_service: Arc::new(io_service),
+			client: client,
+			snapshot: snapshot,
+			database: db,
+			features: features,
+			hot_accounts_path: hot_accounts_path,
+			running_marker: running_marker,
+			summary: summary,
+			fork_schedule: ForkSchedule::from_spec(spec),
+			snapshot_on_shutdown: snapshot_on_shutdown,
+			shutdown_snapshot_timeout: shutdown_snapshot_timeout,
+			throttled_notify: Mutex::new(Vec::new()),
+			log_streams: Mutex::new(Vec::new()),
+			tx_propagation: tx_propagation,
+			private_tx: Mutex::new(None),
+			_stop_guard: stop_guard,
+		};
+
+		if recovered {
+			service.recover_from_local_snapshot();
+		}
+
+		let clock_skew = service.client.clock_skew();
+		if clock_skew >= Duration::from_secs(CLOCK_SKEW_WARN_SECS) {
+			warn!(target: "service", "Recent blocks are {}s ahead of the local clock; check that the system time is correct", clock_skew.as_secs());
+		}
+
+		Ok(service)
+	}
+
+	// fill a fresh database from the latest local snapshot, logging what happened.
+	// Without a snapshot, the chain is synced again from genesis.
+	fn recover_from_local_snapshot(&self) {
+		use ethcore::snapshot::SnapshotService;
+
+		let manifest = match self.snapshot.manifest() {
+			Some(manifest) => manifest,
+			None => {
+				warn!(target: "service", "No local snapshot to recover from; syncing the chain from genesis");
+				return;
+			}
+		};
+
+		info!(target: "service", "Recovering the database from the local snapshot at #{}", manifest.block_number);
+		match self.restore_local_snapshot(&manifest) {
+			Ok(()) => info!(target: "service", "Recovered the database up to #{} from the local snapshot", manifest.block_number),
+			Err(e) => warn!(target: "service", "Failed to recover from the local snapshot: {}; syncing the chain from genesis", e),
+		}
+	}
+
+	/// Start the `ClientService` on a separate thread, so that opening a large
+	/// database doesn't block the caller. Dropping the returned future cancels
+	/// the start; a service which is already up is then shut down again.
+	pub fn start_async(
+		config: ClientConfig,
+		spec: Spec,
+		client_path: PathBuf,
+		snapshot_path: PathBuf,
+		ipc_path: PathBuf,
+		miner: Arc<Miner>,
+	) -> Box<Future<Item = ClientService, Error = Error> + Send> {
+		let (tx, rx) = oneshot::channel();
+
+		let started = thread::Builder::new().name("Client Service Start".into()).spawn(move || {
+			let service = ClientService::start(config, &spec, &client_path, &snapshot_path, &ipc_path, miner);
+			// fails only if the start was cancelled, in which case the service is dropped here.
+			let _ = tx.send(service);
+		});
+
+		if let Err(e) = started {
+			return Box::new(future::err(e.into()));
+		}
+
+		Box::new(rx.then(|res| match res {
+			Ok(service) => service,
+			Err(_) => Err(::std::io::Error::new(::std::io::ErrorKind::Other, "Client service start thread panicked").into()),
+		}))
+	}
 
-		let db = Arc::new(Database::open(
-			&db_config,
-			&client_path.to_str().expect("DB path could not be converted to string.")
-		).map_err(client::Error::Database)?);
+	// make sure the miner is able to seal blocks for the spec's engine.
+	fn check_miner(spec: &Spec, miner: &Miner) -> Result<(), Error> {
+		let engine = spec.engine.name();
+		let miner_kind = if miner.engine_name() != engine {
+			format!("the {} engine", miner.engine_name())
+		} else if miner.notifies_work() && spec.engine.seals_internally().is_some() {
+			// work is never prepared for engines which seal internally.
+			"external sealing through work notifications".into()
+		} else {
+			return Ok(());
+		};
 
+		Err(Error::MinerEngineMismatch { miner_kind: miner_kind, engine: engine.into() })
+	}
 
+	// start everything which depends on the opened database.
+	fn start_subsystems(
+		config: ClientConfig,
+		spec: &Spec,
+		client_path: &Path,
+		snapshot_path: &Path,
+		db: &Arc<Database>,
+		db_config: &DatabaseConfig,
+		miner: Arc<Miner>,
+		io_service: &IoService<ClientIoMessage>,
+	) -> Result<(Arc<Client>, Arc<SnapshotService>), Error> {
 		let pruning = config.pruning;
+		let restore_threads = config.restore_threads;
+		let max_pending_chunks = config.max_pending_restore_chunks;
+		let stall_timeout = config.restoration_stall_timeout;
+		let keep_failed_restoration = config.keep_failed_restoration;
+		let max_restoration_peers = config.max_restoration_peers;
+		let mut restore_db_config = db_config.clone();
+		if let Some(mb) = config.restore_state_cache_mb {
+			restore_db_config.memory_budget = Some(mb);
+		}
+		let max_db_size = config.max_db_size.map(|max| (client_path.to_owned(), max));
+		let snapshot_require_idle = config.snapshot_require_idle;
+		let stats_log_interval = config.stats_log_interval;
+		let watchdog = match config.watchdog_threshold {
+			Some(threshold) => Some(Watchdog::start(threshold)?),
+			None => None,
+		};
 		let client = Client::new(config, &spec, db.clone(), miner, io_service.channel())?;
 
 		let snapshot_params = SnapServiceParams {
 			engine: spec.engine.clone(),
 			genesis_block: spec.genesis_block(),
-			db_config: db_config.clone(),
+			db_config: restore_db_config,
 			pruning: pruning,
 			channel: io_service.channel(),
 			snapshot_root: snapshot_path.into(),
 			db_restore: client.clone(),
+			restore_threads: restore_threads,
+			max_pending_chunks: max_pending_chunks,
+			stall_timeout: stall_timeout,
+			keep_failed_restoration: keep_failed_restoration,
+			max_restoration_peers: max_restoration_peers,
+			chunk_store: Arc::new(FlatChunkStore),
 		};
 		let snapshot = Arc::new(SnapshotService::new(snapshot_params)?);
 
 		let client_io = Arc::new(ClientIoHandler {
 			client: client.clone(),
 			snapshot: snapshot.clone(),
+			watchdog: watchdog,
+			log_throttle: LogThrottle::new(Duration::from_secs(WARNING_THROTTLE_SECS)),
+			max_db_size: max_db_size,
+			snapshot_require_idle: snapshot_require_idle,
+			last_import: Mutex::new(Instant::now()),
+			deferred_snapshot: Mutex::new(None),
+			stats_log_interval: stats_log_interval,
+			db_path: client_path.to_owned(),
+			last_stats: Mutex::new((Instant::now(), 0)),
 		});
 		io_service.register_handler(client_io)?;
 
 		spec.engine.register_client(Arc::downgrade(&client) as _);
 
-		let stop_guard = StopGuard::new();
-
-		Ok(ClientService {
-			io_service: Arc::new(io_service),
-			client: client,
-			snapshot: snapshot,
-			database: db,
-			_stop_guard: stop_guard,
-		})
+		Ok((client, snapshot))
 	}
 
 	/// Get general IO interface
@@ -117,116 +592,2061 @@ impl ClientService {
 		self.snapshot.clone()
 	}
 
+	/// Hand private transactions to `private_tx`, which is retried on the IO service's
+	/// timers from now on. Meant to be called once, right after the service started.
+	pub fn enable_private_tx(&self, private_tx: Arc<PrivateTxService>) -> Result<(), IoError> {
+		self.io_service.register_handler(private_tx.clone())?;
+		*self.private_tx.lock().expect("private tx lock is never poisoned; qed") = Some(private_tx);
+		Ok(())
+	}
+
+	/// Get the private transaction service, if one was enabled with `enable_private_tx`.
+	pub fn private_tx_service(&self) -> Option<Arc<PrivateTxService>> {
+		self.private_tx.lock().expect("private tx lock is never poisoned; qed").clone()
+	}
+
+	/// Get the account provider of the miner the service was started with, if it has one.
+	pub fn account_provider(&self) -> Option<Arc<AccountProvider>> {
+		self.client.miner().account_provider()
+	}
+
+	/// Stop the IO service now rather than when the `ClientService` is dropped.
+	///
+	/// This leaves the service half-stopped: no more messages, timers or queued blocks
+	/// and transactions are handled, and neither snapshots nor restorations make
+	/// progress, while `client()` and `db()` remain usable for final read-only work.
+	/// The IO service can't be restarted; drop the `ClientService` to finish shutting down.
+	/// Stopping it again does nothing.
+	pub fn stop_io(&self) -> Result<(), IoError> {
+		self.io_service.try_stop()
+	}
+
 	/// Get network service component
 	pub fn io(&self) -> Arc<IoService<ClientIoMessage>> {
 		self.io_service.clone()
 	}
 
+	/// List the timers registered with the IO service, including those of
+	/// handlers added through `register_io_handler`.
+	pub fn list_timers(&self) -> Vec<TimerInfo> {
+		self.io_service.timers()
+	}
+
 	/// Set the actor to be notified on certain chain events
 	pub fn add_notify(&self, notify: Arc<ChainNotify>) {
 		self.client.add_notify(notify);
 	}
 
+	/// Like `add_notify`, but `new_blocks` is called at most once every `min_interval`, with
+	/// the blocks of all events in between, so that slow observers can keep up during sync.
+	/// Other events aren't delayed and may arrive before the blocks they refer to.
+	/// Pending blocks are delivered before the database is closed.
+	pub fn add_notify_throttled(&self, notify: Arc<ChainNotify>, min_interval: Duration) -> Result<(), ::std::io::Error> {
+		let throttled = ThrottledNotify::start(&notify, min_interval)?;
+		self.client.add_notify(throttled.clone());
+
+		let mut notifies = self.throttled_notify.lock().expect("throttled notify lock is never poisoned; qed");
+		notifies.retain(|n| n.is_alive());
+		notifies.push(throttled);
+		Ok(())
+	}
+
+	/// Stream the logs matching `filter` as the blocks containing them become canonical.
+	/// When a reorganisation retracts blocks, their matching logs are sent again marked as
+	/// removed, before the logs of the blocks enacted instead. Dropping the receiver unsubscribes.
+	pub fn subscribe_logs(&self, filter: LogFilter) -> Receiver<Log> {
+		let (stream, receiver) = LogStream::new(Arc::downgrade(&self.client), filter);
+		let stream = Arc::new(stream);
+		self.client.add_notify(stream.clone());
+
+		let mut streams = self.log_streams.lock().expect("log streams lock is never poisoned; qed");
+		streams.retain(|s| s.is_alive());
+		streams.push(stream);
+		receiver
+	}
+
 	/// Get a handle to the database.
 	pub fn db(&self) -> Arc<KeyValueDB> { self.database.clone() }
-}
 
-/// IO interface for the Client handler
-struct ClientIoHandler {
-	client: Arc<Client>,
-	snapshot: Arc<SnapshotService>,
-}
+	/// Merge `operand` into the value of `key` in a column of the client database, using the
+	/// merge operator the service was started with for that column.
+	pub fn merge(&self, col: u32, key: &[u8], operand: &[u8]) -> Result<(), ::kvdb::Error> {
+		self.database.merge(Some(col), key, operand)
+	}
 
-const CLIENT_TICK_TIMER: TimerToken = 0;
-const SNAPSHOT_TICK_TIMER: TimerToken = 1;
+	/// Read the raw value stored under `key` in the given column.
+	/// Bypasses every cache and consistency check; for debugging only.
+	#[cfg(feature = "debug_db")]
+	pub fn db_get(&self, column: Option<u32>, key: &[u8]) -> Option<Vec<u8>> {
+		match self.database.get(column, key) {
+			Ok(value) => value.map(|v| v.to_vec()),
+			Err(e) => {
+				warn!(target: "service", "Failed to read from column {:?}: {}", column, e);
+				None
+			}
+		}
+	}
 
-const CLIENT_TICK_MS: u64 = 5000;
-const SNAPSHOT_TICK_MS: u64 = 10000;
+	/// Get all raw key-value pairs in the given column whose key starts with `prefix`.
+	/// Bypasses every cache and consistency check; for debugging only.
+	#[cfg(feature = "debug_db")]
+	pub fn db_iter_prefix(&self, column: Option<u32>, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+		KeyValueDB::iter_from_prefix(&*self.database, column, prefix)
+			.take_while(|&(ref key, _)| key.starts_with(prefix))
+			.map(|(key, value)| (key.into_vec(), value.into_vec()))
+			.collect()
+	}
 
-impl IoHandler<ClientIoMessage> for ClientIoHandler {
-	fn initialize(&self, io: &IoContext<ClientIoMessage>) {
-		io.register_timer(CLIENT_TICK_TIMER, CLIENT_TICK_MS).expect("Error registering client timer");
-		io.register_timer(SNAPSHOT_TICK_TIMER, SNAPSHOT_TICK_MS).expect("Error registering snapshot timer");
+	/// Take a snapshot at the given block, optionally labeling it, e.g. "pre-upgrade".
+	pub fn take_snapshot(&self, num: u64, label: Option<&str>) -> Result<(), Error> {
+		self.snapshot.take_labeled_snapshot(&*self.client, num, label)
 	}
 
-	fn timeout(&self, _io: &IoContext<ClientIoMessage>, timer: TimerToken) {
-		match timer {
-			CLIENT_TICK_TIMER => {
-				use ethcore::snapshot::SnapshotService;
-				let snapshot_restoration = if let RestorationStatus::Ongoing{..} = self.snapshot.status() { true } else { false };
-				self.client.tick(snapshot_restoration)
-			},
-			SNAPSHOT_TICK_TIMER => self.snapshot.tick(),
-			_ => warn!("IO service triggered unregistered timer '{}'", timer),
+	/// Sign the manifests of snapshots taken from now on, e.g. with an account of
+	/// `account_provider()`. Signing is off by default; `None` turns it off again.
+	/// The signature is listed by `list_snapshots`.
+	pub fn set_manifest_signer(&self, signer: Option<ManifestSigner>) {
+		self.snapshot.set_manifest_signer(signer)
+	}
+
+	/// The most recent blocks which failed to import after queue verification, oldest
+	/// first, and why. Bounded, so only the latest rejections are kept.
+	pub fn recent_import_rejections(&self) -> Vec<(H256, ImportRejection)> {
+		self.client.recent_import_rejections()
+	}
+
+	/// Rough estimate of how far behind the chain head is, from the age of the best block
+	/// less the engine's target block time, if it has one. Doesn't need any peers, so it's
+	/// useful for isolated nodes, but a chain which stopped producing blocks looks just the same.
+	pub fn estimated_lag(&self) -> Duration {
+		let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+		let head = Duration::from_secs(self.client.chain_info().best_block_timestamp);
+		let age = now.checked_sub(head).unwrap_or_default();
+
+		match self.client.engine().target_block_time() {
+			Some(block_time) => age.checked_sub(block_time).unwrap_or_default(),
+			None => age,
 		}
 	}
 
-	fn message(&self, _io: &IoContext<ClientIoMessage>, net_message: &ClientIoMessage) {
-		use std::thread;
+	/// Get the operational health of the node.
+	pub fn health(&self) -> Health {
+		Health {
+			clock_skew: self.client.clock_skew(),
+		}
+	}
 
-		match *net_message {
-			ClientIoMessage::BlockVerified => { self.client.import_verified_blocks(); }
-			ClientIoMessage::NewTransactions(ref transactions, peer_id) => {
-				self.client.import_queued_transactions(transactions, peer_id);
-			}
-			ClientIoMessage::BeginRestoration(ref manifest) => {
-				if let Err(e) = self.snapshot.init_restore(manifest.clone(), true) {
-					warn!("Failed to initialize snapshot restoration: {}", e);
-				}
-			}
-			ClientIoMessage::FeedStateChunk(ref hash, ref chunk) => self.snapshot.feed_state_chunk(*hash, chunk),
-			ClientIoMessage::FeedBlockChunk(ref hash, ref chunk) => self.snapshot.feed_block_chunk(*hash, chunk),
-			ClientIoMessage::TakeSnapshot(num) => {
-				let client = self.client.clone();
-				let snapshot = self.snapshot.clone();
+	/// Number of side-chain blocks retained within the pruning history.
+	pub fn side_chain_block_count(&self) -> usize {
+		self.client.side_chain_block_count()
+	}
 
-				let res = thread::Builder::new().name("Periodic Snapshot".into()).spawn(move || {
-					if let Err(e) = snapshot.take_snapshot(&*client, num) {
-						warn!("Failed to take snapshot at block #{}: {}", num, e);
-					}
-				});
+	/// Get an account from the state restored so far, while a snapshot restoration is ongoing.
+	/// `None` if the account hasn't been restored yet; chunks are applied in no particular order.
+	pub fn query_restored_state(&self, address: Address) -> Option<BasicAccount> {
+		self.snapshot.query_restored_state(&address)
+	}
 
-				if let Err(e) = res {
-					debug!(target: "snapshot", "Failed to initialize periodic snapshot thread: {:?}", e);
-				}
-			},
-			ClientIoMessage::NewMessage(ref message) => if let Err(e) = self.client.engine().handle_message(message) {
-				trace!(target: "poa", "Invalid message received: {}", e);
-			},
-			_ => {} // ignore other messages
+	/// Roughly how much disk a snapshot at the given block would need, in bytes.
+	/// Walks the whole state, so it isn't cheap either.
+	pub fn estimate_snapshot_size(&self, num: u64) -> Result<u64, Error> {
+		self.snapshot.estimate_snapshot_size(&*self.client, num)
+	}
+
+	/// Re-execute the transactions of the given block on top of its parent's
+	/// state and return their traces. The canonical state is left untouched.
+	/// Fails if the parent's state has been pruned.
+	pub fn replay_block(&self, block: u64) -> Result<BlockTraces, Error> {
+		let id = BlockId::Number(block);
+		if self.client.block_header(id).is_none() {
+			return Err(client::Error::MissingBlock(block).into());
 		}
+
+		let analytics = CallAnalytics {
+			transaction_tracing: true,
+			vm_tracing: false,
+			state_diffing: false,
+		};
+		let transactions = match self.client.replay_block_transactions(id, analytics) {
+			Ok(transactions) => transactions.collect(),
+			Err(CallError::StatePruned) => return Err(client::Error::StateUnavailable(block.saturating_sub(1)).into()),
+			Err(CallError::Execution(e)) => return Err(e.into()),
+			Err(e) => return Err(Error::Util(format!("{}", e).into())),
+		};
+
+		Ok(BlockTraces {
+			block_number: block,
+			transactions: transactions,
+		})
 	}
-}
 
-#[cfg(test)]
-mod tests {
-	use std::sync::Arc;
-	use std::{time, thread};
+	/// Seal a block with the pending transactions immediately, regardless of the
+	/// engine's schedule. Returns the hash of the sealed block, or `None` if this
+	/// node isn't authorized to seal right now. Meant for instant-seal and dev chains.
+	pub fn seal_block_now(&self) -> Result<Option<H256>, Error> {
+		self.client.miner().seal_block_now(&*self.client)
+	}
 
-	use tempdir::TempDir;
+	/// Get the storage trie root of the contract at `address` as of the given block,
+	/// or `None` if there was no account there. Fails if that block's state has been pruned.
+	pub fn storage_root_at(&self, address: Address, block: u64) -> Result<Option<H256>, Error> {
+		let id = BlockId::Number(block);
+		if self.client.block_header(id).is_none() {
+			return Err(client::Error::MissingBlock(block).into());
+		}
 
-	use ethcore::client::ClientConfig;
-	use ethcore::miner::Miner;
-	use ethcore::spec::Spec;
-	use super::*;
+		let state = self.client.state_at(id).ok_or(client::Error::StateUnavailable(block))?;
+		state.storage_root(&address).map_err(|e| Error::from(*e))
+	}
 
-	#[test]
-	fn it_can_be_started() {
-		let tempdir = TempDir::new("").unwrap();
-		let client_path = tempdir.path().join("client");
-		let snapshot_path = tempdir.path().join("snapshot");
+	/// Get a Merkle proof of the account at `address` and the given storage slots of it as of
+	/// the given block, as returned by `eth_getProof` (EIP-1186). Fails if that block's state
+	/// has been pruned.
+	pub fn state_proof(&self, address: Address, storage_keys: &[H256], block: u64) -> Result<StateProof, Error> {
+		self.client.state_proof(&address, storage_keys, block).map_err(Into::into)
+	}
 
-		let spec = Spec::new_test();
-		let service = ClientService::start(
-			ClientConfig::default(),
-			&spec,
-			&client_path,
-			&snapshot_path,
-			tempdir.path(),
-			Arc::new(Miner::with_spec(&spec)),
-		);
-		assert!(service.is_ok());
-		drop(service.unwrap());
-		thread::park_timeout(time::Duration::from_millis(100));
+	/// Get the code of the contract at `address` as of the given block, or `None` if there
+	/// is no code there. Fails if that block's state has been pruned.
+	pub fn code_at(&self, address: Address, block: u64) -> Result<Option<Vec<u8>>, Error> {
+		let id = BlockId::Number(block);
+		if self.client.block_header(id).is_none() {
+			return Err(client::Error::MissingBlock(block).into());
+		}
+
+		let state = self.client.state_at(id).ok_or(client::Error::StateUnavailable(block))?;
+		let code = state.code(&address).map_err(|e| Error::from(*e))?;
+		Ok(code.map(|code| (*code).clone()))
+	}
+
+	/// Get the balances of `addresses` at the head, along with the number of the head block
+	/// they were read at. All balances come from the same state, even if the chain moves on meanwhile.
+	pub fn balances(&self, addresses: &[Address]) -> Result<(u64, Vec<(Address, U256)>), Error> {
+		let info = self.client.chain_info();
+		let state = self.client.state_at(BlockId::Hash(info.best_block_hash))
+			.ok_or(client::Error::StateUnavailable(info.best_block_number))?;
+
+		let mut balances = Vec::with_capacity(addresses.len());
+		for address in addresses {
+			let balance = state.balance(address).map_err(|e| Error::from(*e))?;
+			balances.push((*address, balance));
+		}
+		Ok((info.best_block_number, balances))
+	}
+
+	/// Tell whether a transaction is pending in the pool, mined in the canonical chain or
+	/// unknown. Only indexes are looked up, no block bodies are read. The chain is checked
+	/// after the pool, so a transaction mined in between is reported as mined rather than unknown.
+	pub fn transaction_status(&self, hash: H256) -> TransactionStatus {
+		let best_block = self.client.chain_info().best_block_number;
+		let pending = self.client.miner().transaction(best_block, &hash).is_some();
+
+		let mined = self.client.transaction_address(TransactionId::Hash(hash))
+			.and_then(|address| self.client.block_number(BlockId::Hash(address.block_hash)).map(|number| (number, address.index)));
+
+		match mined {
+			Some((block, index)) => TransactionStatus::Mined { block: block, index: index },
+			None if pending => TransactionStatus::Pending,
+			None => TransactionStatus::Unknown,
+		}
+	}
+
+	/// Get a point-in-time copy of the transactions ready to be mined, with
+	/// their gas prices and nonces. The miner's queue is only locked for the copy.
+	pub fn pending_transactions(&self) -> Vec<PendingTransaction> {
+		let chain_info = self.client.chain_info();
+		self.client.miner().ready_transactions(chain_info.best_block_number, chain_info.best_block_timestamp)
+	}
+
+	/// Write the transactions ready to be mined to `writer` as an RLP list, e.g. to carry
+	/// the pool over a restart with `import_tx_pool`. Conditions attached to local
+	/// transactions aren't kept. Returns the number of transactions written.
+	pub fn export_tx_pool<W: Write>(&self, mut writer: W) -> Result<usize, Error> {
+		let pending = self.pending_transactions();
+		let mut stream = RlpStream::new_list(pending.len());
+		for tx in &pending {
+			stream.append(&*tx.transaction);
+		}
+
+		writer.write_all(&stream.out())?;
+		writer.flush()?;
+		Ok(pending.len())
+	}
+
+	/// Read transactions written by `export_tx_pool` and queue them like transactions from
+	/// the network, so that each one is checked against the current state again. Returns the
+	/// number of transactions which made it into the pool.
+	pub fn import_tx_pool<R: Read>(&self, mut reader: R) -> Result<usize, Error> {
+		let mut bytes = Vec::new();
+		reader.read_to_end(&mut bytes)?;
+		let transactions: Vec<UnverifiedTransaction> = UntrustedRlp::new(&bytes).as_list()?;
+
+		let results = self.client.miner().import_external_transactions(&*self.client, transactions);
+		for result in &results {
+			if let Err(ref e) = *result {
+				debug!(target: "service", "Dropped transaction from the exported pool: {}", e);
+			}
+		}
+		Ok(results.iter().filter(|result| result.is_ok()).count())
+	}
+
+	/// Counts of transactions received from the network, by format.
+	/// Typed transactions don't exist on this chain, so only the signature
+	/// scheme of legacy transactions is told apart.
+	pub fn tx_type_stats(&self) -> TxTypeStats {
+		self.client.tx_type_stats()
+	}
+
+	/// Hits and lookups of the cache of recently seen blocks, sized by `known_block_cache_size`.
+	pub fn known_block_stats(&self) -> KnownBlockStats {
+		self.client.known_block_stats()
+	}
+
+	/// Delays between first receiving transactions and seeing them mined in a canonical block.
+	/// Only transactions received since the service started are counted.
+	pub fn tx_propagation_stats(&self) -> PropagationStats {
+		self.tx_propagation.stats()
+	}
+
+	/// Approximate memory used by the block and transaction queues, per queue.
+	pub fn queue_memory_usage(&self) -> QueueMemory {
+		self.client.queue_memory_usage()
+	}
+
+	/// Queue raw transactions submitted by this node for import.
+	/// With `retain_local_transactions` set they are kept in the pool regardless of its limits.
+	pub fn queue_local_transactions(&self, transactions: Vec<Vec<u8>>) {
+		self.client.queue_transactions(transactions, LOCAL_PEER_ID);
+	}
+
+	/// Resize the client's state cache without restarting, e.g. to trade memory for speed
+	/// while batch indexing. Shrinking evicts the least recently used entries right away.
+	pub fn set_state_cache_size(&self, bytes: usize) {
+		self.client.set_state_cache_size(bytes);
+	}
+
+	/// Set a policy every verified block must pass, on top of the engine's rules,
+	/// before it is imported. Rejected blocks are dropped, and the reason logged.
+	pub fn set_block_filter(&self, filter: Box<Fn(&Header) -> bool + Send + Sync>) {
+		self.client.set_block_filter(filter);
+	}
+
+	/// Evict stale and underpriced transactions from the pool now and report how many
+	/// went for each reason. Complements the eviction done after every imported block.
+	pub fn gc_transaction_pool(&self) -> PoolGcReport {
+		self.client.miner().gc_transaction_pool(&*self.client)
+	}
+
+	/// Get the gas floor target used for blocks sealed by this node.
+	pub fn gas_floor_target(&self) -> U256 {
+		self.client.miner().gas_floor_target()
+	}
+
+	/// Set the gas floor target used for blocks sealed by this node.
+	/// The pending block is prepared again, so the next block sealed uses it.
+	pub fn set_gas_floor_target(&self, target: U256) {
+		let miner = self.client.miner();
+		miner.set_gas_floor_target(target);
+		miner.update_sealing(&*self.client);
+	}
+
+	/// Begin restoring the given snapshot, replacing any ongoing restoration.
+	/// Together with `feed_state_chunk`, `feed_block_chunk` and `finish_restoration`
+	/// this lets the caller drive a restoration without going through the IO
+	/// service. These may be called from any thread.
+	pub fn begin_restoration(&self, manifest: ManifestData) -> Result<(), Error> {
+		self.snapshot.init_restore(manifest, true)
+	}
+
+	/// Feed a state chunk to the ongoing restoration.
+	pub fn feed_state_chunk(&self, hash: H256, chunk: &[u8]) -> Result<(), Error> {
+		self.snapshot.try_feed_state_chunk(hash, chunk)
+	}
+
+	/// Feed a block chunk to the ongoing restoration.
+	pub fn feed_block_chunk(&self, hash: H256, chunk: &[u8]) -> Result<(), Error> {
+		self.snapshot.try_feed_block_chunk(hash, chunk)
+	}
+
+	/// Check that every chunk has been fed and the restored database is in use.
+	pub fn finish_restoration(&self) -> Result<(), Error> {
+		self.snapshot.finish_restoration()
+	}
+
+	/// Restore the local snapshot with the given manifest into a new database
+	/// at `target_path`, e.g. to migrate to it later. The open database is left
+	/// untouched. `target_path` must not exist yet.
+	pub fn restore_to(&self, manifest: &ManifestData, target_path: &Path) -> Result<(), Error> {
+		self.snapshot.restore_to(manifest, target_path)
+	}
+
+	/// Switch the state database to another pruning algorithm by taking a snapshot
+	/// at the best block and restoring it with the new algorithm. Progress is
+	/// reported through the snapshot service's `RestorationStatus`.
+	///
+	/// Only the blocks covered by the snapshot remain afterwards; older ones have to
+	/// be synced again. Persisting the new choice for the next start is up to the caller.
+	pub fn change_pruning_mode(&self, new_mode: Algorithm) -> Result<(), Error> {
+		use ethcore::snapshot::SnapshotService;
+
+		let best_block = self.client.chain_info().best_block_number;
+		info!(target: "service", "Switching pruning mode to {} using a snapshot at #{}", new_mode, best_block);
+
+		self.snapshot.take_snapshot(&*self.client, best_block)?;
+		let manifest = match self.snapshot.manifest() {
+			Some(manifest) => manifest,
+			None => return Err(snapshot::Error::InvalidStartingBlock(BlockId::Number(best_block)).into()),
+		};
+		// taking the snapshot may have been skipped, leaving an older one in place.
+		if manifest.block_number != best_block {
+			return Err(snapshot::Error::InvalidStartingBlock(BlockId::Number(best_block)).into());
+		}
+
+		let old_mode = self.client.pruning_mode();
+		self.client.set_pruning(new_mode);
+		self.snapshot.set_pruning(new_mode);
+
+		let res = self.restore_local_snapshot(&manifest);
+		if res.is_err() {
+			self.client.set_pruning(old_mode);
+			self.snapshot.set_pruning(old_mode);
+		}
+		res
+	}
+
+	// restore the given snapshot from the local snapshot directory, keeping the snapshot.
+	fn restore_local_snapshot(&self, manifest: &ManifestData) -> Result<(), Error> {
+		use std::io::{Error as StdIoError, ErrorKind};
+		use ethcore::snapshot::SnapshotService;
+
+		self.restore_chunks(manifest, false, |hash| self.snapshot.chunk(hash)
+			.ok_or_else(|| StdIoError::new(ErrorKind::NotFound, "snapshot chunk missing").into()))
+	}
+
+	// restore the given snapshot, fetching every chunk with `chunk`.
+	fn restore_chunks<F>(&self, manifest: &ManifestData, recover: bool, chunk: F) -> Result<(), Error>
+		where F: Fn(H256) -> Result<Vec<u8>, Error>
+	{
+		self.snapshot.init_restore(manifest.clone(), recover)?;
+		for &hash in &manifest.state_hashes {
+			self.snapshot.try_feed_state_chunk(hash, &chunk(hash)?)?;
+		}
+		for &hash in &manifest.block_hashes {
+			self.snapshot.try_feed_block_chunk(hash, &chunk(hash)?)?;
+		}
+		self.snapshot.finish_restoration()
+	}
+
+	/// Take a snapshot at the given block and write it to a single file at `dest`,
+	/// which holds the manifest and all (compressed) chunks. Easier to move between
+	/// machines than a snapshot directory.
+	pub fn take_snapshot_archive(&self, block: u64, dest: &Path) -> Result<(), Error> {
+		let progress = snapshot::Progress::default();
+		self.client.take_snapshot(PackedWriter::new(dest)?, BlockId::Number(block), &progress)?;
+
+		info!(target: "snapshot", "Wrote snapshot archive of #{} to {}", block, dest.display());
+		Ok(())
+	}
+
+	/// Restore from a snapshot archive written by `take_snapshot_archive`, replacing
+	/// the current database.
+	pub fn restore_from_archive(&self, path: &Path) -> Result<(), Error> {
+		use std::io::{Error as StdIoError, ErrorKind};
+
+		let reader = PackedReader::new(path)?
+			.ok_or_else(|| Error::from(StdIoError::new(ErrorKind::InvalidData, "not a snapshot archive")))?;
+		let manifest = reader.manifest().clone();
+
+		self.restore_chunks(&manifest, true, |hash| reader.chunk(hash).map_err(Into::into))
+	}
+
+	/// Keep the state at the given block from being pruned. Fails if the state is already gone.
+	///
+	/// This holds back pruning of all state from that block onwards, not just of the block
+	/// itself, so the database keeps growing until the block is unpinned. With `max_db_size`
+	/// set, imports stop once it is reached. Pins are kept across restarts.
+	pub fn pin_state(&self, block: u64) -> Result<(), Error> {
+		self.client.pin_state(block).map_err(Into::into)
+	}
+
+	/// Allow the state at the given block to be pruned again.
+	pub fn unpin_state(&self, block: u64) -> Result<(), Error> {
+		self.client.unpin_state(block).map_err(Into::into)
+	}
+
+	/// List the local snapshots along with their labels.
+	pub fn list_snapshots(&self) -> Vec<LocalSnapshot> {
+		self.snapshot.list_snapshots()
+	}
+
+	/// Take a snapshot of the current chain into `tmp`, restore it into a fresh
+	/// database there and verify the resulting state root.
+	/// Doubles as a storage benchmark. `tmp` should be an empty scratch directory.
+	pub fn snapshot_roundtrip_selftest(&self, tmp: &Path) -> Result<SelftestReport, Error> {
+		let snapshot_dir = tmp.join("snapshot");
+		let progress = snapshot::Progress::default();
+
+		let start = Instant::now();
+		self.client.take_snapshot(LooseWriter::new(snapshot_dir.clone())?, BlockId::Latest, &progress)?;
+		let snapshot_time = start.elapsed();
+
+		let reader = LooseReader::new(snapshot_dir)?;
+		let manifest = reader.manifest().clone();
+
+		let start = Instant::now();
+		let bytes = self.snapshot.verify_restoration(&reader, tmp.join("restoration"))?;
+		let restore_time = start.elapsed();
+
+		info!(target: "snapshot", "Snapshot self-test passed at #{}: {} bytes, taken in {:?}, restored in {:?}",
+			manifest.block_number, bytes, snapshot_time, restore_time);
+
+		Ok(SelftestReport {
+			block_number: manifest.block_number,
+			state_chunks: manifest.state_hashes.len(),
+			block_chunks: manifest.block_hashes.len(),
+			bytes: bytes,
+			snapshot_time: snapshot_time,
+			restore_time: restore_time,
+		})
+	}
+
+	/// Write the RLP of blocks `from..=to` to `writer`, one block at a time.
+	/// Fails if `from` is after `to`, or if any block in the range is missing, e.g. because
+	/// the chain was warp synced. Returns the number of blocks exported.
+	pub fn export_blocks<W: Write>(&self, from: u64, to: u64, mut writer: W) -> Result<u64, Error> {
+		if to < from { return Err(client::Error::InvalidRange(from, to).into()) }
+
+		let mut exported = 0;
+		// `to + 1` would overflow at `u64::MAX`.
+		for number in (from..to).chain(Some(to)) {
+			let block = self.client.block(BlockId::Number(number))
+				.ok_or(client::Error::MissingBlock(number))?;
+
+			writer.write_all(&block.into_inner())?;
+			exported += 1;
+		}
+
+		writer.flush()?;
+		Ok(exported)
+	}
+
+	/// Get the receipts of blocks `from..=to` in one go, for at most `MAX_RECEIPTS_RANGE` blocks.
+	/// Fails if any block in the range is missing or has no stored receipts.
+	pub fn receipts_in_range(&self, from: u64, to: u64) -> Result<Vec<BlockReceipts>, Error> {
+		self.client.receipts_in_range(from, to).map_err(Into::into)
+	}
+
+	/// Get the difficulty of every block in `from..=to`, for at most 10,000 blocks, e.g. to
+	/// chart the network hashrate. Only headers are read. Fails if `from` is after `to`, or if
+	/// any header in the range is missing, e.g. because the chain was warp synced.
+	pub fn difficulty_history(&self, from: u64, to: u64) -> Result<Vec<(u64, U256)>, Error> {
+		if to < from { return Err(client::Error::InvalidRange(from, to).into()) }
+		if to - from >= MAX_DIFFICULTY_RANGE {
+			return Err(client::Error::RangeTooLarge(MAX_DIFFICULTY_RANGE).into());
+		}
+
+		// `to + 1` would overflow at `u64::MAX`.
+		(from..to).chain(Some(to)).map(|number| {
+			let header = self.client.block_header(BlockId::Number(number))
+				.ok_or(client::Error::MissingBlock(number))?;
+			Ok((number, header.difficulty()))
+		}).collect()
+	}
+
+	/// Count the uncles of up to `blocks` canonical blocks ending at the best block, for at
+	/// most 10,000 blocks. Sampling stops early at the genesis block, which has no uncles,
+	/// and at the first missing block, e.g. because the chain was warp synced.
+	pub fn uncle_stats(&self, blocks: u64) -> UncleStats {
+		let best_block = self.client.chain_info().best_block_number;
+		let blocks = cmp::min(blocks, MAX_UNCLE_STATS_BLOCKS);
+
+		let mut stats = UncleStats::default();
+		for number in (best_block.saturating_sub(blocks) + 1..best_block + 1).rev() {
+			match self.client.block_body(BlockId::Number(number)) {
+				Some(body) => stats.total_uncles += body.uncles_count() as u64,
+				None => break,
+			}
+			stats.blocks_sampled += 1;
+		}
+
+		if stats.blocks_sampled > 0 {
+			stats.uncle_rate = stats.total_uncles as f64 / stats.blocks_sampled as f64;
+		}
+		stats
+	}
+
+	/// Write every account in the state of the given block to `writer` as newline-delimited
+	/// JSON, with its balance, nonce, code hash and storage root. Accounts are keyed by
+	/// `address` on a fat database and by `addressHash` otherwise. The state is streamed
+	/// from the trie, but this still reads all of it, so expect it to take a while.
+	/// Fails if the state of the block has been pruned.
+	pub fn dump_state<W: Write>(&self, block: u64, mut writer: W) -> Result<(), Error> {
+		if self.client.block_header(BlockId::Number(block)).is_none() {
+			return Err(client::Error::MissingBlock(block).into());
+		}
+
+		let key_name = if self.client.is_fat_db() { "address" } else { "addressHash" };
+		self.client.for_each_account(block, |key, account| {
+			writeln!(
+				writer,
+				"{{\"{}\":\"0x{}\",\"balance\":\"0x{:x}\",\"nonce\":\"0x{:x}\",\"codeHash\":\"0x{}\",\"storageRoot\":\"0x{}\"}}",
+				key_name,
+				to_hex(key),
+				account.balance,
+				account.nonce,
+				to_hex(&account.code_hash),
+				to_hex(&account.storage_root),
+			)?;
+			Ok(())
+		})?;
+
+		writer.flush()?;
+		Ok(())
+	}
+
+	/// Read consecutive RLP-encoded blocks from `reader` and import them in order.
+	/// Blocks already in the chain or queue are skipped. Returns once every block read
+	/// has been through the verification queue.
+	pub fn import_blocks<R: Read>(&self, mut reader: R) -> Result<ImportSummary, Error> {
+		use std::io::{Error as StdIoError, ErrorKind};
+		use ethcore::block_status::BlockStatus;
+
+		let mut summary = ImportSummary::default();
+		let mut queued = Vec::new();
+
+		loop {
+			// reads may return less than asked for, e.g. from a pipe, so read the
+			// RLP header exactly: its first byte tells how long it is.
+			let mut bytes = vec![0; 1];
+			match reader.read_exact(&mut bytes) {
+				Ok(()) => {},
+				Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => break,
+				Err(e) => return Err(e.into()),
+			}
+			let header_len = rlp_header_len(bytes[0]);
+			bytes.resize(header_len, 0);
+			reader.read_exact(&mut bytes[1..])?;
+
+			let len = PayloadInfo::from(&bytes)?.total();
+			if len < header_len {
+				return Err(StdIoError::new(ErrorKind::InvalidData, "invalid RLP header in block stream").into());
+			}
+			bytes.resize(len, 0);
+			reader.read_exact(&mut bytes[header_len..])?;
+
+			// wait for the queue to drain, which it signals, rather than polling it.
+			if self.client.queue_info().is_full() {
+				self.client.flush_queue();
+			}
+			match self.client.import_block(bytes) {
+				Ok(hash) => queued.push(hash),
+				Err(BlockImportError::Import(ImportError::AlreadyInChain)) |
+				Err(BlockImportError::Import(ImportError::AlreadyQueued)) => summary.skipped += 1,
+				Err(e) => {
+					trace!(target: "import", "Rejected block from stream: {:?}", e);
+					summary.failed += 1;
+				}
+			}
+		}
+
+		self.client.flush_queue();
+		self.client.wait_for_import();
+
+		// only the blocks read here count, not those imported by others meanwhile.
+		for hash in queued {
+			match self.client.block_status(BlockId::Hash(hash)) {
+				BlockStatus::InChain => summary.imported += 1,
+				_ => summary.failed += 1,
+			}
+		}
+		Ok(summary)
+	}
+
+	/// Fully verify a block against the current chain, including executing its transactions,
+	/// without importing it. Nothing is written, so this is safe for blocks from untrusted
+	/// sources. Fails only if `rlp` isn't a block at all.
+	pub fn verify_block(&self, rlp: &[u8]) -> Result<VerificationResult, Error> {
+		let header: Header = UntrustedRlp::new(rlp).val_at(0)?;
+		Ok(match self.client.verify_block_without_import(header, rlp.to_vec()) {
+			Ok(()) => VerificationResult::Valid,
+			Err(rejection) => VerificationResult::Invalid(rejection),
+		})
+	}
+
+	/// Import a single block and return once it's visible to chain and state queries.
+	///
+	/// `import_block` only queues a block: it's verified and committed later, by the IO
+	/// handler or whichever thread flushes the queue. Once committed, which also updates
+	/// the state cache, every query sees it. Flushing the queue alone isn't enough to
+	/// observe that, since the IO handler may have taken the block off the queue and
+	/// still be committing it; this waits for such an import to finish as well.
+	/// Fails if the block was rejected or was only a proposal.
+	pub fn import_and_sync(&self, block: Vec<u8>) -> Result<H256, Error> {
+		let hash = self.client.import_block(block)?;
+		self.client.flush_queue();
+		self.client.wait_for_import();
+
+		match self.client.block_header(BlockId::Hash(hash)) {
+			Some(_) => Ok(hash),
+			None => Err(ImportError::KnownBad.into()),
+		}
+	}
+
+	/// Get the subsystems which are active for this service.
+	pub fn active_features(&self) -> Features {
+		Features {
+			private_tx: self.private_tx_service().is_some(),
+			..self.features
+		}
+	}
+
+	/// Get the activation blocks of the hard forks known to the spec the service was started with.
+	pub fn fork_schedule(&self) -> ForkSchedule {
+		self.fork_schedule.clone()
+	}
+
+	/// Where the time importing the most recent block went, to tell CPU-bound from
+	/// I/O-bound imports. `None` until a block is imported.
+	pub fn last_block_timings(&self) -> Option<BlockTimings> {
+		self.client.last_block_timings()
+	}
+
+	/// Summarize how the service was set up, including whether the previous run shut down cleanly.
+	pub fn config_summary(&self) -> ConfigSummary {
+		ConfigSummary {
+			pruning: self.client.pruning_mode(),
+			..self.summary.clone()
+		}
+	}
+
+	/// Shut the service down in order, logging every phase and how long it took.
+	/// Dropping the service tears it down as well, just silently.
+	pub fn shutdown(self) -> ShutdownReport {
+		let mut report = ShutdownReport::default();
+		let start = Instant::now();
+
+		report.io = shutdown_phase("stopping IO", || self.io_service.stop());
+		report.snapshot = shutdown_phase("finishing snapshot", || {
+			while self.snapshot.is_taking_snapshot() {
+				thread::sleep(Duration::from_millis(100));
+			}
+			if self.snapshot_on_shutdown {
+				self.take_shutdown_snapshot();
+			}
+		});
+		report.flush = shutdown_phase("flushing db", || {
+			if let Err(e) = self.database.flush() {
+				warn!(target: "service", "Failed to flush database: {}", e);
+			}
+		});
+		report.close = shutdown_phase("closing db", move || drop(self));
+
+		info!(target: "service", "Client service shut down in {:?}", start.elapsed());
+		report
+	}
+
+	// take a snapshot at the best block unless the latest one is recent, waiting for it
+	// at most `shutdown_snapshot_timeout`. A snapshot still being written then holds the
+	// database open until it finishes or the process exits, and is never half-installed.
+	fn take_shutdown_snapshot(&self) {
+		use std::sync::mpsc::{self, RecvTimeoutError};
+
+		let best_block = self.client.chain_info().best_block_number;
+		if let Some(manifest) = self.snapshot.latest_manifest() {
+			if best_block.saturating_sub(manifest.block_number) < SHUTDOWN_SNAPSHOT_MIN_BLOCKS {
+				info!(target: "service", "Skipping shutdown snapshot: the latest snapshot at #{} is recent", manifest.block_number);
+				return;
+			}
+		}
+
+		info!(target: "service", "Taking shutdown snapshot at #{}", best_block);
+		let (tx, rx) = mpsc::channel();
+		let client = self.client.clone();
+		let snapshot = self.snapshot.clone();
+		thread::spawn(move || {
+			let _ = tx.send(snapshot.take_snapshot(&*client, best_block));
+		});
+
+		let res = match self.shutdown_snapshot_timeout {
+			Some(timeout) => rx.recv_timeout(timeout),
+			None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+		};
+		match res {
+			Ok(Ok(())) => info!(target: "service", "Took shutdown snapshot at #{}", best_block),
+			Ok(Err(e)) => warn!(target: "service", "Failed to take shutdown snapshot at #{}: {}", best_block, e),
+			Err(RecvTimeoutError::Timeout) => warn!(target: "service", "Shutdown snapshot at #{} didn't finish in time; keeping the previous snapshot", best_block),
+			Err(RecvTimeoutError::Disconnected) => warn!(target: "service", "Shutdown snapshot at #{} panicked", best_block),
+		}
+	}
+}
+
+// the marker file which exists while a service is running on the database at `client_path`.
+fn running_marker_path(client_path: &Path) -> PathBuf {
+	let name = client_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+	client_path.with_file_name(format!("{}.{}", name, RUNNING_MARKER_SUFFIX))
+}
+
+// rename the directory at `path` to a timestamped sibling, returning the new path.
+fn move_aside(path: &Path) -> Result<PathBuf, ::std::io::Error> {
+	let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+	let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+	let target = path.with_file_name(format!("{}.corrupt-{}", name, timestamp));
+	fs::rename(path, &target)?;
+	Ok(target)
+}
+
+// length of the RLP header starting with `first`, including the length of the length.
+fn rlp_header_len(first: u8) -> usize {
+	match first {
+		0xb8...0xbf => 1 + (first - 0xb7) as usize,
+		0xf8...0xff => 1 + (first - 0xf7) as usize,
+		_ => 1,
+	}
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// run one phase of the shutdown, logging when it starts and how long it took.
+fn shutdown_phase<F: FnOnce()>(phase: &str, f: F) -> Duration {
+	info!(target: "service", "Shutdown: {}", phase);
+	let start = Instant::now();
+	f();
+	let elapsed = start.elapsed();
+	info!(target: "service", "Shutdown: {} took {:?}", phase, elapsed);
+	elapsed
+}
+
+impl Drop for ClientService {
+	fn drop(&mut self) {
+		if let Some(ref path) = self.hot_accounts_path {
+			save_hot_accounts(&self.client, path);
+		}
+		self.client.notify_database_closing();
+		// a service dropped while unwinding from a panic didn't shut down cleanly.
+		if thread::panicking() {
+			return;
+		}
+		if let Err(e) = fs::remove_file(&self.running_marker) {
+			warn!(target: "service", "Failed to remove {}: {}", self.running_marker.display(), e);
+		}
+	}
+}
+
+/// IO interface for the Client handler
+struct ClientIoHandler {
+	client: Arc<Client>,
+	snapshot: Arc<SnapshotService>,
+	watchdog: Option<Arc<Watchdog>>,
+	log_throttle: LogThrottle,
+	// database path and the size it must stay below.
+	max_db_size: Option<(PathBuf, u64)>,
+	// how long no block must have been imported before a snapshot is started.
+	snapshot_require_idle: Option<Duration>,
+	// when a block was last imported, or the service started.
+	last_import: Mutex<Instant>,
+	// block of a snapshot requested while imports were going on.
+	deferred_snapshot: Mutex<Option<u64>>,
+	stats_log_interval: Option<Duration>,
+	db_path: PathBuf,
+	// when stats were last logged, and the number of blocks imported by then.
+	last_stats: Mutex<(Instant, usize)>,
+}
+
+impl ClientIoHandler {
+	// log a summary line if the stats interval has passed since the last one.
+	fn log_stats(&self) {
+		let interval = match self.stats_log_interval {
+			Some(interval) => interval,
+			None => return,
+		};
+
+		let mut last = self.last_stats.lock().expect("last stats lock is never poisoned; qed");
+		let elapsed = last.0.elapsed();
+		if elapsed < interval {
+			return;
+		}
+
+		let report = self.client.report();
+		let secs = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1e9;
+		let import_rate = report.blocks_imported.saturating_sub(last.1) as f64 / secs;
+		*last = (Instant::now(), report.blocks_imported);
+
+		let queue = self.client.queue_info();
+		let pool = self.client.miner().status();
+		let tx_types = self.client.tx_type_stats();
+		let db_size = dir_size(&self.db_path).map(|size| size.to_string()).unwrap_or_else(|_| "unknown".into());
+		info!(target: "service",
+			"best=#{} import_rate={:.2}blk/s unverified={} verifying={} verified={} pending_txs={} future_txs={} db_bytes={} received_txs={} undecodable_txs={}",
+			self.client.chain_info().best_block_number,
+			import_rate,
+			queue.unverified_queue_size,
+			queue.verifying_queue_size,
+			queue.verified_queue_size,
+			pool.transactions_in_pending_queue,
+			pool.transactions_in_future_queue,
+			db_size,
+			tx_types.replay_protected + tx_types.unprotected,
+			tx_types.undecodable,
+		);
+	}
+
+	// take the snapshot at `num` now if the node is idle enough, otherwise defer it
+	// until the next snapshot tick finds it idle.
+	fn request_snapshot(&self, num: u64) {
+		let mut deferred = self.deferred_snapshot.lock().expect("deferred snapshot lock is never poisoned; qed");
+		let busy = match self.snapshot_require_idle {
+			Some(idle) => self.last_import.lock().expect("last import lock is never poisoned; qed").elapsed() < idle,
+			None => false,
+		};
+
+		if busy {
+			debug!(target: "snapshot", "Deferring snapshot at block #{} until imports are idle", num);
+			*deferred = Some(deferred.map_or(num, |previous| cmp::max(previous, num)));
+			return;
+		}
+
+		*deferred = None;
+		self.take_snapshot_in_background(num);
+	}
+
+	fn take_snapshot_in_background(&self, num: u64) {
+		let client = self.client.clone();
+		let snapshot = self.snapshot.clone();
+
+		let res = thread::Builder::new().name("Periodic Snapshot".into()).spawn(move || {
+			if let Err(e) = snapshot.take_snapshot(&*client, num) {
+				warn!(target: "snapshot", "Failed to take snapshot at block #{}: {}", num, e);
+			}
+		});
+
+		if let Err(e) = res {
+			debug!(target: "snapshot", "Failed to initialize periodic snapshot thread: {:?}", e);
+		}
+	}
+
+	// warn if recent blocks suggest that the local clock is behind.
+	fn check_clock_skew(&self) {
+		let clock_skew = self.client.clock_skew();
+		if clock_skew >= Duration::from_secs(CLOCK_SKEW_WARN_SECS) {
+			// the message must not vary with the skew for identical warnings to be throttled.
+			self.log_throttle.warn("service", format!("Recent blocks are more than {}s ahead of the local clock; check that the system time is correct", CLOCK_SKEW_WARN_SECS));
+		}
+	}
+
+	// throttle the client if the database has outgrown its limit.
+	fn check_db_size(&self) {
+		if let Some((ref path, max)) = self.max_db_size {
+			match dir_size(path) {
+				Ok(size) => self.client.set_storage_full(size >= max),
+				Err(e) => self.log_throttle.warn("service", format!("Failed to determine database size: {}", e)),
+			}
+		}
+	}
+}
+
+// total size of the files under `path`.
+fn dir_size(path: &Path) -> ::std::io::Result<u64> {
+	let mut size = 0;
+	for entry in fs::read_dir(path)? {
+		let entry = entry?;
+		let metadata = entry.metadata()?;
+		size += match metadata.is_dir() {
+			true => dir_size(&entry.path())?,
+			false => metadata.len(),
+		};
+	}
+	Ok(size)
+}
+
+// save the most recently used accounts of the state cache to `path`.
+fn save_hot_accounts(client: &Client, path: &Path) {
+	let mut accounts = client.cached_accounts();
+	let skip = accounts.len().saturating_sub(MAX_HOT_ACCOUNTS);
+	accounts.drain(..skip);
+
+	let encoded = ::rlp::encode_list::<Address, _>(&accounts);
+	match fs::File::create(path).and_then(|mut file| file.write_all(&encoded)) {
+		Ok(()) => debug!(target: "service", "Saved {} hot accounts", accounts.len()),
+		Err(e) => warn!(target: "service", "Failed to save hot accounts: {}", e),
+	}
+}
+
+// read the accounts saved by `save_hot_accounts` back into the caches on a separate thread.
+// best-effort: the client isn't kept alive for it and failures are only logged.
+fn warm_caches(client: Weak<Client>, path: PathBuf) {
+	let warm = move || {
+		let mut encoded = Vec::new();
+		if let Err(e) = fs::File::open(&path).and_then(|mut file| file.read_to_end(&mut encoded)) {
+			debug!(target: "service", "No hot accounts to warm the caches with: {}", e);
+			return;
+		}
+
+		let accounts = match ::rlp::UntrustedRlp::new(&encoded).as_list::<Address>() {
+			Ok(accounts) => accounts,
+			Err(e) => {
+				warn!(target: "service", "Failed to read hot accounts: {}", e);
+				return;
+			}
+		};
+
+		let start = Instant::now();
+		let mut cached = 0;
+		for batch in accounts.chunks(WARM_CACHE_BATCH) {
+			let client = match client.upgrade() {
+				Some(client) => client,
+				None => return,
+			};
+
+			match client.prefetch_accounts(batch) {
+				Ok(n) => cached += n,
+				Err(e) => {
+					warn!(target: "service", "Failed to warm caches: {}", e);
+					return;
+				}
+			}
+		}
+		info!(target: "service", "Warmed caches with {} of {} hot accounts in {:?}", cached, accounts.len(), start.elapsed());
+	};
+
+	if let Err(e) = thread::Builder::new().name("Cache Warmup".into()).spawn(warm) {
+		warn!(target: "service", "Failed to start warming caches: {}", e);
+	}
+}
+
+// name of the message variant, as reported by the watchdog.
+fn message_name(message: &ClientIoMessage) -> &'static str {
+	match *message {
+		ClientIoMessage::NewChainHead => "NewChainHead",
+		ClientIoMessage::BlockVerified => "BlockVerified",
+		ClientIoMessage::NewTransactions(..) => "NewTransactions",
+		ClientIoMessage::BeginRestoration(..) => "BeginRestoration",
+		ClientIoMessage::FeedStateChunk(..) => "FeedStateChunk",
+		ClientIoMessage::FeedStateChunkFrom(..) => "FeedStateChunkFrom",
+		ClientIoMessage::FeedBlockChunk(..) => "FeedBlockChunk",
+		ClientIoMessage::TakeSnapshot(..) => "TakeSnapshot",
+		ClientIoMessage::NewMessage(..) => "NewMessage",
+	}
+}
+
+// a snapshot fewer than this many blocks behind the best block is recent enough
+// to skip the one of `snapshot_on_shutdown`.
+const SHUTDOWN_SNAPSHOT_MIN_BLOCKS: u64 = 100;
+// maximum number of blocks read by `difficulty_history`.
+const MAX_DIFFICULTY_RANGE: u64 = 10_000;
+// maximum number of blocks sampled by `uncle_stats`.
+const MAX_UNCLE_STATS_BLOCKS: u64 = 10_000;
+// clock skew from which on a warning is logged.
+const CLOCK_SKEW_WARN_SECS: u64 = 10;
+// suffix of the file next to the client path which exists while a service is running on it.
+const RUNNING_MARKER_SUFFIX: &'static str = "running";
+// file under the client path the most recently used accounts are saved to.
+const HOT_ACCOUNTS_FILE: &'static str = "hot_accounts";
+// maximum number of accounts saved to warm the caches with.
+const MAX_HOT_ACCOUNTS: usize = 10_000;
+// accounts prefetched at once while warming the caches.
+const WARM_CACHE_BATCH: usize = 256;
+
+const CLIENT_TICK_TIMER: TimerToken = 0;
+const SNAPSHOT_TICK_TIMER: TimerToken = 1;
+
+const CLIENT_TICK_MS: u64 = 5000;
+// identical warnings from message handling are logged at most once in this period.
+const WARNING_THROTTLE_SECS: u64 = 60;
+const SNAPSHOT_TICK_MS: u64 = 10000;
+
+impl IoHandler<ClientIoMessage> for ClientIoHandler {
+	fn initialize(&self, io: &IoContext<ClientIoMessage>) {
+		io.register_unique_timer(CLIENT_TICK_TIMER, CLIENT_TICK_MS).expect("Error registering client timer");
+		io.register_unique_timer(SNAPSHOT_TICK_TIMER, SNAPSHOT_TICK_MS).expect("Error registering snapshot timer");
+	}
+
+	fn timeout(&self, _io: &IoContext<ClientIoMessage>, timer: TimerToken) {
+		match timer {
+			CLIENT_TICK_TIMER => {
+				use ethcore::snapshot::SnapshotService;
+				let snapshot_restoration = if let RestorationStatus::Ongoing{..} = self.snapshot.status() { true } else { false };
+				self.client.tick(snapshot_restoration);
+				self.client.engine().on_tick(&*self.client);
+				self.check_db_size();
+				self.check_clock_skew();
+				self.log_stats();
+				self.log_throttle.flush();
+			},
+			SNAPSHOT_TICK_TIMER => {
+				if self.snapshot.check_stalled_restoration() {
+					self.client.notify_restoration_stalled();
+				}
+				self.snapshot.tick();
+				let deferred = *self.deferred_snapshot.lock().expect("deferred snapshot lock is never poisoned; qed");
+				if let Some(num) = deferred {
+					self.request_snapshot(num);
+				}
+			},
+			_ => warn!(target: "service", "IO service triggered unregistered timer '{}'", timer),
+		}
+	}
+
+	fn message(&self, _io: &IoContext<ClientIoMessage>, net_message: &ClientIoMessage) {
+		let _guard = self.watchdog.as_ref().and_then(|watchdog| watchdog.enter(message_name(net_message)));
+
+		match *net_message {
+			ClientIoMessage::BlockVerified => {
+				let imported = self.client.import_verified_blocks();
+				trace!(target: "import", "Imported {} verified blocks", imported);
+				if imported > 0 {
+					*self.last_import.lock().expect("last import lock is never poisoned; qed") = Instant::now();
+				}
+			}
+			ClientIoMessage::NewTransactions(ref transactions, peer_id) => {
+				let imported = self.client.import_queued_transactions(transactions, peer_id);
+				trace!(target: "import", "Imported {} transactions from peer {}", imported, peer_id);
+			}
+			ClientIoMessage::BeginRestoration(ref manifest) => {
+				if let Err(e) = self.snapshot.init_restore(manifest.clone(), true) {
+					self.log_throttle.warn("snapshot", format!("Failed to initialize snapshot restoration: {}", e));
+				}
+			}
+			ClientIoMessage::FeedStateChunk(ref hash, ref chunk) => self.snapshot.feed_queued_state_chunk(*hash, chunk),
+			ClientIoMessage::FeedStateChunkFrom(peer, ref hash, ref chunk) => self.snapshot.feed_queued_state_chunk_from(peer, *hash, chunk),
+			ClientIoMessage::FeedBlockChunk(ref hash, ref chunk) => self.snapshot.feed_queued_block_chunk(*hash, chunk),
+			ClientIoMessage::TakeSnapshot(num) => self.request_snapshot(num),
+			ClientIoMessage::NewMessage(ref message, peer_id) => match self.client.engine().handle_message(message) {
+				Ok(()) => {},
+				// the message is validly signed, so the peer may just have relayed it.
+				Err(EngineError::Equivocation(validator, height)) => {
+					warn!(target: "poa", "Validator {} signed conflicting messages at height {}", validator, height);
+					self.client.notify_equivocation_detected(validator, height);
+				},
+				Err(e) => {
+					trace!(target: "poa", "Invalid message received from peer {}: {}", peer_id, e);
+					self.client.notify_invalid_consensus_message(peer_id, format!("{}", e));
+				},
+			},
+			_ => {} // ignore other messages
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::{time, thread};
+
+	use tempdir::TempDir;
+
+	use ethcore::client::ClientConfig;
+	use ethcore::miner::Miner;
+	use ethcore::spec::Spec;
+	use transaction::{Action, SignedTransaction, Transaction};
+	use super::*;
+
+	// start a service for `spec` with its databases in `tempdir`.
+	// poll `condition` until it holds, for at most five seconds. Returns whether it held.
+	fn wait_for<F: Fn() -> bool>(condition: F) -> bool {
+		let deadline = Instant::now() + time::Duration::from_secs(5);
+		while !condition() {
+			if Instant::now() > deadline {
+				return false;
+			}
+			thread::sleep(time::Duration::from_millis(10));
+		}
+		true
+	}
+
+	fn start_service(tempdir: &TempDir, spec: &Spec, config: ClientConfig) -> ClientService {
+		ClientService::start(
+			config,
+			spec,
+			&tempdir.path().join("client"),
+			&tempdir.path().join("snapshot"),
+			tempdir.path(),
+			Arc::new(Miner::with_spec(spec)),
+		).unwrap()
+	}
+
+	// a transfer from an account funded by the test specs, with a fake signature.
+	fn fake_transaction(nonce: u64) -> SignedTransaction {
+		Transaction {
+			nonce: nonce.into(),
+			gas_price: 0.into(),
+			gas: 21000.into(),
+			action: Action::Call(Address::default()),
+			value: 0.into(),
+			data: Vec::new(),
+		}.fake_sign(Address::from(1))
+	}
+
+	#[test]
+	fn it_can_be_started() {
+		let tempdir = TempDir::new("").unwrap();
+		let client_path = tempdir.path().join("client");
+		let snapshot_path = tempdir.path().join("snapshot");
+
+		let spec = Spec::new_test();
+		let service = ClientService::start(
+			ClientConfig::default(),
+			&spec,
+			&client_path,
+			&snapshot_path,
+			tempdir.path(),
+			Arc::new(Miner::with_spec(&spec)),
+		);
+		assert!(service.is_ok());
+		let service = service.unwrap();
+		assert!(!service.active_features().private_tx);
+		thread::park_timeout(time::Duration::from_millis(100));
+		assert_eq!(service.list_timers().len(), 2);
+		drop(service);
+		thread::park_timeout(time::Duration::from_millis(100));
+	}
+
+	#[test]
+	fn enables_private_transactions() {
+		use private_tx::{Encryptor, PrivateTxProvider};
+
+		struct Provider;
+		struct KeyServer;
+
+		impl Encryptor for KeyServer {
+			fn connect(&self) -> Result<(), String> {
+				Ok(())
+			}
+		}
+
+		impl PrivateTxProvider for Provider {
+			fn on_private_transaction_queued(&self, _transaction: &UnverifiedTransaction) -> Result<(), String> {
+				Err("unavailable".into())
+			}
+		}
+
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+		assert!(service.private_tx_service().is_none());
+
+		service.enable_private_tx(Arc::new(PrivateTxService::new(Arc::new(Provider), Arc::new(KeyServer), 1))).unwrap();
+		assert!(service.active_features().private_tx);
+
+		service.private_tx_service().unwrap().queue((*fake_transaction(0)).clone());
+		assert_eq!(service.private_tx_service().unwrap().quarantined().len(), 1);
+	}
+
+	#[test]
+	fn it_can_be_started_asynchronously() {
+		use futures::Future;
+
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let miner = Arc::new(Miner::with_spec(&spec));
+
+		let service = ClientService::start_async(
+			ClientConfig::default(),
+			spec,
+			tempdir.path().join("client"),
+			tempdir.path().join("snapshot"),
+			tempdir.path().to_owned(),
+			miner,
+		).wait();
+		assert!(service.is_ok());
+	}
+
+	#[cfg(feature = "debug_db")]
+	#[test]
+	fn reads_raw_columns() {
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		let mut batch = service.db().transaction();
+		batch.put(db::COL_EXTRA, b"debug_a", b"1");
+		batch.put(db::COL_EXTRA, b"debug_b", b"2");
+		batch.put(db::COL_EXTRA, b"other", b"3");
+		service.db().write(batch).unwrap();
+
+		assert_eq!(service.db_get(db::COL_EXTRA, b"debug_a"), Some(b"1".to_vec()));
+		assert_eq!(service.db_get(db::COL_EXTRA, b"missing"), None);
+		assert_eq!(service.db_iter_prefix(db::COL_EXTRA, b"debug_"), vec![
+			(b"debug_a".to_vec(), b"1".to_vec()),
+			(b"debug_b".to_vec(), b"2".to_vec()),
+		]);
+	}
+
+	#[test]
+	fn exposes_account_provider() {
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let accounts = Arc::new(AccountProvider::transient_provider());
+
+		let service = ClientService::start(
+			ClientConfig::default(),
+			&spec,
+			&tempdir.path().join("client"),
+			&tempdir.path().join("snapshot"),
+			tempdir.path(),
+			Arc::new(Miner::with_spec_and_accounts(&spec, Some(accounts.clone()))),
+		).unwrap();
+
+		assert!(Arc::ptr_eq(&service.account_provider().unwrap(), &accounts));
+	}
+
+	#[test]
+	fn shutdown_releases_database() {
+		let tempdir = TempDir::new("").unwrap();
+		let client_path = tempdir.path().join("client");
+
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		let report = service.shutdown();
+		assert!(report.total() >= report.close);
+
+		let db_config = DatabaseConfig::with_columns(db::NUM_COLUMNS);
+		assert!(Database::open(&db_config, client_path.to_str().unwrap()).is_ok());
+	}
+
+	#[test]
+	fn takes_snapshot_on_shutdown() {
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let start = || {
+			let mut config = ClientConfig::default();
+			config.snapshot_on_shutdown = true;
+			start_service(&tempdir, &spec, config)
+		};
+
+		let service = start();
+		assert!(service.snapshot_service().latest_manifest().is_none());
+		service.shutdown();
+
+		let service = start();
+		assert_eq!(service.snapshot_service().latest_manifest().unwrap().block_number, 0);
+	}
+
+	#[test]
+	fn client_is_usable_after_stopping_io() {
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		service.stop_io().unwrap();
+		assert!(service.list_timers().is_empty());
+		assert_eq!(service.client().chain_info().best_block_number, 0);
+		service.stop_io().unwrap();
+	}
+
+	#[test]
+	fn estimates_lag_from_best_block_age() {
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		// the genesis block is from 1970.
+		assert!(service.estimated_lag() > time::Duration::from_secs(365 * 24 * 3600));
+	}
+
+	#[test]
+	fn failed_start_releases_database() {
+		use std::fs::File;
+
+		let tempdir = TempDir::new("").unwrap();
+		let client_path = tempdir.path().join("client");
+
+		// a file where the snapshot directory should be makes the snapshot
+		// service fail after the database has been opened.
+		let bad_snapshot_path = tempdir.path().join("not_a_dir");
+		File::create(&bad_snapshot_path).unwrap();
+
+		let spec = Spec::new_test();
+		let service = ClientService::start(
+			ClientConfig::default(),
+			&spec,
+			&client_path,
+			&bad_snapshot_path,
+			tempdir.path(),
+			Arc::new(Miner::with_spec(&spec)),
+		);
+		assert!(service.is_err());
+		assert!(!running_marker_path(&client_path).exists());
+
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+		assert!(!service.config_summary().unclean_shutdown_recovered);
+	}
+
+	#[test]
+	fn failed_handler_registration_releases_database() {
+		let tempdir = TempDir::new("").unwrap();
+		let client_path = tempdir.path().join("client");
+		let spec = Spec::new_test();
+
+		// handlers can't be registered once the IO service is stopped, which fails
+		// the start after the database has been opened and the client created.
+		let io_service = IoService::<ClientIoMessage>::start().unwrap();
+		io_service.stop();
+		let service = ClientService::start_on(
+			io_service,
+			ClientConfig::default(),
+			&spec,
+			&client_path,
+			&tempdir.path().join("snapshot"),
+			Arc::new(Miner::with_spec(&spec)),
+			HashMap::new(),
+		);
+		assert!(service.is_err());
+		assert!(!running_marker_path(&client_path).exists());
+
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+		assert!(!service.config_summary().unclean_shutdown_recovered);
+	}
+
+	#[test]
+	fn notifies_before_closing_database() {
+		use std::sync::Mutex;
+
+		struct DbHolder(Mutex<Option<Arc<KeyValueDB>>>);
+
+		impl ChainNotify for DbHolder {
+			fn database_closing(&self) {
+				self.0.lock().unwrap().take();
+			}
+		}
+
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		let holder = Arc::new(DbHolder(Mutex::new(Some(service.db()))));
+		service.add_notify(holder.clone());
+
+		drop(service);
+		assert!(holder.0.lock().unwrap().is_none());
+	}
+
+	#[test]
+	fn reports_invalid_consensus_messages() {
+		use std::sync::Mutex;
+
+		struct InvalidMessages(Mutex<Vec<usize>>);
+
+		impl ChainNotify for InvalidMessages {
+			fn invalid_consensus_message(&self, peer_id: usize, _reason: String) {
+				self.0.lock().unwrap().push(peer_id);
+			}
+		}
+
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		let invalid = Arc::new(InvalidMessages(Mutex::new(Vec::new())));
+		service.add_notify(invalid.clone());
+
+		// the test engine doesn't expect any consensus messages.
+		service.io().send_message(ClientIoMessage::NewMessage(vec![0xc0], 7)).unwrap();
+		assert!(wait_for(|| !invalid.0.lock().unwrap().is_empty()));
+		assert_eq!(*invalid.0.lock().unwrap(), vec![7]);
+	}
+
+	#[test]
+	fn exports_blocks() {
+		let tempdir = TempDir::new("").unwrap();
+
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		let mut out = Vec::new();
+		assert_eq!(service.export_blocks(0, 0, &mut out).unwrap(), 1);
+		assert_eq!(out, spec.genesis_block());
+
+		assert!(service.export_blocks(0, 1, Vec::new()).is_err());
+		assert!(service.export_blocks(1, 0, Vec::new()).is_err());
+		assert!(service.export_blocks(u64::max_value(), u64::max_value(), Vec::new()).is_err());
+	}
+
+	#[test]
+	fn queries_historical_storage_root() {
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		assert!(service.storage_root_at(Address::from(1), 0).unwrap().is_some());
+		assert_eq!(service.storage_root_at(Address::from(0xdead), 0).unwrap(), None);
+		assert!(service.storage_root_at(Address::from(1), 1).is_err());
+	}
+
+	#[test]
+	fn proves_account_and_storage() {
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		let proof = service.state_proof(Address::from(1), &[H256::from(7)], 0).unwrap();
+		assert!(!proof.account_proof.is_empty());
+		assert_eq!(proof.storage_proof.len(), 1);
+		assert_eq!(proof.storage_proof[0].key, H256::from(7));
+		assert_eq!(proof.storage_proof[0].value, H256::zero());
+		assert!(service.state_proof(Address::from(1), &[], 1).is_err());
+	}
+
+	#[test]
+	fn queries_historical_code() {
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_validator_safe_contract();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		assert!(!service.code_at(Address::from(5), 0).unwrap().unwrap().is_empty());
+		assert_eq!(service.code_at(Address::from(1), 0).unwrap(), None);
+		assert!(service.code_at(Address::from(5), 1).is_err());
+	}
+
+	#[test]
+	fn imported_block_is_visible_immediately() {
+		use ethcore::client::PrepareOpenBlock;
+
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		let client = service.client();
+		let block = client.prepare_open_block(Address::default(), (3141562.into(), 31415620.into()), vec![])
+			.close_and_lock()
+			.seal(&*spec.engine, vec![])
+			.unwrap();
+
+		let hash = service.import_and_sync(block.rlp_bytes()).unwrap();
+		assert_eq!(client.chain_info().best_block_hash, hash);
+		assert!(service.import_and_sync(block.rlp_bytes()).is_err());
+	}
+
+	#[test]
+	fn verifies_block_without_importing() {
+		use ethcore::client::PrepareOpenBlock;
+
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		let client = service.client();
+		let bytes = client.prepare_open_block(Address::default(), (3141562.into(), 31415620.into()), vec![])
+			.close_and_lock()
+			.seal(&*spec.engine, vec![])
+			.unwrap()
+			.rlp_bytes();
+
+		assert_eq!(service.verify_block(&bytes).unwrap(), VerificationResult::Valid);
+		assert_eq!(client.chain_info().best_block_number, 0);
+
+		let rlp = UntrustedRlp::new(&bytes);
+		let mut header: Header = rlp.val_at(0).unwrap();
+		header.set_parent_hash(H256::from(0xdead));
+		let mut orphan = RlpStream::new_list(3);
+		orphan.append(&header);
+		orphan.append_raw(rlp.at(1).unwrap().as_raw(), 1);
+		orphan.append_raw(rlp.at(2).unwrap().as_raw(), 1);
+		assert_eq!(
+			service.verify_block(&orphan.out()).unwrap(),
+			VerificationResult::Invalid(ImportRejection::UnknownParent(H256::from(0xdead)))
+		);
+
+		assert!(service.verify_block(&[0xc0]).is_err());
+	}
+
+	#[test]
+	fn records_block_timings() {
+		use ethcore::client::PrepareOpenBlock;
+
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+		assert!(service.last_block_timings().is_none());
+
+		let block = service.client().prepare_open_block(Address::default(), (3141562.into(), 31415620.into()), vec![])
+			.close_and_lock()
+			.seal(&*spec.engine, vec![])
+			.unwrap();
+		service.import_and_sync(block.rlp_bytes()).unwrap();
+
+		let timings = service.last_block_timings().unwrap();
+		assert!(timings.total >= timings.execution + timings.state_write);
+	}
+
+	#[test]
+	fn dumps_state_as_json_lines() {
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		let mut out = Vec::new();
+		service.dump_state(0, &mut out).unwrap();
+		let dump = String::from_utf8(out).unwrap();
+
+		assert_eq!(dump.lines().count(), 5);
+		assert!(dump.lines().all(|line| line.starts_with("{\"addressHash\":\"0x") && line.contains("\"nonce\":\"0x100000\"")));
+
+		assert!(service.dump_state(1, Vec::new()).is_err());
+	}
+
+	#[test]
+	fn reads_balances_at_head() {
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		let funded: Address = "0000000000000000000000000000000000000001".into();
+		let empty: Address = "00000000000000000000000000000000000000ff".into();
+		let (number, balances) = service.balances(&[funded, empty]).unwrap();
+		assert_eq!(number, 0);
+		assert_eq!(balances, vec![(funded, 1.into()), (empty, 0.into())]);
+	}
+
+	#[test]
+	fn tells_transaction_status() {
+		let transaction = fake_transaction(0);
+		let hash = transaction.hash();
+
+		let status_after_import = |spec: Spec| {
+			let tempdir = TempDir::new("").unwrap();
+			let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+			assert_eq!(service.transaction_status(hash), TransactionStatus::Unknown);
+			let client = service.client();
+			client.miner().import_own_transaction(&*client, PendingTransaction::new(transaction.clone(), None)).unwrap();
+			service.seal_block_now().unwrap();
+			service.transaction_status(hash)
+		};
+
+		assert_eq!(status_after_import(Spec::new_test()), TransactionStatus::Pending);
+		assert_eq!(status_after_import(Spec::new_instant()), TransactionStatus::Mined { block: 1, index: 0 });
+	}
+
+	#[test]
+	fn exports_and_reimports_tx_pool() {
+		let transaction = fake_transaction(0);
+
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+		let client = service.client();
+		client.miner().import_own_transaction(&*client, PendingTransaction::new(transaction.clone(), None)).unwrap();
+
+		let mut exported = Vec::new();
+		assert_eq!(service.export_tx_pool(&mut exported).unwrap(), 1);
+		let decoded: Vec<UnverifiedTransaction> = UntrustedRlp::new(&exported).as_list().unwrap();
+		assert_eq!(decoded, vec![(*transaction).clone()]);
+
+		// checked again on import, where the fake signature doesn't pass.
+		assert_eq!(service.import_tx_pool(&exported[..]).unwrap(), 0);
+		assert!(service.import_tx_pool(&[0x01u8][..]).is_err());
+	}
+
+	#[test]
+	fn reads_receipts_in_range() {
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_instant();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		let transaction = fake_transaction(0);
+		let client = service.client();
+		client.miner().import_own_transaction(&*client, PendingTransaction::new(transaction, None)).unwrap();
+		service.seal_block_now().unwrap();
+
+		let receipts = service.receipts_in_range(0, 1).unwrap();
+		assert_eq!(receipts.iter().map(|r| r.receipts.len()).collect::<Vec<_>>(), vec![0, 1]);
+		assert!(service.receipts_in_range(1, 2).is_err());
+		assert!(service.receipts_in_range(0, client::MAX_RECEIPTS_RANGE).is_err());
+		assert!(service.receipts_in_range(1, 0).is_err());
+		assert!(service.receipts_in_range(u64::max_value(), u64::max_value()).is_err());
+	}
+
+	#[test]
+	fn reports_no_clock_skew_for_past_blocks() {
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		assert_eq!(service.health(), Health { clock_skew: Duration::from_secs(0) });
+	}
+
+	#[test]
+	fn streams_matching_logs() {
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_instant();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		let all = service.subscribe_logs(LogFilter::default());
+		let none = service.subscribe_logs(LogFilter { address: Some(vec![Address::from(0xdead)]), topics: vec![] });
+
+		// creation code running LOG0 on empty memory.
+		let transaction = Transaction {
+			nonce: 0.into(),
+			gas_price: 0.into(),
+			gas: 100_000.into(),
+			action: Action::Create,
+			value: 0.into(),
+			data: vec![0x60, 0x00, 0x60, 0x00, 0xa0],
+		}.fake_sign(Address::from(1));
+		let client = service.client();
+		client.miner().import_own_transaction(&*client, PendingTransaction::new(transaction.clone(), None)).unwrap();
+		service.seal_block_now().unwrap();
+
+		let log = all.try_recv().unwrap();
+		assert!(!log.removed);
+		assert_eq!(log.entry.block_number, 1);
+		assert_eq!(log.entry.transaction_hash, transaction.hash());
+		assert!(all.try_recv().is_err());
+		assert!(none.try_recv().is_err());
+	}
+
+	#[test]
+	fn reads_difficulty_history() {
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		let genesis = ::ethcore::encoded::Block::new(spec.genesis_block());
+		assert_eq!(service.difficulty_history(0, 0).unwrap(), vec![(0, genesis.difficulty())]);
+		assert!(service.difficulty_history(0, 1).is_err());
+		assert!(service.difficulty_history(0, MAX_DIFFICULTY_RANGE).is_err());
+		assert!(service.difficulty_history(1, 0).is_err());
+		assert!(service.difficulty_history(u64::max_value(), u64::max_value()).is_err());
+	}
+
+	#[test]
+	fn counts_uncles_of_recent_blocks() {
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_instant();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		assert_eq!(service.uncle_stats(10), UncleStats::default());
+
+		let transaction = fake_transaction(0);
+		let client = service.client();
+		client.miner().import_own_transaction(&*client, PendingTransaction::new(transaction, None)).unwrap();
+		service.seal_block_now().unwrap();
+
+		assert_eq!(service.uncle_stats(10), UncleStats { total_uncles: 0, blocks_sampled: 1, uncle_rate: 0.0 });
+		assert_eq!(service.uncle_stats(0).blocks_sampled, 0);
+	}
+
+	#[test]
+	fn imports_known_blocks_as_skipped() {
+		let tempdir = TempDir::new("").unwrap();
+
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		let genesis = spec.genesis_block();
+		let summary = service.import_blocks(&genesis[..]).unwrap();
+		assert_eq!(summary, ImportSummary { imported: 0, skipped: 1, failed: 0 });
+	}
+
+	#[test]
+	fn imports_blocks_from_short_reads() {
+		// hands out a single byte per read, like a slow pipe.
+		struct ByteReader<'a>(&'a [u8]);
+
+		impl<'a> Read for ByteReader<'a> {
+			fn read(&mut self, buf: &mut [u8]) -> ::std::io::Result<usize> {
+				if self.0.is_empty() || buf.is_empty() { return Ok(0) }
+				buf[0] = self.0[0];
+				self.0 = &self.0[1..];
+				Ok(1)
+			}
+		}
+
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		let genesis = spec.genesis_block();
+		let summary = service.import_blocks(ByteReader(&genesis)).unwrap();
+		assert_eq!(summary, ImportSummary { imported: 0, skipped: 1, failed: 0 });
+		assert!(service.import_blocks(ByteReader(&genesis[..genesis.len() - 1])).is_err());
+	}
+
+	#[test]
+	fn refuses_recently_seen_blocks_from_cache() {
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let mut config = ClientConfig::default();
+		config.known_block_cache_size = 16;
+		let service = start_service(&tempdir, &spec, config);
+
+		let genesis = spec.genesis_block();
+		let mut blocks = genesis.clone();
+		blocks.extend_from_slice(&genesis);
+		let summary = service.import_blocks(&blocks[..]).unwrap();
+		assert_eq!(summary, ImportSummary { imported: 0, skipped: 2, failed: 0 });
+
+		let stats = service.known_block_stats();
+		assert_eq!(stats, KnownBlockStats { hits: 1, lookups: 2 });
+		assert_eq!(stats.hit_rate(), 0.5);
+	}
+
+	#[test]
+	fn snapshot_roundtrip_selftest_passes() {
+		let tempdir = TempDir::new("").unwrap();
+
+		let spec = Spec::new_null();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		let report = service.snapshot_roundtrip_selftest(&tempdir.path().join("selftest")).unwrap();
+		assert_eq!(report.block_number, 0);
+		assert!(report.state_chunks > 0);
+	}
+
+	#[test]
+	fn replaying_unknown_block_fails() {
+		let tempdir = TempDir::new("").unwrap();
+
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		match service.replay_block(5) {
+			Err(Error::Client(client::Error::MissingBlock(5))) => {},
+			other => panic!("unexpected replay result: {:?}", other),
+		}
+	}
+
+	#[test]
+	fn sets_gas_floor_target() {
+		let tempdir = TempDir::new("").unwrap();
+
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		service.set_gas_floor_target(4_700_000.into());
+		assert_eq!(service.gas_floor_target(), 4_700_000.into());
+	}
+
+	#[test]
+	fn rejects_miner_for_other_engine() {
+		let tempdir = TempDir::new("").unwrap();
+		let client_path = tempdir.path().join("client");
+		let snapshot_path = tempdir.path().join("snapshot");
+
+		let spec = Spec::new_test();
+		let service = ClientService::start(
+			ClientConfig::default(),
+			&spec,
+			&client_path,
+			&snapshot_path,
+			tempdir.path(),
+			Arc::new(Miner::with_spec(&Spec::new_null())),
+		);
+
+		match service {
+			Err(Error::MinerEngineMismatch { .. }) => {},
+			_ => panic!("service should not start with a miner for another engine"),
+		}
+	}
+
+	#[test]
+	fn lists_labeled_snapshot() {
+		let tempdir = TempDir::new("").unwrap();
+
+		let spec = Spec::new_null();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		assert!(service.list_snapshots().is_empty());
+		service.take_snapshot(0, Some("pre-upgrade")).unwrap();
+
+		let snapshots = service.list_snapshots();
+		assert_eq!(snapshots.len(), 1);
+		assert_eq!(snapshots[0].manifest.block_number, 0);
+		assert_eq!(snapshots[0].label, Some("pre-upgrade".into()));
+		assert_eq!(snapshots[0].signature, None);
+	}
+
+	#[test]
+	fn signs_snapshot_manifest() {
+		use ethcore::snapshot::verify_manifest_signature;
+
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_null();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		let accounts = Arc::new(AccountProvider::transient_provider());
+		let (address, public) = accounts.new_account_and_public("").unwrap();
+		let (_, other) = accounts.new_account_and_public("").unwrap();
+
+		service.set_manifest_signer(Some(ManifestSigner {
+			accounts: accounts,
+			address: address,
+			password: Some("".into()),
+		}));
+		service.take_snapshot(0, None).unwrap();
+
+		let snapshot = service.list_snapshots().pop().unwrap();
+		let signature = snapshot.signature.unwrap();
+		assert!(verify_manifest_signature(&snapshot.manifest, &signature, &public));
+		assert!(!verify_manifest_signature(&snapshot.manifest, &signature, &other));
+	}
+
+	#[test]
+	fn keeps_snapshot_if_signing_fails() {
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_null();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		let accounts = Arc::new(AccountProvider::transient_provider());
+		let (address, _) = accounts.new_account_and_public("").unwrap();
+
+		service.set_manifest_signer(Some(ManifestSigner {
+			accounts: accounts,
+			address: address,
+			password: Some("wrong".into()),
+		}));
+		service.take_snapshot(0, None).unwrap();
+
+		let snapshots = service.list_snapshots();
+		assert_eq!(snapshots.len(), 1);
+		assert_eq!(snapshots[0].signature, None);
+	}
+
+	#[test]
+	fn restores_snapshot_into_new_path() {
+		let tempdir = TempDir::new("").unwrap();
+		let target_path = tempdir.path().join("migrated");
+
+		let spec = Spec::new_null();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		service.take_snapshot(0, None).unwrap();
+		let manifest = service.list_snapshots()[0].manifest.clone();
+
+		service.restore_to(&manifest, &target_path).unwrap();
+		assert!(target_path.exists());
+		assert_eq!(service.client().chain_info().best_block_number, 0);
+		assert!(service.restore_to(&manifest, &target_path).is_err());
+	}
+
+	#[test]
+	fn writes_snapshot_archive() {
+		use std::fs::File;
+
+		let tempdir = TempDir::new("").unwrap();
+		let archive_path = tempdir.path().join("snapshot.bin");
+
+		let spec = Spec::new_null();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		service.take_snapshot_archive(0, &archive_path).unwrap();
+		let reader = PackedReader::new(&archive_path).unwrap().unwrap();
+		assert_eq!(reader.manifest().block_number, 0);
+		assert!(!reader.manifest().state_hashes.is_empty());
+
+		let not_an_archive = tempdir.path().join("empty");
+		File::create(&not_an_archive).unwrap();
+		assert!(service.restore_from_archive(&not_an_archive).is_err());
+	}
+
+	#[test]
+	fn defers_snapshot_while_importing() {
+		use ethcore::snapshot::SnapshotService;
+
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_null();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		// the service's own handler runs on the IO threads, so drive one directly.
+		let handler = ClientIoHandler {
+			client: service.client(),
+			snapshot: service.snapshot_service(),
+			watchdog: None,
+			log_throttle: LogThrottle::new(Duration::from_secs(WARNING_THROTTLE_SECS)),
+			max_db_size: None,
+			snapshot_require_idle: Some(Duration::from_secs(3600)),
+			last_import: Mutex::new(Instant::now()),
+			deferred_snapshot: Mutex::new(None),
+			stats_log_interval: None,
+			db_path: tempdir.path().join("client"),
+			last_stats: Mutex::new((Instant::now(), 0)),
+		};
+
+		handler.message(&IoContext::new(service.io().channel(), 0), &ClientIoMessage::TakeSnapshot(0));
+		assert_eq!(*handler.deferred_snapshot.lock().unwrap(), Some(0));
+		assert!(service.snapshot_service().manifest().is_none());
+	}
+
+	#[test]
+	fn reports_fork_schedule() {
+		let tempdir = TempDir::new("").unwrap();
+		let spec = Spec::new_test();
+		let service = start_service(&tempdir, &spec, ClientConfig::default());
+
+		let schedule = service.fork_schedule();
+		assert_eq!(schedule.homestead, None);
+		assert_eq!(schedule.eip155, Some(0));
+		assert_eq!(schedule.eip140, None);
+
+		let byzantium = ForkSchedule::from_spec(&::ethcore::ethereum::new_byzantium_test());
+		assert_eq!(byzantium.homestead, Some(0));
+		assert_eq!(byzantium.eip140, Some(0));
+		assert_eq!(byzantium.eip98, None);
+	}
+
+	#[test]
+	fn moves_corrupt_database_aside() {
+		let tempdir = TempDir::new("").unwrap();
+		let client_path = tempdir.path().join("client");
+		fs::create_dir(&client_path).unwrap();
+		fs::File::create(client_path.join("CURRENT")).unwrap();
+
+		let moved = move_aside(&client_path).unwrap();
+		assert!(!client_path.exists());
+		assert!(moved.join("CURRENT").exists());
+		assert!(moved.file_name().unwrap().to_string_lossy().starts_with("client.corrupt-"));
+	}
+
+	#[test]
+	fn tells_unclean_shutdown() {
+		use std::fs::File;
+
+		let tempdir = TempDir::new("").unwrap();
+		let client_path = tempdir.path().join("client");
+		let spec = Spec::new_test();
+		let start = || start_service(&tempdir, &spec, ClientConfig::default());
+
+		let service = start();
+		assert!(!service.config_summary().unclean_shutdown_recovered);
+		assert_eq!(service.config_summary().chain, spec.name);
+		drop(service);
+
+		let service = start();
+		assert!(!service.config_summary().unclean_shutdown_recovered);
+		drop(service);
+
+		// as left behind by a crash.
+		File::create(running_marker_path(&client_path)).unwrap();
+		let service = start();
+		assert!(service.config_summary().unclean_shutdown_recovered);
+	}
+
+	#[test]
+	fn tells_unclean_shutdown_after_panic() {
+		let tempdir = TempDir::new("").unwrap();
+		let client_path = tempdir.path().join("client");
+		let snapshot_path = tempdir.path().join("snapshot");
+
+		let path = client_path.clone();
+		let panicked = thread::spawn(move || {
+			let spec = Spec::new_test();
+			let _service = ClientService::start(
+				ClientConfig::default(),
+				&spec,
+				&path,
+				&snapshot_path,
+				&path,
+				Arc::new(Miner::with_spec(&spec)),
+			).unwrap();
+			panic!("crashing with the service running");
+		}).join();
+		assert!(panicked.is_err());
+		assert!(running_marker_path(&client_path).exists());
+
+		let service = start_service(&tempdir, &Spec::new_test(), ClientConfig::default());
+		assert!(service.config_summary().unclean_shutdown_recovered);
 	}
 }
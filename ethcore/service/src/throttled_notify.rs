@@ -0,0 +1,315 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Coalescing of block notifications for slow observers.
+//!
+//! `new_blocks` events are collected and handed on at most once per interval,
+//! with the block lists of every event in between concatenated in order.
+//! Enacted and retracted blocks are netted out, so that a block enacted and
+//! retracted again within one interval appears in neither list. A flusher
+//! thread delivers the last batch once the interval has passed, so nothing is
+//! held back indefinitely, and takes over delivery while the observer is busy,
+//! so that importing never waits for it. All other events are passed on right
+//! away, which means they may arrive before the batch of blocks they refer to.
+
+use std::mem;
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ethcore::client::{ChainNotify, TxPoolEvent};
+use ethereum_types::{Address, H256, U256};
+
+#[derive(Default)]
+struct Batch {
+	imported: Vec<H256>,
+	invalid: Vec<H256>,
+	enacted: Vec<H256>,
+	retracted: Vec<H256>,
+	sealed: Vec<H256>,
+	proposed: Vec<Vec<u8>>,
+	duration: u64,
+	events: usize,
+}
+
+struct State {
+	batch: Batch,
+	last_delivery: Instant,
+}
+
+/// Passes block notifications on to another observer at most once per interval.
+pub struct ThrottledNotify {
+	inner: Weak<ChainNotify>,
+	min_interval: Duration,
+	state: Mutex<State>,
+	// held while delivering, so that batches can't overtake each other.
+	delivering: Mutex<()>,
+}
+
+impl ThrottledNotify {
+	/// Wrap `inner` and start the thread flushing pending blocks. The thread exits once
+	/// the returned notify is dropped. `inner` isn't kept alive by the wrapper.
+	pub fn start(inner: &Arc<ChainNotify>, min_interval: Duration) -> Result<Arc<ThrottledNotify>, ::std::io::Error> {
+		let notify = Arc::new(ThrottledNotify {
+			inner: Arc::downgrade(inner),
+			min_interval: min_interval,
+			state: Mutex::new(State {
+				batch: Batch::default(),
+				last_delivery: Instant::now(),
+			}),
+			delivering: Mutex::new(()),
+		});
+
+		let weak = Arc::downgrade(&notify);
+		thread::Builder::new().name("Notify Flusher".into()).spawn(move || Self::flusher(weak, min_interval))?;
+
+		Ok(notify)
+	}
+
+	/// Whether the wrapped observer is still around.
+	pub fn is_alive(&self) -> bool {
+		self.inner.upgrade().is_some()
+	}
+
+	/// Number of `new_blocks` events waiting to be delivered as one.
+	pub fn pending(&self) -> usize {
+		self.state.lock().expect("throttled notify lock is never poisoned; qed").batch.events
+	}
+
+	fn flusher(notify: Weak<ThrottledNotify>, interval: Duration) {
+		loop {
+			thread::sleep(interval);
+			match notify.upgrade() {
+				Some(notify) => notify.flush(false),
+				None => break,
+			}
+		}
+	}
+
+	// deliver the pending blocks if there are any and, unless forced, the interval has passed.
+	fn flush(&self, force: bool) {
+		let _delivering = self.delivering.lock().expect("throttled notify delivery lock is never poisoned; qed");
+		self.deliver(force);
+	}
+
+	// must be called holding `delivering`. the observer is called without holding `state`,
+	// so that new events can be collected meanwhile.
+	fn deliver(&self, force: bool) {
+		let batch = {
+			let mut state = self.state.lock().expect("throttled notify lock is never poisoned; qed");
+			if state.batch.events == 0 || !(force || state.last_delivery.elapsed() >= self.min_interval) {
+				return;
+			}
+			state.last_delivery = Instant::now();
+			mem::replace(&mut state.batch, Batch::default())
+		};
+
+		if let Some(inner) = self.inner.upgrade() {
+			trace!(target: "service", "Delivering {} coalesced block notifications", batch.events);
+			inner.new_blocks(batch.imported, batch.invalid, batch.enacted, batch.retracted, batch.sealed, batch.proposed, batch.duration);
+		}
+	}
+
+	fn forward<F: FnOnce(&ChainNotify)>(&self, f: F) {
+		if let Some(inner) = self.inner.upgrade() {
+			f(&*inner);
+		}
+	}
+}
+
+impl Drop for ThrottledNotify {
+	fn drop(&mut self) {
+		self.flush(true);
+	}
+}
+
+impl ChainNotify for ThrottledNotify {
+	fn new_blocks(
+		&self,
+		imported: Vec<H256>,
+		invalid: Vec<H256>,
+		enacted: Vec<H256>,
+		retracted: Vec<H256>,
+		sealed: Vec<H256>,
+		proposed: Vec<Vec<u8>>,
+		duration: u64,
+	) {
+		{
+			let mut state = self.state.lock().expect("throttled notify lock is never poisoned; qed");
+			let batch = &mut state.batch;
+			batch.imported.extend(imported);
+			batch.invalid.extend(invalid);
+			// blocks are retracted before the ones replacing them are enacted.
+			for hash in retracted {
+				match batch.enacted.iter().position(|h| *h == hash) {
+					Some(i) => { batch.enacted.remove(i); },
+					None => batch.retracted.push(hash),
+				}
+			}
+			for hash in enacted {
+				match batch.retracted.iter().position(|h| *h == hash) {
+					Some(i) => { batch.retracted.remove(i); },
+					None => batch.enacted.push(hash),
+				}
+			}
+			batch.sealed.extend(sealed);
+			batch.proposed.extend(proposed);
+			batch.duration += duration;
+			batch.events += 1;
+		}
+
+		// while the observer is busy with the last batch, the flusher delivers this one.
+		if let Ok(_delivering) = self.delivering.try_lock() {
+			self.deliver(false);
+		}
+	}
+
+	fn reorg(&self, retracted: &[(u64, H256)], enacted: &[(u64, H256)]) {
+		self.forward(|n| n.reorg(retracted, enacted));
+	}
+
+	fn reorg_rejected(&self, depth: u64, competing_hash: H256) {
+		self.forward(|n| n.reorg_rejected(depth, competing_hash));
+	}
+
+	fn state_pruned(&self, block: u64) {
+		self.forward(|n| n.state_pruned(block));
+	}
+
+	fn state_root_mismatch(&self, block: u64, expected: H256, found: H256) {
+		self.forward(|n| n.state_root_mismatch(block, expected, found));
+	}
+
+	fn database_closing(&self) {
+		self.flush(true);
+		self.forward(|n| n.database_closing());
+	}
+
+	fn restoration_stalled(&self) {
+		self.forward(|n| n.restoration_stalled());
+	}
+
+	fn invalid_consensus_message(&self, peer_id: usize, reason: String) {
+		self.forward(|n| n.invalid_consensus_message(peer_id, reason));
+	}
+
+	fn equivocation_detected(&self, validator: Address, height: u64) {
+		self.forward(|n| n.equivocation_detected(validator, height));
+	}
+
+	fn genesis_written(&self, hash: H256) {
+		self.forward(|n| n.genesis_written(hash));
+	}
+
+	fn authority_status_changed(&self, is_authority: bool) {
+		self.forward(|n| n.authority_status_changed(is_authority));
+	}
+
+	fn block_gas_stats(&self, number: u64, gas_used: U256, gas_limit: U256, base_fee: Option<U256>) {
+		self.forward(|n| n.block_gas_stats(number, gas_used, gas_limit, base_fee));
+	}
+
+	fn side_chain_block(&self, hash: H256, number: u64) {
+		self.forward(|n| n.side_chain_block(hash, number));
+	}
+
+	fn block_sealed(&self, hash: H256, number: u64) {
+		self.forward(|n| n.block_sealed(hash, number));
+	}
+
+	fn tx_pool_event(&self, event: TxPoolEvent) {
+		self.forward(|n| n.tx_pool_event(event));
+	}
+
+	fn start(&self) {
+		self.forward(|n| n.start());
+	}
+
+	fn stop(&self) {
+		self.forward(|n| n.stop());
+	}
+
+	fn broadcast(&self, data: Vec<u8>) {
+		self.forward(|n| n.broadcast(data));
+	}
+
+	fn transactions_received(&self, hashes: Vec<H256>, peer_id: usize) {
+		self.forward(|n| n.transactions_received(hashes, peer_id));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::{Arc, Mutex};
+	use std::thread;
+	use std::time::Duration;
+
+	use ethcore::client::ChainNotify;
+	use ethereum_types::H256;
+	use super::ThrottledNotify;
+
+	#[derive(Default)]
+	struct Blocks(Mutex<Vec<Vec<H256>>>);
+
+	#[derive(Default)]
+	struct Route(Mutex<Vec<(Vec<H256>, Vec<H256>)>>);
+
+	impl ChainNotify for Route {
+		fn new_blocks(&self, _: Vec<H256>, _: Vec<H256>, enacted: Vec<H256>, retracted: Vec<H256>, _: Vec<H256>, _: Vec<Vec<u8>>, _: u64) {
+			self.0.lock().unwrap().push((enacted, retracted));
+		}
+	}
+
+	impl ChainNotify for Blocks {
+		fn new_blocks(&self, imported: Vec<H256>, _: Vec<H256>, _: Vec<H256>, _: Vec<H256>, _: Vec<H256>, _: Vec<Vec<u8>>, _: u64) {
+			self.0.lock().unwrap().push(imported);
+		}
+	}
+
+	#[test]
+	fn coalesces_block_notifications() {
+		let blocks = Arc::new(Blocks::default());
+		let inner: Arc<ChainNotify> = blocks.clone();
+		let throttled = ThrottledNotify::start(&inner, Duration::from_millis(200)).unwrap();
+
+		for i in 1..4 {
+			throttled.new_blocks(vec![H256::from(i)], vec![], vec![], vec![], vec![], vec![], 0);
+		}
+		assert_eq!(throttled.pending(), 3);
+		assert!(blocks.0.lock().unwrap().is_empty());
+
+		thread::sleep(Duration::from_millis(600));
+		assert_eq!(throttled.pending(), 0);
+		assert_eq!(*blocks.0.lock().unwrap(), vec![vec![H256::from(1), H256::from(2), H256::from(3)]]);
+
+		throttled.new_blocks(vec![H256::from(4)], vec![], vec![], vec![], vec![], vec![], 0);
+		drop(throttled);
+		assert_eq!(blocks.0.lock().unwrap().len(), 2);
+	}
+
+	#[test]
+	fn nets_out_enacted_and_retracted_blocks() {
+		let route = Arc::new(Route::default());
+		let inner: Arc<ChainNotify> = route.clone();
+		let throttled = ThrottledNotify::start(&inner, Duration::from_secs(3600)).unwrap();
+
+		throttled.new_blocks(vec![], vec![], vec![H256::from(1)], vec![H256::from(9)], vec![], vec![], 0);
+		throttled.new_blocks(vec![], vec![], vec![H256::from(2), H256::from(9)], vec![H256::from(1)], vec![], vec![], 0);
+		drop(throttled);
+
+		assert_eq!(*route.0.lock().unwrap(), vec![(vec![H256::from(2)], vec![])]);
+	}
+}
@@ -0,0 +1,197 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Latency from first seeing a transaction until it is mined.
+//!
+//! Transactions are timed from when they are first received, from the network
+//! or locally, until a block containing them becomes canonical. Only a bounded
+//! number of transactions is tracked at once; the oldest are forgotten first.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use ethcore::client::{BlockChainClient, BlockId, ChainNotify, Client};
+use ethereum_types::H256;
+
+// maximum number of transactions waiting to be mined which are tracked.
+const MAX_TRACKED_TRANSACTIONS: usize = 8192;
+
+/// Delays between first seeing transactions and their inclusion in a canonical block,
+/// as returned by `ClientService::tx_propagation_stats`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PropagationStats {
+	/// Number of transactions which were seen before being mined.
+	pub samples: u64,
+	/// Mean delay.
+	pub mean: Duration,
+	/// Shortest delay.
+	pub min: Duration,
+	/// Longest delay.
+	pub max: Duration,
+	/// Transactions currently waiting to be mined.
+	pub tracked: usize,
+}
+
+#[derive(Default)]
+struct State {
+	first_seen: HashMap<H256, Instant>,
+	// order the tracked transactions were seen in, for forgetting the oldest.
+	order: VecDeque<H256>,
+	samples: u64,
+	total: Duration,
+	min: Option<Duration>,
+	max: Duration,
+}
+
+/// Times transactions from when they are received until they are mined.
+pub struct TxPropagation {
+	client: Weak<Client>,
+	state: Mutex<State>,
+}
+
+impl TxPropagation {
+	/// Create a tracker reading mined transactions from `client`, which isn't kept alive by it.
+	pub fn new(client: Weak<Client>) -> Self {
+		TxPropagation {
+			client: client,
+			state: Mutex::new(State::default()),
+		}
+	}
+
+	/// Aggregated delays of the transactions mined so far.
+	pub fn stats(&self) -> PropagationStats {
+		let state = self.state.lock().expect("tx propagation lock is never poisoned; qed");
+		PropagationStats {
+			samples: state.samples,
+			mean: match state.samples {
+				0 => Duration::from_secs(0),
+				samples => state.total / samples as u32,
+			},
+			min: state.min.unwrap_or_default(),
+			max: state.max,
+			tracked: state.first_seen.len(),
+		}
+	}
+
+	// start timing the transactions which aren't tracked yet.
+	fn seen(&self, hashes: &[H256], now: Instant) {
+		let mut state = self.state.lock().expect("tx propagation lock is never poisoned; qed");
+		for hash in hashes {
+			if state.first_seen.contains_key(hash) { continue }
+
+			if state.order.len() >= MAX_TRACKED_TRANSACTIONS {
+				if let Some(oldest) = state.order.pop_front() {
+					state.first_seen.remove(&oldest);
+				}
+			}
+			state.first_seen.insert(*hash, now);
+			state.order.push_back(*hash);
+		}
+	}
+
+	// record the delays of the tracked transactions among `hashes`, which were just mined.
+	fn mined(&self, hashes: &[H256], now: Instant) {
+		let mut state = self.state.lock().expect("tx propagation lock is never poisoned; qed");
+		for hash in hashes {
+			let first_seen = match state.first_seen.remove(hash) {
+				Some(first_seen) => first_seen,
+				None => continue,
+			};
+
+			let delay = now.duration_since(first_seen);
+			state.samples += 1;
+			state.total += delay;
+			state.min = Some(state.min.map_or(delay, |min| ::std::cmp::min(min, delay)));
+			state.max = ::std::cmp::max(state.max, delay);
+		}
+
+		if state.order.len() > state.first_seen.len() {
+			let state = &mut *state;
+			let first_seen = &state.first_seen;
+			state.order.retain(|hash| first_seen.contains_key(hash));
+		}
+	}
+}
+
+impl ChainNotify for TxPropagation {
+	fn new_blocks(
+		&self,
+		_imported: Vec<H256>,
+		_invalid: Vec<H256>,
+		enacted: Vec<H256>,
+		_retracted: Vec<H256>,
+		_sealed: Vec<H256>,
+		_proposed: Vec<Vec<u8>>,
+		_duration: u64,
+	) {
+		let client = match self.client.upgrade() {
+			Some(client) => client,
+			None => return,
+		};
+
+		let now = Instant::now();
+		for hash in enacted {
+			if let Some(body) = client.block_body(BlockId::Hash(hash)) {
+				self.mined(&body.transaction_hashes(), now);
+			}
+		}
+	}
+
+	fn transactions_received(&self, hashes: Vec<H256>, _peer_id: usize) {
+		self.seen(&hashes, Instant::now());
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Weak;
+	use std::time::{Duration, Instant};
+
+	use ethereum_types::H256;
+	use super::{TxPropagation, MAX_TRACKED_TRANSACTIONS};
+
+	#[test]
+	fn aggregates_delays_until_mined() {
+		let tracker = TxPropagation::new(Weak::new());
+		let start = Instant::now();
+
+		tracker.seen(&[H256::from(1), H256::from(2)], start);
+		tracker.seen(&[H256::from(1)], start + Duration::from_secs(5));
+		tracker.mined(&[H256::from(1), H256::from(3)], start + Duration::from_secs(2));
+		tracker.mined(&[H256::from(2)], start + Duration::from_secs(4));
+
+		let stats = tracker.stats();
+		assert_eq!(stats.samples, 2);
+		assert_eq!(stats.mean, Duration::from_secs(3));
+		assert_eq!(stats.min, Duration::from_secs(2));
+		assert_eq!(stats.max, Duration::from_secs(4));
+		assert_eq!(stats.tracked, 0);
+	}
+
+	#[test]
+	fn forgets_oldest_transactions() {
+		let tracker = TxPropagation::new(Weak::new());
+		let hashes: Vec<_> = (0..MAX_TRACKED_TRANSACTIONS as u64 + 1).map(H256::from).collect();
+		let now = Instant::now();
+
+		tracker.seen(&hashes, now);
+		assert_eq!(tracker.stats().tracked, MAX_TRACKED_TRANSACTIONS);
+
+		tracker.mined(&hashes[..1], now);
+		assert_eq!(tracker.stats().samples, 0);
+	}
+}
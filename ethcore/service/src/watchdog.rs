@@ -0,0 +1,170 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Watchdog for IO handler dispatches.
+//!
+//! Every dispatch records when it started in an atomic slot, and a monitor
+//! thread warns about dispatches which have been running for longer than
+//! the configured threshold. This doesn't resolve a deadlock, but it tells
+//! which message the stuck worker was handling.
+
+use std::cmp;
+use std::sync::{Arc, Mutex, Weak};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// enough for every IO worker to be dispatching at once.
+const SLOTS: usize = 8;
+// `started` value of a slot with no dispatch in progress.
+const IDLE: usize = 0;
+
+struct Slot {
+	busy: AtomicBool,
+	// milliseconds since the watchdog epoch, plus one.
+	started: AtomicUsize,
+	// `started` value of the dispatch which was last reported.
+	reported: AtomicUsize,
+	operation: Mutex<&'static str>,
+}
+
+/// Watches for IO handler dispatches which don't complete in time.
+pub struct Watchdog {
+	epoch: Instant,
+	threshold: Duration,
+	slots: Vec<Slot>,
+	stalls: AtomicUsize,
+}
+
+impl Watchdog {
+	/// Create a watchdog and start its monitor thread.
+	/// The thread exits once the returned watchdog is dropped.
+	pub fn start(threshold: Duration) -> Result<Arc<Watchdog>, ::std::io::Error> {
+		let watchdog = Arc::new(Watchdog {
+			epoch: Instant::now(),
+			threshold: threshold,
+			slots: (0..SLOTS).map(|_| Slot {
+				busy: AtomicBool::new(false),
+				started: AtomicUsize::new(IDLE),
+				reported: AtomicUsize::new(IDLE),
+				operation: Mutex::new(""),
+			}).collect(),
+			stalls: AtomicUsize::new(0),
+		});
+
+		let weak = Arc::downgrade(&watchdog);
+		let interval = cmp::max(threshold / 4, Duration::from_millis(10));
+		thread::Builder::new().name("IO Watchdog".into()).spawn(move || Self::monitor(weak, interval))?;
+
+		Ok(watchdog)
+	}
+
+	/// Record the start of a dispatch. The dispatch is considered complete
+	/// when the returned guard is dropped.
+	pub fn enter(&self, operation: &'static str) -> Option<DispatchGuard> {
+		for (index, slot) in self.slots.iter().enumerate() {
+			if slot.busy.compare_and_swap(false, true, Ordering::SeqCst) {
+				continue;
+			}
+
+			*slot.operation.lock().expect("watchdog lock is never poisoned; qed") = operation;
+			slot.started.store(self.now() + 1, Ordering::SeqCst);
+			return Some(DispatchGuard { watchdog: self, index: index });
+		}
+
+		// more concurrent dispatches than slots; leave this one unwatched.
+		None
+	}
+
+	/// Number of stuck dispatches reported so far.
+	pub fn stalls(&self) -> usize {
+		self.stalls.load(Ordering::SeqCst)
+	}
+
+	fn now(&self) -> usize {
+		let elapsed = self.epoch.elapsed();
+		elapsed.as_secs() as usize * 1000 + elapsed.subsec_nanos() as usize / 1_000_000
+	}
+
+	fn monitor(watchdog: Weak<Watchdog>, interval: Duration) {
+		loop {
+			thread::sleep(interval);
+			match watchdog.upgrade() {
+				Some(watchdog) => watchdog.check(),
+				None => break,
+			}
+		}
+	}
+
+	fn check(&self) {
+		let now = self.now() + 1;
+		let threshold = self.threshold.as_secs() as usize * 1000 + self.threshold.subsec_nanos() as usize / 1_000_000;
+
+		for slot in &self.slots {
+			let started = slot.started.load(Ordering::SeqCst);
+			if started == IDLE || now.saturating_sub(started) < threshold {
+				continue;
+			}
+
+			// only warn once for every stuck dispatch.
+			if slot.reported.swap(started, Ordering::SeqCst) == started {
+				continue;
+			}
+
+			let operation = *slot.operation.lock().expect("watchdog lock is never poisoned; qed");
+			self.stalls.fetch_add(1, Ordering::SeqCst);
+			warn!(target: "service", "IO handler has been processing {} for {}ms; it may be deadlocked", operation, now - started);
+		}
+	}
+}
+
+/// Marks a dispatch as in progress until dropped.
+pub struct DispatchGuard<'a> {
+	watchdog: &'a Watchdog,
+	index: usize,
+}
+
+impl<'a> Drop for DispatchGuard<'a> {
+	fn drop(&mut self) {
+		let slot = &self.watchdog.slots[self.index];
+		slot.started.store(IDLE, Ordering::SeqCst);
+		slot.busy.store(false, Ordering::SeqCst);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::thread;
+	use std::time::Duration;
+	use super::Watchdog;
+
+	#[test]
+	fn reports_stuck_dispatch_once() {
+		let watchdog = Watchdog::start(Duration::from_millis(20)).unwrap();
+
+		{
+			let _guard = watchdog.enter("BlockVerified");
+			thread::sleep(Duration::from_millis(200));
+		}
+		assert_eq!(watchdog.stalls(), 1);
+
+		{
+			let _guard = watchdog.enter("NewTransactions");
+		}
+		thread::sleep(Duration::from_millis(100));
+		assert_eq!(watchdog.stalls(), 1);
+	}
+}
@@ -14,9 +14,21 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
-use ethereum_types::H256;
+use ethereum_types::{Address, H256, U256};
 use bytes::Bytes;
 
+/// Outcome of offering a transaction received from the network to the transaction pool.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TxPoolEvent {
+	/// Transaction entered the pool and is ready to be mined.
+	Pending(H256),
+	/// Transaction entered the pool, but waits for a transaction with a lower nonce.
+	Future(H256),
+	/// Transaction was not admitted, e.g. its gas price was too low to enter
+	/// the pool or to replace the transaction it competes with.
+	Rejected(H256, String),
+}
+
 /// Represents what has to be handled by actor listening to chain events
 pub trait ChainNotify : Send + Sync {
 	/// fires when chain has new blocks.
@@ -34,6 +46,92 @@ pub trait ChainNotify : Send + Sync {
 		// does nothing by default
 	}
 
+	/// fires when new blocks cause a reorganisation of the canonical chain,
+	/// after `new_blocks`. Blocks are given as `(number, hash)` pairs:
+	/// `retracted` in descending and `enacted` in ascending order of number.
+	/// Retracted blocks are reported even if their state has been pruned.
+	fn reorg(&self, _retracted: &[(u64, H256)], _enacted: &[(u64, H256)]) {
+		// does nothing by default
+	}
+
+	/// fires when a block was refused because the reorganisation it would cause
+	/// retracts `depth` canonical blocks, more than `max_reorg_depth` allows.
+	fn reorg_rejected(&self, _depth: u64, _competing_hash: H256) {
+		// does nothing by default
+	}
+
+	/// fires when the state of the given block is about to be pruned.
+	/// Called during block import, so should not block for long.
+	fn state_pruned(&self, _block: u64) {
+		// does nothing by default
+	}
+
+	/// fires when the state committed for an imported block doesn't match its
+	/// state root. Only checked with `verify_state_root_on_import`; import is halted.
+	fn state_root_mismatch(&self, _block: u64, _expected: H256, _found: H256) {
+		// does nothing by default
+	}
+
+	/// fires right before the client service closes the database. Observers holding
+	/// a handle to it should drop that now, or the database will stay open.
+	fn database_closing(&self) {
+		// does nothing by default
+	}
+
+	/// fires when a snapshot restoration was failed because no chunk was restored
+	/// within `restoration_stall_timeout`. The restoration may be started again,
+	/// preferably with chunks from other peers.
+	fn restoration_stalled(&self) {
+		// does nothing by default
+	}
+
+	/// fires when the engine rejected a consensus message received from `peer_id`.
+	/// Lets the network layer punish peers sending bogus messages, e.g. by disabling them.
+	fn invalid_consensus_message(&self, _peer_id: usize, _reason: String) {
+		// does nothing by default
+	}
+
+	/// fires when the engine caught `validator` signing conflicting consensus messages
+	/// at `height`. Engines which don't detect equivocation never fire this.
+	fn equivocation_detected(&self, _validator: Address, _height: u64) {
+		// does nothing by default
+	}
+
+	/// fires when registered with a client which wrote the spec's genesis block to a fresh
+	/// database. Since that happens before any observer can be added, every observer gets
+	/// this on registration instead.
+	fn genesis_written(&self, _hash: H256) {
+		// does nothing by default
+	}
+
+	/// fires when the engine finds that this node became an authority or stopped being one,
+	/// e.g. after a validator set change, and once when the status is first known.
+	/// Only engines with a set of authorities, like Aura, report this.
+	fn authority_status_changed(&self, _is_authority: bool) {
+		// does nothing by default
+	}
+
+	/// fires for every imported block, canonical or not.
+	/// `base_fee` is always `None` for now, as no supported engine has one.
+	fn block_gas_stats(&self, _number: u64, _gas_used: U256, _gas_limit: U256, _base_fee: Option<U256>) {
+		// does nothing by default
+	}
+
+	/// fires when an imported block didn't become part of the canonical chain.
+	fn side_chain_block(&self, _hash: H256, _number: u64) {
+		// does nothing by default
+	}
+
+	/// fires when a block sealed by this node has been imported, after `new_blocks`.
+	fn block_sealed(&self, _hash: H256, _number: u64) {
+		// does nothing by default
+	}
+
+	/// fires for every transaction from the network offered to the transaction pool.
+	fn tx_pool_event(&self, _event: TxPoolEvent) {
+		// does nothing by default
+	}
+
 	/// fires when chain achieves active mode
 	fn start(&self) {
 		// does nothing by default
@@ -14,18 +14,21 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::collections::{HashSet, HashMap, BTreeMap, VecDeque};
+use std::collections::{HashSet, HashMap, BTreeMap, BTreeSet, VecDeque};
 use std::str::FromStr;
 use std::sync::{Arc, Weak};
 use std::sync::atomic::{AtomicUsize, AtomicBool, Ordering as AtomicOrdering};
-use std::time::{Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use itertools::Itertools;
+use lru_cache::LruCache;
 
 // util
 use hash::keccak;
 use bytes::Bytes;
 use journaldb;
-use trie::{TrieSpec, TrieFactory, Trie};
+use trie::{TrieSpec, TrieFactory, Trie, TrieDB, TrieDBMut, TrieMut};
+use memorydb::MemoryDB;
+use hashdb::HashDB;
 use kvdb::{DBValue, KeyValueDB, DBTransaction};
 use util_error::UtilError;
 
@@ -45,7 +48,7 @@ use client::{
 use client::{
 	BlockId, TransactionId, UncleId, TraceId, ClientConfig, BlockChainClient,
 	MiningBlockChainClient, TraceFilter, CallAnalytics, BlockImportError, Mode,
-	ChainNotify, PruningInfo, ProvingBlockChainClient, EngineInfo
+	ChainNotify, PruningInfo, ProvingBlockChainClient, EngineInfo, TxPoolEvent
 };
 use encoded;
 use engines::{EthEngine, EpochTransition};
@@ -56,7 +59,7 @@ use executive::{Executive, Executed, TransactOptions, contract_address};
 use factory::{Factories, VmFactory};
 use header::{BlockNumber, Header};
 use io::IoChannel;
-use log_entry::LocalizedLogEntry;
+use log_entry::{LogEntry, LocalizedLogEntry};
 use miner::{Miner, MinerService};
 use parking_lot::{Mutex, RwLock};
 use rand::OsRng;
@@ -69,6 +72,7 @@ use state::{self, State};
 use trace;
 use trace::{TraceDB, ImportRequest as TraceImportRequest, LocalizedTrace, Database as TraceDatabase};
 use transaction::{self, LocalizedTransaction, UnverifiedTransaction, SignedTransaction, Transaction, PendingTransaction, Action};
+use types::basic_account::BasicAccount;
 use types::filter::Filter;
 use types::mode::Mode as IpcMode;
 use verification;
@@ -80,6 +84,7 @@ use views::BlockView;
 pub use types::blockchain_info::BlockChainInfo;
 pub use types::block_status::BlockStatus;
 pub use blockchain::CacheSize as BlockChainCacheSize;
+pub use blockchain::BlockReceipts;
 pub use verification::queue::QueueInfo as BlockQueueInfo;
 
 use_contract!(registry, "Registry", "res/contracts/registrar.json");
@@ -87,6 +92,23 @@ use_contract!(registry, "Registry", "res/contracts/registrar.json");
 const MAX_TX_QUEUE_SIZE: usize = 4096;
 const MAX_QUEUE_SIZE_TO_SLEEP_ON: usize = 2;
 const MIN_HISTORY_SIZE: u64 = 8;
+// Blocks further ahead than this are left for the verifier to reject.
+const MAX_FUTURE_BLOCK_HOLD_SECS: u64 = 150;
+// Maximum number of blocks held back for their timestamps.
+const MAX_FUTURE_BLOCKS: usize = 1024;
+// Number of recent canonical blocks whose timestamps are checked by `clock_skew`.
+const CLOCK_SKEW_SAMPLE_BLOCKS: u64 = 16;
+/// Peer id under which the node queues transactions it submitted itself.
+pub const LOCAL_PEER_ID: usize = ::std::usize::MAX;
+
+/// Maximum number of blocks whose receipts are read at once by `receipts_in_range`.
+pub const MAX_RECEIPTS_RANGE: u64 = 1024;
+
+// number of rejected blocks remembered for `recent_import_rejections`.
+const MAX_IMPORT_REJECTIONS: usize = 64;
+
+// Key of the set of blocks whose state is pinned against pruning.
+const PINNED_STATE_KEY: &'static [u8] = b"pinned_state";
 
 /// Report on the status of a client.
 #[derive(Default, Clone, Debug, Eq, PartialEq)]
@@ -99,6 +121,8 @@ pub struct ClientReport {
 	pub gas_processed: U256,
 	/// Memory used by state DB
 	pub state_db_mem: usize,
+	/// Blocks with future timestamps held back from import
+	pub future_blocks_queued: usize,
 }
 
 impl ClientReport {
@@ -126,6 +150,126 @@ impl<'a> ::std::ops::Sub<&'a ClientReport> for ClientReport {
 	}
 }
 
+/// Counts of transactions received from the network, by format.
+#[derive(Default, Clone, Debug, Eq, PartialEq)]
+pub struct TxTypeStats {
+	/// Transactions with EIP-155 replay protection.
+	pub replay_protected: usize,
+	/// Transactions signed without a chain id.
+	pub unprotected: usize,
+	/// Payloads which couldn't be decoded as transactions.
+	pub undecodable: usize,
+	/// Transactions rejected for paying less than the minimal gas price.
+	pub underpriced: usize,
+}
+
+/// Merkle proof of an account and some of its storage slots at a block, as in EIP-1186.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateProof {
+	/// The account, or an empty one if it doesn't exist.
+	pub account: BasicAccount,
+	/// Trie nodes on the path from the state root to the account, or to where it would be.
+	pub account_proof: Vec<Bytes>,
+	/// Proofs of the requested storage slots, in the order they were asked for.
+	pub storage_proof: Vec<StorageProof>,
+}
+
+/// Merkle proof of a storage slot within the storage trie of an account.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StorageProof {
+	/// The storage slot.
+	pub key: H256,
+	/// Value of the slot, zero if it isn't set.
+	pub value: H256,
+	/// Trie nodes on the path from the storage root to the slot, or to where it would be.
+	pub proof: Vec<Bytes>,
+}
+
+/// Lookups of imported blocks in the cache of recently seen block hashes.
+#[derive(Default, Clone, Debug, Eq, PartialEq)]
+pub struct KnownBlockStats {
+	/// Blocks which were found in the cache and refused right away.
+	pub hits: usize,
+	/// Blocks which were looked up in the cache.
+	pub lookups: usize,
+}
+
+impl KnownBlockStats {
+	/// Share of lookups which were hits, between 0 and 1.
+	pub fn hit_rate(&self) -> f64 {
+		match self.lookups {
+			0 => 0.0,
+			lookups => self.hits as f64 / lookups as f64,
+		}
+	}
+}
+
+/// Approximate memory used by the client's queues, in bytes.
+#[derive(Default, Clone, Debug, Eq, PartialEq)]
+pub struct QueueMemory {
+	/// Blocks waiting for verification.
+	pub unverified_blocks: usize,
+	/// Blocks being verified.
+	pub verifying_blocks: usize,
+	/// Verified blocks waiting for import.
+	pub verified_blocks: usize,
+	/// Transactions in the transaction queue.
+	pub transactions: usize,
+}
+
+impl QueueMemory {
+	/// Memory used by all queues together.
+	pub fn total(&self) -> usize {
+		self.unverified_blocks + self.verifying_blocks + self.verified_blocks + self.transactions
+	}
+}
+
+/// Where the time importing a block went.
+#[derive(Default, Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BlockTimings {
+	/// Executing the block's transactions and closing it.
+	pub execution: Duration,
+	/// Writing the block and its state changes to the database.
+	pub state_write: Duration,
+	/// The whole import, including any checks in between.
+	pub total: Duration,
+}
+
+/// Why a verified block was not imported.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ImportRejection {
+	/// Failed verification on its own, e.g. of its header fields or transactions root.
+	Basic(String),
+	/// Failed the checks which don't depend on other blocks, e.g. of its seal or transaction signatures.
+	Unordered(String),
+	/// The block is older than the earliest available state.
+	Ancient,
+	/// The parent block isn't in the chain.
+	UnknownParent(H256),
+	/// The parent block was rejected itself.
+	InvalidParent,
+	/// Rejected by the block filter.
+	Filtered,
+	/// Importing it would retract more canonical blocks than `max_reorg_depth` allows.
+	ReorgTooDeep(u64),
+	/// Failed verification against its parent and the chain, e.g. on difficulty or uncles.
+	Family(String),
+	/// Failed the engine's external verification, e.g. of the seal.
+	External(String),
+	/// Its transactions couldn't be executed on the parent state.
+	Enactment(String),
+	/// The resulting header doesn't match the block's, e.g. on state root or gas used.
+	Final(String),
+}
+
+#[derive(Default)]
+struct TxTypeCounters {
+	replay_protected: AtomicUsize,
+	unprotected: AtomicUsize,
+	undecodable: AtomicUsize,
+	underpriced: AtomicUsize,
+}
+
 struct SleepState {
 	last_activity: Option<Instant>,
 	last_autosleep: Option<Instant>,
@@ -185,7 +329,7 @@ pub struct Client {
 	config: ClientConfig,
 
 	/// Database pruning strategy to use for StateDB
-	pruning: journaldb::Algorithm,
+	pruning: RwLock<journaldb::Algorithm>,
 
 	/// Client uses this to store blocks, traces, etc.
 	db: RwLock<Arc<KeyValueDB>>,
@@ -206,6 +350,28 @@ pub struct Client {
 
 	/// Count of pending transactions in the queue
 	queue_transactions: AtomicUsize,
+	/// Blocks with future timestamps, by timestamp, waiting to be imported
+	future_blocks: Mutex<BTreeMap<u64, Vec<Bytes>>>,
+	/// Transactions received from the network, by format
+	tx_types: TxTypeCounters,
+	/// Hashes of recently queued or already imported blocks
+	known_blocks: Mutex<LruCache<H256, ()>>,
+	known_block_hits: AtomicUsize,
+	known_block_lookups: AtomicUsize,
+	/// Gas price floor for transactions queued for import
+	minimal_gas_price: RwLock<Option<U256>>,
+	/// Blocks whose state must not be pruned
+	pinned_state: RwLock<BTreeSet<u64>>,
+	/// Timings of the most recently imported block
+	last_block_timings: RwLock<Option<BlockTimings>>,
+	/// Whether the database has outgrown its size limit
+	storage_full: AtomicBool,
+	/// Recently imported blocks which didn't become canonical, as `(number, hash)`
+	side_chain_blocks: Mutex<VecDeque<(u64, H256)>>,
+	/// Most recently rejected blocks, oldest first
+	import_rejections: Mutex<VecDeque<(H256, ImportRejection)>>,
+	/// Hash of the genesis block if this client wrote it to a fresh database
+	written_genesis: Option<H256>,
 	last_hashes: RwLock<VecDeque<H256>>,
 	factories: Factories,
 
@@ -222,6 +388,9 @@ pub struct Client {
 	/// A closure to call when we want to restart the client
 	exit_handler: Mutex<Option<Box<Fn(bool, Option<String>) + 'static + Send>>>,
 
+	/// Policy verified blocks must pass before they are imported
+	block_filter: RwLock<Option<Box<Fn(&Header) -> bool + Send + Sync>>>,
+
 	importer: Importer,
 }
 
@@ -232,7 +401,18 @@ impl Importer {
 		message_channel: IoChannel<ClientIoMessage>,
 		miner: Arc<Miner>,
 	) -> Result<Importer, ::error::Error> {
-		let block_queue = BlockQueue::new(config.queue.clone(), engine.clone(), message_channel.clone(), config.verifier_type.verifying_seal());
+		let mut queue_config = config.queue.clone();
+		queue_config.max_timestamp_drift = future_block_tolerance(config);
+		if let Some(threads) = config.verifier_threads {
+			if threads == 0 {
+				return Err(ClientError::NoVerifierThreads.into());
+			}
+			queue_config.verifier_settings.max_verifiers = Some(threads);
+			queue_config.verifier_settings.num_verifiers = threads;
+		}
+
+		let block_queue = BlockQueue::new(queue_config, engine.clone(), message_channel.clone(), config.verifier_type.verifying_seal());
+		info!(target: "client", "Verifying blocks on {} threads", block_queue.verifier_threads());
 
 		Ok(Importer {
 			import_lock: Mutex::new(()),
@@ -280,13 +460,18 @@ impl Importer {
 		}
 
 		let max_blocks_to_import = 4;
-		let (imported_blocks, import_results, invalid_blocks, imported, proposed_blocks, duration, is_empty) = {
+		let (imported_blocks, import_results, invalid_blocks, imported, proposed_blocks, gas_stats, duration, is_empty) = {
 			let mut imported_blocks = Vec::with_capacity(max_blocks_to_import);
+			let mut gas_stats = Vec::with_capacity(max_blocks_to_import);
 			let mut invalid_blocks = HashSet::new();
 			let mut proposed_blocks = Vec::with_capacity(max_blocks_to_import);
 			let mut import_results = Vec::with_capacity(max_blocks_to_import);
+			// set once a block's state root didn't check out, halting import.
+			let mut halted = false;
 
 			let _import_lock = self.import_lock.lock();
+			// noted first, as these were rejected before any of the drained blocks.
+			client.note_queue_rejections();
 			let blocks = self.block_queue.drain(max_blocks_to_import);
 			if blocks.is_empty() {
 				return 0;
@@ -296,25 +481,64 @@ impl Importer {
 
 			for block in blocks {
 				let header = &block.header;
+				// the rest of the drained blocks go back as bad, rather than being left unaccounted.
+				if halted {
+					invalid_blocks.insert(header.hash());
+					continue;
+				}
 				let is_invalid = invalid_blocks.contains(header.parent_hash());
-				if is_invalid {
+				let rejection = if is_invalid {
+					Some(ImportRejection::InvalidParent)
+				} else if !client.block_allowed(header) {
+					Some(ImportRejection::Filtered)
+				} else {
+					None
+				};
+				if let Some(rejection) = rejection {
 					invalid_blocks.insert(header.hash());
+					client.note_import_rejection(header.hash(), rejection);
 					continue;
 				}
-				if let Ok(closed_block) = self.check_and_close_block(&block, client) {
-					if self.engine.is_proposal(&block.header) {
-						self.block_queue.mark_as_good(&[header.hash()]);
-						proposed_blocks.push(block.bytes);
-					} else {
-						imported_blocks.push(header.hash());
+				let started = Instant::now();
+				match self.check_and_close_block(&block, client) {
+					Ok(closed_block) => {
+						let execution = started.elapsed();
+						if self.engine.is_proposal(&block.header) {
+							self.block_queue.mark_as_good(&[header.hash()]);
+							proposed_blocks.push(block.bytes);
+						} else {
+							// checked before committing, so that a mismatching block never becomes canonical.
+							if client.config.verify_state_root_on_import && !client.verify_state_root(header, closed_block.state().db().as_hashdb()) {
+								invalid_blocks.insert(header.hash());
+								halted = true;
+								continue;
+							}
 
-						let route = self.commit_block(closed_block, &header, &block.bytes, client);
-						import_results.push(route);
+							imported_blocks.push(header.hash());
+							gas_stats.push((header.number(), *header.gas_used(), *header.gas_limit()));
+
+							let committing = Instant::now();
+							let route = self.commit_block(closed_block, &header, &block.bytes, client);
+							*client.last_block_timings.write() = Some(BlockTimings {
+								execution: execution,
+								state_write: committing.elapsed(),
+								total: started.elapsed(),
+							});
+							if !route.omitted.is_empty() {
+								client.note_side_chain_block(header.number(), header.hash());
+							}
+							import_results.push(route);
 
-						client.report.write().accrue_block(&block);
-					}
-				} else {
-					invalid_blocks.insert(header.hash());
+							client.report.write().accrue_block(&block);
+						}
+					},
+					Err(rejection) => {
+						if let ImportRejection::ReorgTooDeep(depth) = rejection {
+							client.notify(|notify| notify.reorg_rejected(depth, header.hash()));
+						}
+						invalid_blocks.insert(header.hash());
+						client.note_import_rejection(header.hash(), rejection);
+					},
 				}
 			}
 
@@ -329,7 +553,7 @@ impl Importer {
 				let elapsed = start.elapsed();
 				elapsed.as_secs() * 1_000_000_000 + elapsed.subsec_nanos() as u64
 			};
-			(imported_blocks, import_results, invalid_blocks, imported, proposed_blocks, duration_ns, is_empty)
+			(imported_blocks, import_results, invalid_blocks, imported, proposed_blocks, gas_stats, duration_ns, is_empty)
 		};
 
 		{
@@ -351,14 +575,31 @@ impl Importer {
 						duration,
 					);
 				});
+				client.notify_reorg(&enacted, &retracted);
 			}
 		}
 
+		client.notify(|notify| {
+			for &(number, gas_used, gas_limit) in &gas_stats {
+				notify.block_gas_stats(number, gas_used, gas_limit, None);
+			}
+		});
+
 		client.db.read().flush().expect("DB flush failed.");
 		imported
 	}
 
-	fn check_and_close_block(&self, block: &PreverifiedBlock, client: &Client) -> Result<LockedBlock, ()> {
+	// Number of canonical blocks importing `header` would retract, if it would become the new best block.
+	fn reorg_depth(header: &Header, chain: &BlockChain) -> Option<u64> {
+		let parent_details = chain.block_details(header.parent_hash())?;
+		if parent_details.total_difficulty + *header.difficulty() <= chain.best_block_total_difficulty() {
+			return None;
+		}
+
+		chain.tree_route(chain.best_block_hash(), *header.parent_hash()).map(|route| route.index as u64)
+	}
+
+	fn check_and_close_block(&self, block: &PreverifiedBlock, client: &Client) -> Result<LockedBlock, ImportRejection> {
 		let engine = &*self.engine;
 		let header = &block.header;
 
@@ -367,7 +608,7 @@ impl Importer {
 		let best_block_number = chain.best_block_number();
 		if client.pruning_info().earliest_state > header.number() {
 			warn!(target: "client", "Block import failed for #{} ({})\nBlock is ancient (current best block: #{}).", header.number(), header.hash(), best_block_number);
-			return Err(());
+			return Err(ImportRejection::Ancient);
 		}
 
 		// Check if parent is in chain
@@ -375,7 +616,7 @@ impl Importer {
 			Some(h) => h,
 			None => {
 				warn!(target: "client", "Block import failed for #{} ({}): Parent not found ({}) ", header.number(), header.hash(), header.parent_hash());
-				return Err(());
+				return Err(ImportRejection::UnknownParent(*header.parent_hash()));
 			}
 		};
 
@@ -394,15 +635,24 @@ impl Importer {
 
 		if let Err(e) = verify_family_result {
 			warn!(target: "client", "Stage 3 block verification failed for #{} ({})\nError: {:?}", header.number(), header.hash(), e);
-			return Err(());
+			return Err(ImportRejection::Family(format!("{}", e)));
 		};
 
 		let verify_external_result = self.verifier.verify_block_external(header, engine);
 		if let Err(e) = verify_external_result {
 			warn!(target: "client", "Stage 4 block verification failed for #{} ({})\nError: {:?}", header.number(), header.hash(), e);
-			return Err(());
+			return Err(ImportRejection::External(format!("{}", e)));
 		};
 
+		if let Some(max_depth) = client.config.max_reorg_depth {
+			if let Some(depth) = Self::reorg_depth(header, &**chain) {
+				if depth > max_depth {
+					warn!(target: "client", "Block import failed for #{} ({}): reorg of depth {} exceeds the maximum of {}", header.number(), header.hash(), depth, max_depth);
+					return Err(ImportRejection::ReorgTooDeep(depth));
+				}
+			}
+		}
+
 		// Enact Verified Block
 		let last_hashes = client.build_last_hashes(header.parent_hash());
 		let db = client.state_db.read().boxed_clone_canon(header.parent_hash());
@@ -419,6 +669,7 @@ impl Importer {
 		);
 		let mut locked_block = enact_result.map_err(|e| {
 			warn!(target: "client", "Block import failed for #{} ({})\nError: {:?}", header.number(), header.hash(), e);
+			ImportRejection::Enactment(format!("{}", e))
 		})?;
 
 		if header.number() < engine.params().validate_receipts_transition && header.receipts_root() != locked_block.block().header().receipts_root() {
@@ -428,7 +679,7 @@ impl Importer {
 		// Final Verification
 		if let Err(e) = self.verifier.verify_block_final(header, locked_block.block().header()) {
 			warn!(target: "client", "Stage 5 block verification failed for #{} ({})\nError: {:?}", header.number(), header.hash(), e);
-			return Err(());
+			return Err(ImportRejection::Final(format!("{}", e)));
 		}
 
 		Ok(locked_block)
@@ -699,9 +950,17 @@ impl Client {
 			accountdb: Default::default(),
 		};
 
+		let spec_genesis = spec.genesis_header().hash();
+		if let Some(expected) = config.expected_genesis {
+			if spec_genesis != expected {
+				return Err(ClientError::GenesisMismatch(expected, spec_genesis).into());
+			}
+		}
+
 		let journal_db = journaldb::new(db.clone(), config.pruning, ::db::COL_STATE);
 		let mut state_db = StateDB::new(journal_db, config.state_cache_size);
-		if state_db.journal_db().is_empty() {
+		let written_genesis = state_db.journal_db().is_empty();
+		if written_genesis {
 			// Sets the correct state root.
 			state_db = spec.ensure_db_good(state_db, &factories)?;
 			let mut batch = DBTransaction::new();
@@ -709,8 +968,19 @@ impl Client {
 			db.write(batch).map_err(ClientError::Database)?;
 		}
 
+		let pinned_state = db.get(::db::COL_EXTRA, PINNED_STATE_KEY).map_err(ClientError::Database)?
+			.map_or_else(BTreeSet::new, |bytes| ::rlp::decode_list::<u64>(&bytes).into_iter().collect());
+
 		let gb = spec.genesis_block();
 		let chain = Arc::new(BlockChain::new(config.blockchain.clone(), &gb, db.clone()));
+
+		if chain.genesis_hash() != spec_genesis {
+			if !config.skip_genesis_check {
+				return Err(ClientError::GenesisMismatch(spec_genesis, chain.genesis_hash()).into());
+			}
+			warn!(target: "client", "!!! Genesis check skipped: the database genesis block {:x} doesn't match the spec's {:x}.", chain.genesis_hash(), spec_genesis);
+			warn!(target: "client", "!!! Results are unreliable; never run a node like this outside of analysis.");
+		}
 		let tracedb = RwLock::new(TraceDB::new(config.tracing.clone(), db.clone(), chain.clone()));
 
 		trace!("Cleanup journal: DB Earliest = {:?}, Latest = {:?}", state_db.journal_db().earliest_era(), state_db.journal_db().latest_era());
@@ -747,7 +1017,11 @@ impl Client {
 			chain: RwLock::new(chain),
 			tracedb: tracedb,
 			engine: engine,
-			pruning: config.pruning.clone(),
+			pruning: RwLock::new(config.pruning.clone()),
+			minimal_gas_price: RwLock::new(config.minimal_gas_price),
+			known_blocks: Mutex::new(LruCache::new(config.known_block_cache_size)),
+			known_block_hits: AtomicUsize::new(0),
+			known_block_lookups: AtomicUsize::new(0),
 			config: config,
 			db: RwLock::new(db),
 			state_db: RwLock::new(state_db),
@@ -755,6 +1029,14 @@ impl Client {
 			io_channel: Mutex::new(message_channel),
 			notify: RwLock::new(Vec::new()),
 			queue_transactions: AtomicUsize::new(0),
+			future_blocks: Mutex::new(BTreeMap::new()),
+			tx_types: Default::default(),
+			pinned_state: RwLock::new(pinned_state),
+			last_block_timings: RwLock::new(None),
+			storage_full: AtomicBool::new(false),
+			side_chain_blocks: Mutex::new(VecDeque::new()),
+			import_rejections: Mutex::new(VecDeque::new()),
+			written_genesis: if written_genesis { Some(spec_genesis) } else { None },
 			last_hashes: RwLock::new(VecDeque::new()),
 			factories: factories,
 			history: history,
@@ -762,6 +1044,7 @@ impl Client {
 			registrar: registry::Registry::default(),
 			registrar_address,
 			exit_handler: Mutex::new(None),
+			block_filter: RwLock::new(None),
 			importer,
 		});
 
@@ -823,6 +1106,9 @@ impl Client {
 
 	/// Adds an actor to be notified on certain events
 	pub fn add_notify(&self, target: Arc<ChainNotify>) {
+		if let Some(hash) = self.written_genesis {
+			target.genesis_written(hash);
+		}
 		self.notify.write().push(Arc::downgrade(&target));
 	}
 
@@ -831,6 +1117,23 @@ impl Client {
 		*self.exit_handler.lock() = Some(Box::new(f));
 	}
 
+	/// Set a policy consulted for every verified block before it is imported.
+	/// Blocks it returns `false` for are dropped and marked bad, along with their descendants.
+	pub fn set_block_filter(&self, filter: Box<Fn(&Header) -> bool + Send + Sync>) {
+		*self.block_filter.write() = Some(filter);
+	}
+
+	// whether the block filter, if any, lets the block through.
+	fn block_allowed(&self, header: &Header) -> bool {
+		match *self.block_filter.read() {
+			Some(ref filter) if !filter(header) => {
+				warn!(target: "client", "Block #{} ({}) rejected by the block filter", header.number(), header.hash());
+				false
+			},
+			_ => true,
+		}
+	}
+
 	/// Returns engine reference.
 	pub fn engine(&self) -> &EthEngine {
 		&*self.engine
@@ -844,6 +1147,24 @@ impl Client {
 		}
 	}
 
+	// notify of a reorganisation, if the given route retracted any blocks.
+	fn notify_reorg(&self, enacted: &[H256], retracted: &[H256]) {
+		if retracted.is_empty() { return }
+
+		let (mut enacted, mut retracted) = {
+			let chain = self.chain.read();
+			let numbered = |hashes: &[H256]| -> Vec<(u64, H256)> {
+				hashes.iter().filter_map(|h| chain.block_number(h).map(|n| (n, *h))).collect()
+			};
+			(numbered(enacted), numbered(retracted))
+		};
+
+		enacted.sort();
+		retracted.sort_by(|a, b| b.cmp(a));
+
+		self.notify(|notify| notify.reorg(&retracted, &enacted));
+	}
+
 	/// Register an action to be done if a mode/spec_name change happens.
 	pub fn on_user_defaults_change<F>(&self, f: F) where F: 'static + FnMut(Option<Mode>) + Send {
 		*self.on_user_defaults_change.lock() = Some(Box::new(f));
@@ -857,6 +1178,12 @@ impl Client {
 		}
 	}
 
+	/// Block until a block import going on in another thread, e.g. the IO handler's, has been
+	/// committed. Blocks which left the queue before the call are visible to queries afterwards.
+	pub fn wait_for_import(&self) {
+		let _import_lock = self.importer.import_lock.lock();
+	}
+
 	/// The env info as of the best block.
 	pub fn latest_env_info(&self) -> EnvInfo {
 		self.env_info(BlockId::Latest).expect("Best block header always stored; qed")
@@ -937,14 +1264,28 @@ impl Client {
 		// but have at least the minimum number of states.
 		loop {
 			let needs_pruning = state_db.journal_db().is_pruned() &&
-				state_db.journal_db().journal_size() >= self.config.history_mem;
+				(state_db.journal_db().journal_size() >= self.config.history_mem || self.is_storage_full());
 
 			if !needs_pruning { break }
 			match state_db.journal_db().earliest_era() {
 				Some(era) if era + self.history <= number => {
+					// the journal only drops states in order, so keeping a pinned state
+					// means keeping every later one too.
+					match self.pinned_state.read().iter().next() {
+						Some(&pinned) if era >= pinned => {
+							if self.is_storage_full() {
+								warn!(target: "client", "Database size limit reached, but state from pinned block #{} on can't be pruned; unpin it to resume imports", pinned);
+							}
+							break;
+						}
+						_ => {}
+					}
+
 					trace!(target: "client", "Pruning state for ancient era {}", era);
 					match chain.block_hash(era) {
 						Some(ancient_hash) => {
+							self.notify(|notify| notify.state_pruned(era));
+
 							let mut batch = DBTransaction::new();
 							state_db.mark_canonical(&mut batch, era, &ancient_hash)?;
 							self.db.read().write_buffered(batch);
@@ -961,6 +1302,162 @@ impl Client {
 		Ok(())
 	}
 
+	// rebuild the state trie of the given block from `db`, which holds its state as
+	// closed, and compare its root to the header's. on mismatch, block import is halted.
+	fn verify_state_root(&self, header: &Header, db: &HashDB) -> bool {
+		let expected = *header.state_root();
+		let found = match self.rebuild_state_root(db, &expected) {
+			Ok(root) => root,
+			Err(e) => {
+				error!(target: "client", "Failed to read state of block #{}: {}", header.number(), e);
+				H256::zero()
+			}
+		};
+
+		if found == expected {
+			return true;
+		}
+
+		error!(target: "client", "!!! State root mismatch at block #{} ({:x}): expected {:x}, found {:x}.", header.number(), header.hash(), expected, found);
+		error!(target: "client", "!!! The database may be corrupt. The block wasn't imported and block import is halted.");
+		self.enabled.store(false, AtomicOrdering::SeqCst);
+		self.notify(|notify| notify.state_root_mismatch(header.number(), expected, found));
+		false
+	}
+
+	fn rebuild_state_root(&self, db: &HashDB, root: &H256) -> trie::Result<H256> {
+		let trie = TrieDB::new(db, root)?;
+
+		let mut memdb = MemoryDB::new();
+		let mut rebuilt_root = H256::new();
+		{
+			let mut rebuilt = TrieDBMut::new(&mut memdb, &mut rebuilt_root);
+			for item in trie.iter()? {
+				let (key, value) = item?;
+				rebuilt.insert(&key, &value)?;
+			}
+		}
+		Ok(rebuilt_root)
+	}
+
+	/// Mark the database as having reached its size limit. While it is, state
+	/// is pruned down to the minimum history and new blocks are refused.
+	pub fn set_storage_full(&self, full: bool) {
+		if self.storage_full.swap(full, AtomicOrdering::SeqCst) != full {
+			match full {
+				true => warn!(target: "client", "Database size limit reached. Pruning aggressively and refusing new blocks."),
+				false => info!(target: "client", "Database is below its size limit again. Resuming block import."),
+			}
+		}
+
+		// no blocks are imported to trigger pruning, so do it here.
+		if full {
+			let _import_lock = self.importer.import_lock.lock();
+			let state_db = self.state_db.read().boxed_clone();
+			let chain = self.chain.read();
+			if let Err(e) = self.prune_ancient(state_db, &chain) {
+				warn!(target: "client", "Failed to prune ancient state data: {}", e);
+			}
+		}
+	}
+
+	/// Whether the database has reached its size limit.
+	pub fn is_storage_full(&self) -> bool {
+		self.storage_full.load(AtomicOrdering::SeqCst)
+	}
+
+	/// Number of side-chain blocks imported within the last `history` blocks.
+	/// Older ones can't be built upon any more, as their state is gone.
+	pub fn side_chain_block_count(&self) -> usize {
+		let mut side_chain_blocks = self.side_chain_blocks.lock();
+		self.forget_old_side_chain_blocks(&mut side_chain_blocks);
+		side_chain_blocks.len()
+	}
+
+	// drop side-chain blocks which fell out of the pruning history.
+	fn forget_old_side_chain_blocks(&self, side_chain_blocks: &mut VecDeque<(u64, H256)>) {
+		let best_block_number = self.chain.read().best_block_number();
+		let history = self.history;
+		side_chain_blocks.retain(|&(number, _)| number + history >= best_block_number);
+	}
+
+	/// Blocks most recently rejected on import, oldest first, with the reason.
+	/// Only the last 64 rejections are kept.
+	pub fn recent_import_rejections(&self) -> Vec<(H256, ImportRejection)> {
+		self.note_queue_rejections();
+		self.import_rejections.lock().iter().cloned().collect()
+	}
+
+	// blocks rejected by the verifiers never reach the importer, so take them from the queue.
+	fn note_queue_rejections(&self) {
+		for (hash, reason) in self.importer.block_queue.take_rejected() {
+			let rejection = match reason {
+				Some(reason) => ImportRejection::Unordered(reason),
+				None => ImportRejection::InvalidParent,
+			};
+			self.note_import_rejection(hash, rejection);
+		}
+	}
+
+	fn note_import_rejection(&self, hash: H256, rejection: ImportRejection) {
+		let mut rejections = self.import_rejections.lock();
+		if rejections.len() == MAX_IMPORT_REJECTIONS {
+			rejections.pop_front();
+		}
+		rejections.push_back((hash, rejection));
+	}
+
+	fn note_side_chain_block(&self, number: u64, hash: H256) {
+		trace!(target: "client", "Imported side-chain block #{} ({})", number, hash);
+		{
+			// also done here, so that the blocks don't pile up without `max_side_chain_blocks`.
+			let mut side_chain_blocks = self.side_chain_blocks.lock();
+			self.forget_old_side_chain_blocks(&mut side_chain_blocks);
+			side_chain_blocks.push_back((number, hash));
+		}
+		self.notify(|notify| notify.side_chain_block(hash, number));
+	}
+
+	/// Keep the state of the given block from being pruned until it is unpinned.
+	///
+	/// The journal only drops states in order, so while any block is pinned, no state from
+	/// that block onwards is pruned at all and the database grows without bound. With
+	/// `max_db_size` set, imports stop for good once it is reached. Pins are persisted, so
+	/// one which is forgotten keeps taking effect after restarts.
+	pub fn pin_state(&self, block: BlockNumber) -> Result<(), ClientError> {
+		if self.state_at(BlockId::Number(block)).is_none() {
+			return Err(ClientError::StateUnavailable(block));
+		}
+
+		self.pinned_state.write().insert(block);
+
+		// the state may have been pruned in the meantime.
+		if self.state_at(BlockId::Number(block)).is_none() {
+			self.pinned_state.write().remove(&block);
+			return Err(ClientError::StateUnavailable(block));
+		}
+
+		self.write_pinned_state()
+	}
+
+	/// Allow the state of the given block to be pruned again.
+	pub fn unpin_state(&self, block: BlockNumber) -> Result<(), ClientError> {
+		self.pinned_state.write().remove(&block);
+		self.write_pinned_state()
+	}
+
+	/// Blocks whose state is pinned against pruning.
+	pub fn pinned_state(&self) -> Vec<BlockNumber> {
+		self.pinned_state.read().iter().cloned().collect()
+	}
+
+	fn write_pinned_state(&self) -> Result<(), ClientError> {
+		let pinned: Vec<u64> = self.pinned_state.read().iter().cloned().collect();
+		let mut batch = DBTransaction::new();
+		batch.put(::db::COL_EXTRA, PINNED_STATE_KEY, &::rlp::encode_list::<u64, _>(&pinned));
+		self.db.read().write(batch).map_err(ClientError::Database)
+	}
+
 	fn update_last_hashes(&self, parent: &H256, hash: &H256) {
 		let mut hashes = self.last_hashes.write();
 		if hashes.front().map_or(false, |h| h == parent) {
@@ -976,15 +1473,128 @@ impl Client {
 		trace!(target: "external_tx", "Importing queued");
 		trace_time!("import_queued_transactions");
 		self.queue_transactions.fetch_sub(transactions.len(), AtomicOrdering::SeqCst);
-		let txs: Vec<UnverifiedTransaction> = transactions.iter().filter_map(|bytes| UntrustedRlp::new(bytes).as_val().ok()).collect();
+		// the node's own transactions don't tell about the network.
+		let from_network = peer_id != LOCAL_PEER_ID;
+		let txs: Vec<UnverifiedTransaction> = transactions.iter().filter_map(|bytes| {
+			let tx = UntrustedRlp::new(bytes).as_val::<UnverifiedTransaction>().ok();
+			if from_network {
+				let counter = match tx {
+					Some(ref tx) if tx.chain_id().is_some() => &self.tx_types.replay_protected,
+					Some(_) => &self.tx_types.unprotected,
+					None => &self.tx_types.undecodable,
+				};
+				counter.fetch_add(1, AtomicOrdering::Relaxed);
+			}
+			tx
+		}).collect();
+		self.notify(|notify| {
+			notify.transactions_received(txs.iter().map(|tx| tx.hash()).collect(), peer_id);
+		});
+
+		let minimal_gas_price = *self.minimal_gas_price.read();
+		let (txs, underpriced): (Vec<_>, Vec<_>) = txs.into_iter()
+			.partition(|tx| minimal_gas_price.map_or(true, |min| tx.gas_price >= min));
+		if from_network {
+			self.tx_types.underpriced.fetch_add(underpriced.len(), AtomicOrdering::Relaxed);
+		}
+
 		let hashes: Vec<_> = txs.iter().map(|tx| tx.hash()).collect();
+		let results = match peer_id == LOCAL_PEER_ID && self.config.retain_local_transactions {
+			true => self.importer.miner.import_local_transactions(self, txs),
+			false => self.importer.miner.import_external_transactions(self, txs),
+		};
+		let mut events: Vec<_> = underpriced.iter().map(|tx| TxPoolEvent::Rejected(
+			tx.hash(),
+			format!("Gas price {} is below the minimal gas price {}", tx.gas_price, minimal_gas_price.unwrap_or_default()),
+		)).collect();
+		events.extend(hashes.into_iter().zip(results.iter()).map(|(hash, result)| match *result {
+			Ok(transaction::ImportResult::Current) => TxPoolEvent::Pending(hash),
+			Ok(transaction::ImportResult::Future) => TxPoolEvent::Future(hash),
+			Err(ref e) => TxPoolEvent::Rejected(hash, format!("{}", e)),
+		}));
 		self.notify(|notify| {
-			notify.transactions_received(hashes.clone(), peer_id);
+			for event in &events {
+				notify.tx_pool_event(event.clone());
+			}
 		});
-		let results = self.importer.miner.import_external_transactions(self, txs);
 		results.len()
 	}
 
+	/// Counts of transactions received from the network so far, by format.
+	pub fn tx_type_stats(&self) -> TxTypeStats {
+		TxTypeStats {
+			replay_protected: self.tx_types.replay_protected.load(AtomicOrdering::Relaxed),
+			unprotected: self.tx_types.unprotected.load(AtomicOrdering::Relaxed),
+			undecodable: self.tx_types.undecodable.load(AtomicOrdering::Relaxed),
+			underpriced: self.tx_types.underpriced.load(AtomicOrdering::Relaxed),
+		}
+	}
+
+	/// Hits and lookups of the cache of recently seen blocks so far.
+	pub fn known_block_stats(&self) -> KnownBlockStats {
+		KnownBlockStats {
+			hits: self.known_block_hits.load(AtomicOrdering::Relaxed),
+			lookups: self.known_block_lookups.load(AtomicOrdering::Relaxed),
+		}
+	}
+
+	// whether the block was recently found in the chain. Only blocks in the chain are
+	// remembered: queued ones may still be dropped as bad or by `clear_queue`.
+	fn check_known_block(&self, hash: &H256) -> bool {
+		if self.config.known_block_cache_size == 0 { return false }
+
+		self.known_block_lookups.fetch_add(1, AtomicOrdering::Relaxed);
+		let mut known_blocks = self.known_blocks.lock();
+		if known_blocks.get_mut(hash).is_some() {
+			self.known_block_hits.fetch_add(1, AtomicOrdering::Relaxed);
+			return true;
+		}
+		false
+	}
+
+	fn remember_known_block(&self, hash: H256) {
+		if self.config.known_block_cache_size != 0 {
+			self.known_blocks.lock().insert(hash, ());
+		}
+	}
+
+	/// Change the gas price below which queued transactions are rejected. `None` accepts any price.
+	pub fn set_minimal_gas_price(&self, minimal_gas_price: Option<U256>) {
+		*self.minimal_gas_price.write() = minimal_gas_price;
+	}
+
+	/// Resize the shared state cache, evicting the least recently used entries if it shrinks.
+	pub fn set_state_cache_size(&self, bytes: usize) {
+		self.state_db.write().set_cache_size(bytes);
+	}
+
+	/// Run a block through every verification stage, up to executing it on its parent's
+	/// state, without importing it or writing anything to the database.
+	pub fn verify_block_without_import(&self, header: Header, bytes: Bytes) -> Result<(), ImportRejection> {
+		let engine = &*self.engine;
+		let block = verification::verify_block_basic(&header, &bytes, engine, future_block_tolerance(&self.config))
+			.and_then(|_| verification::verify_block_unordered(header, bytes, engine, self.config.check_seal))
+			.map_err(|e| ImportRejection::Basic(format!("{}", e)))?;
+
+		self.importer.check_and_close_block(&block, self).map(|_| ())
+	}
+
+	/// Where the time importing the most recent block went, if any block was imported yet.
+	pub fn last_block_timings(&self) -> Option<BlockTimings> {
+		*self.last_block_timings.read()
+	}
+
+	/// Approximate memory used by the block queues and the transaction queue.
+	pub fn queue_memory_usage(&self) -> QueueMemory {
+		let (unverified, verifying, verified) = self.importer.block_queue.mem_usage();
+		QueueMemory {
+			unverified_blocks: unverified,
+			verifying_blocks: verifying,
+			verified_blocks: verified,
+			transactions: self.importer.miner.queue_mem_usage(),
+		}
+	}
+
 	/// Get shared miner reference.
 	pub fn miner(&self) -> Arc<Miner> {
 		self.importer.miner.clone()
@@ -1007,6 +1617,55 @@ impl Client {
 		.expect("State root of best block header always valid.")
 	}
 
+	/// Addresses of the accounts in the state cache, least recently used first.
+	pub fn cached_accounts(&self) -> Vec<Address> {
+		self.state_db.read().cached_accounts()
+	}
+
+	/// Read the given accounts from the latest state into the state cache, warming up
+	/// the database's caches on the way. Returns the number of accounts cached, which is
+	/// none if a block was imported meanwhile.
+	pub fn prefetch_accounts(&self, addresses: &[Address]) -> Result<usize, EthcoreError> {
+		let header = self.best_block_header();
+		let state_db = self.state_db.read().boxed_clone_canon(&header.hash());
+		let state_root = header.state_root();
+
+		let accounts = {
+			let trie = self.factories.trie.readonly(state_db.as_hashdb(), &state_root)?;
+			let mut accounts = Vec::with_capacity(addresses.len());
+			for address in addresses {
+				accounts.push((address.clone(), trie.get_with(address, state::Account::from_rlp)?));
+			}
+			accounts
+		};
+
+		Ok(state_db.warm_account_cache(accounts))
+	}
+
+	/// Whether the state trie keeps account addresses next to their hashes (`ClientConfig::fat_db`).
+	pub fn is_fat_db(&self) -> bool {
+		self.factories.trie.is_fat()
+	}
+
+	/// Visit every account in the state of the given block, in trie order, reading the
+	/// trie node by node instead of loading the whole state. Keys are addresses on a fat
+	/// database and address hashes otherwise. Stops at the first error returned by `f`.
+	pub fn for_each_account<F>(&self, block: BlockNumber, mut f: F) -> Result<(), EthcoreError>
+		where F: FnMut(&[u8], BasicAccount) -> Result<(), EthcoreError>
+	{
+		let state = self.state_at(BlockId::Number(block)).ok_or(ClientError::StateUnavailable(block))?;
+		let (root, db) = state.drop();
+		let trie = self.factories.trie.readonly(db.as_hashdb(), &root)?;
+
+		for item in trie.iter()? {
+			let (key, value) = item?;
+			let account: BasicAccount = UntrustedRlp::new(&value).as_val()?;
+			f(&key, account)?;
+		}
+
+		Ok(())
+	}
+
 	/// Attempt to get a copy of a specific block's final state.
 	///
 	/// This will not fail if given BlockId::Latest.
@@ -1062,18 +1721,61 @@ impl Client {
 	pub fn report(&self) -> ClientReport {
 		let mut report = self.report.read().clone();
 		report.state_db_mem = self.state_db.read().mem_used();
+		report.future_blocks_queued = self.future_blocks.lock().values().map(Vec::len).sum();
 		report
 	}
 
 	/// Tick the client.
 	// TODO: manage by real events.
 	pub fn tick(&self, prevent_sleep: bool) {
+		self.import_future_blocks();
 		self.check_garbage();
 		if !prevent_sleep {
 			self.check_snooze();
 		}
 	}
 
+	// current time and the latest timestamp a block may have to be imported right now.
+	fn block_time_bounds(&self) -> (u64, u64) {
+		let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+		(now, now + future_block_tolerance(&self.config))
+	}
+
+	/// Estimate how far the local clock is behind, from how far the timestamps of recent
+	/// canonical blocks and of held back future blocks are ahead of it. A clock running
+	/// ahead looks just like a lagging chain, so it isn't detected.
+	pub fn clock_skew(&self) -> Duration {
+		let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+		let held_back = self.future_blocks.lock().keys().next_back().cloned();
+
+		let chain = self.chain.read();
+		let best = chain.best_block_number();
+		let latest = (best.saturating_sub(CLOCK_SKEW_SAMPLE_BLOCKS - 1)..(best + 1))
+			.filter_map(|number| chain.block_hash(number))
+			.filter_map(|hash| chain.block_header_data(&hash))
+			.map(|header| header.timestamp())
+			.chain(held_back)
+			.max()
+			.unwrap_or(0);
+
+		Duration::from_secs(latest.saturating_sub(now))
+	}
+
+	// import held back blocks whose time has come.
+	fn import_future_blocks(&self) {
+		let ready = {
+			let mut future_blocks = self.future_blocks.lock();
+			let later = future_blocks.split_off(&(self.block_time_bounds().1 + 1));
+			::std::mem::replace(&mut *future_blocks, later)
+		};
+
+		for bytes in ready.into_iter().flat_map(|(_, blocks)| blocks) {
+			if let Err(e) = self.import_block(bytes) {
+				debug!(target: "client", "Failed to import held back future block: {:?}", e);
+			}
+		}
+	}
+
 	fn check_garbage(&self) {
 		self.chain.read().collect_garbage();
 		self.importer.block_queue.collect_garbage();
@@ -1117,6 +1819,12 @@ impl Client {
 	/// Take a snapshot at the given block.
 	/// If the ID given is "latest", this will default to 1000 blocks behind.
 	pub fn take_snapshot<W: snapshot_io::SnapshotWriter + Send>(&self, writer: W, at: BlockId, p: &snapshot::Progress) -> Result<(), EthcoreError> {
+		self.take_snapshot_with_codecs(writer, at, p, &Default::default())
+	}
+
+	/// Take a snapshot at the given block, compressing and hashing chunks with
+	/// the codec `codecs` holds for the engine's current snapshot version.
+	pub fn take_snapshot_with_codecs<W: snapshot_io::SnapshotWriter + Send>(&self, writer: W, at: BlockId, p: &snapshot::Progress, codecs: &snapshot::CodecRegistry) -> Result<(), EthcoreError> {
 		let db = self.state_db.read().journal_db().boxed_clone();
 		let best_block_number = self.chain_info().best_block_number;
 		let block_number = self.block_number(at).ok_or(snapshot::Error::InvalidStartingBlock(at))?;
@@ -1145,11 +1853,56 @@ impl Client {
 			},
 		};
 
-		snapshot::take_snapshot(&*self.engine, &self.chain.read(), start_hash, db.as_hashdb(), writer, p)?;
+		snapshot::take_snapshot(&*self.engine, &self.chain.read(), start_hash, db.as_hashdb(), writer, p, codecs)?;
 
 		Ok(())
 	}
 
+	/// Estimate the size in bytes of a snapshot taken at the given block.
+	/// See `snapshot::estimate_size` for how the estimate is made.
+	pub fn estimate_snapshot_size(&self, at: BlockId) -> Result<u64, EthcoreError> {
+		let db = self.state_db.read().journal_db().boxed_clone();
+		let block_number = self.block_number(at).ok_or(snapshot::Error::InvalidStartingBlock(at))?;
+
+		if db.is_pruned() && self.pruning_info().earliest_state > block_number {
+			return Err(snapshot::Error::OldBlockPrunedDB.into());
+		}
+
+		let hash = self.block_hash(at).ok_or(snapshot::Error::InvalidStartingBlock(at))?;
+		Ok(snapshot::estimate_size(&*self.engine, &self.chain.read(), hash, db.as_hashdb())?)
+	}
+
+	/// Tell observers that the database is about to be closed.
+	pub fn notify_database_closing(&self) {
+		self.notify(|notify| notify.database_closing());
+	}
+
+	/// Tell observers that the snapshot restoration was failed for making no progress.
+	pub fn notify_restoration_stalled(&self) {
+		self.notify(|notify| notify.restoration_stalled());
+	}
+
+	/// Tell observers that the engine rejected a consensus message from the given peer.
+	pub fn notify_invalid_consensus_message(&self, peer_id: usize, reason: String) {
+		self.notify(|notify| notify.invalid_consensus_message(peer_id, reason.clone()));
+	}
+
+	/// Tell observers that the engine caught a validator equivocating at the given height.
+	pub fn notify_equivocation_detected(&self, validator: Address, height: u64) {
+		self.notify(|notify| notify.equivocation_detected(validator, height));
+	}
+
+	/// Get the pruning algorithm of the state database.
+	pub fn pruning_mode(&self) -> journaldb::Algorithm {
+		*self.pruning.read()
+	}
+
+	/// Set the pruning algorithm the state database is opened with when it's
+	/// next replaced, e.g. by a snapshot restoration.
+	pub fn set_pruning(&self, pruning: journaldb::Algorithm) {
+		*self.pruning.write() = pruning;
+	}
+
 	/// Ask the client what the history parameter is.
 	pub fn pruning_history(&self) -> u64 {
 		self.history
@@ -1164,7 +1917,87 @@ impl Client {
 		}
 	}
 
-	fn transaction_address(&self, id: TransactionId) -> Option<TransactionAddress> {
+	/// Get the receipts of the canonical blocks `from..=to`, read under a single lock on the chain.
+	/// Fails if `from` is after `to`, if the range spans more than `MAX_RECEIPTS_RANGE` blocks,
+	/// or if any block in it is missing or has no stored receipts, e.g. because the chain was
+	/// warp synced.
+	pub fn receipts_in_range(&self, from: BlockNumber, to: BlockNumber) -> Result<Vec<BlockReceipts>, ClientError> {
+		if to < from { return Err(ClientError::InvalidRange(from, to)) }
+		if to - from >= MAX_RECEIPTS_RANGE {
+			return Err(ClientError::RangeTooLarge(MAX_RECEIPTS_RANGE));
+		}
+
+		let chain = self.chain.read();
+		// `to + 1` would overflow at `u64::MAX`.
+		(from..to).chain(Some(to)).map(|number| {
+			let hash = chain.block_hash(number).ok_or(ClientError::MissingBlock(number))?;
+			match chain.block_receipts(&hash) {
+				Some(receipts) => Ok(receipts),
+				// the genesis block has no transactions, so no receipts are stored for it.
+				None if number == 0 => Ok(BlockReceipts::new(Vec::new())),
+				None => Err(ClientError::MissingReceipts(number)),
+			}
+		}).collect()
+	}
+
+	/// Get the logs of the block with the given hash which pass `matches`, in order. Works for
+	/// blocks which aren't or are no longer canonical too. Empty if the block or its receipts
+	/// aren't stored.
+	pub fn block_logs<F>(&self, hash: &H256, matches: F) -> Vec<LocalizedLogEntry> where F: Fn(&LogEntry) -> bool {
+		let chain = self.chain.read();
+		let (number, receipts, body) = match (chain.block_number(hash), chain.block_receipts(hash), chain.block_body(hash)) {
+			(Some(number), Some(receipts), Some(body)) => (number, receipts.receipts, body),
+			_ => return Vec::new(),
+		};
+
+		let mut logs = Vec::new();
+		let mut log_index = 0;
+		for (index, (receipt, tx_hash)) in receipts.into_iter().zip(body.transaction_hashes()).enumerate() {
+			for (i, entry) in receipt.logs.into_iter().enumerate() {
+				if matches(&entry) {
+					logs.push(LocalizedLogEntry {
+						entry: entry,
+						block_hash: *hash,
+						block_number: number,
+						transaction_hash: tx_hash,
+						transaction_index: index,
+						transaction_log_index: i,
+						log_index: log_index,
+					});
+				}
+				log_index += 1;
+			}
+		}
+		logs
+	}
+
+	/// Prove the account at `address` and the given storage slots of it as of the given
+	/// canonical block, collecting the trie nodes a light client needs to check them
+	/// against the block's state root. Fails if the block's state has been pruned.
+	pub fn state_proof(&self, address: &Address, storage_keys: &[H256], block: BlockNumber) -> Result<StateProof, ClientError> {
+		let id = BlockId::Number(block);
+		if BlockChainClient::block_header(self, id).is_none() {
+			return Err(ClientError::MissingBlock(block));
+		}
+
+		let state = self.state_at(id).ok_or(ClientError::StateUnavailable(block))?;
+		let account_key = keccak(address);
+		let (account_proof, account) = state.prove_account(account_key).map_err(|e| ClientError::from(*e))?;
+
+		let storage_proof = storage_keys.iter().map(|key| {
+			let (proof, value) = state.prove_storage(account_key, keccak(key)).map_err(|e| ClientError::from(*e))?;
+			Ok(StorageProof { key: *key, value: value, proof: proof })
+		}).collect::<Result<_, ClientError>>()?;
+
+		Ok(StateProof {
+			account: account,
+			account_proof: account_proof,
+			storage_proof: storage_proof,
+		})
+	}
+
+	/// Get the block hash and index of a transaction in the canonical chain.
+	pub fn transaction_address(&self, id: TransactionId) -> Option<TransactionAddress> {
 		match id {
 			TransactionId::Hash(ref hash) => self.chain.read().transaction_address(hash),
 			TransactionId::Location(id, index) => Self::block_hash(&self.chain.read(), id).map(|hash| TransactionAddress {
@@ -1276,9 +2109,10 @@ impl snapshot::DatabaseRestore for Client {
 		db.restore(new_db)?;
 
 		let cache_size = state_db.cache_size();
-		*state_db = StateDB::new(journaldb::new(db.clone(), self.pruning, ::db::COL_STATE), cache_size);
+		*state_db = StateDB::new(journaldb::new(db.clone(), *self.pruning.read(), ::db::COL_STATE), cache_size);
 		*chain = Arc::new(BlockChain::new(self.config.blockchain.clone(), &[], db.clone()));
 		*tracedb = TraceDB::new(self.config.tracing.clone(), db.clone(), chain.clone());
+		self.known_blocks.lock().clear();
 		Ok(())
 	}
 }
@@ -1370,24 +2204,79 @@ impl CallContract for Client {
 	}
 }
 
+// how far ahead of the local clock a block's timestamp may be for it to be imported right away.
+fn future_block_tolerance(config: &ClientConfig) -> u64 {
+	match config.future_block_tolerance.as_secs() {
+		0 => verification::DEFAULT_MAX_TIMESTAMP_DRIFT,
+		secs => secs,
+	}
+}
+
 impl ImportBlock for Client {
 	fn import_block(&self, bytes: Bytes) -> Result<H256, BlockImportError> {
 		use verification::queue::kind::BlockLike;
 		use verification::queue::kind::blocks::Unverified;
 
+		if self.is_storage_full() {
+			return Err(BlockImportError::Other("Database size limit reached".into()));
+		}
+
 		// create unverified block here so the `keccak` calculation can be cached.
 		let unverified = Unverified::new(bytes);
 
 		{
+			// blocks often arrive from several peers, so spare the lookups for recent ones.
+			if self.check_known_block(&unverified.hash()) {
+				return Err(BlockImportError::Import(ImportError::AlreadyInChain));
+			}
 			if self.chain.read().is_known(&unverified.hash()) {
+				self.remember_known_block(unverified.hash());
 				return Err(BlockImportError::Import(ImportError::AlreadyInChain));
 			}
 			let status = self.block_status(BlockId::Hash(unverified.parent_hash()));
 			if status == BlockStatus::Unknown || status == BlockStatus::Pending {
 				return Err(BlockImportError::Block(BlockError::UnknownParent(unverified.parent_hash())));
 			}
+
+			// blocks not on top of the best block start or extend a side chain.
+			if let Some(max) = self.config.max_side_chain_blocks {
+				let forks = status == BlockStatus::InChain && unverified.parent_hash() != self.chain.read().best_block_hash();
+				if forks && self.side_chain_block_count() >= max {
+					return Err(BlockImportError::Other("Side-chain block limit reached".into()));
+				}
+			}
+		}
+
+		// hold back blocks from slightly in the future rather than rejecting them.
+		let timestamp = unverified.header().timestamp();
+		let (now, max_timestamp) = self.block_time_bounds();
+		if timestamp > max_timestamp && timestamp <= now + MAX_FUTURE_BLOCK_HOLD_SECS {
+			let mut future_blocks = self.future_blocks.lock();
+			if future_blocks.values().map(Vec::len).sum::<usize>() < MAX_FUTURE_BLOCKS {
+				trace!(target: "client", "Holding back block {} until its timestamp {}", unverified.hash(), timestamp);
+				let bytes = unverified.into_bytes();
+				let held = future_blocks.entry(timestamp).or_insert_with(Vec::new);
+				// the same block often arrives from several peers.
+				if !held.contains(&bytes) {
+					held.push(bytes);
+				}
+				return Err(BlockImportError::Import(ImportError::HeldBack));
+			}
+		}
+
+		let hash = unverified.hash();
+		match self.importer.block_queue.import(unverified) {
+			Ok(hash) => Ok(hash),
+			Err(e) => {
+				match e {
+					// blocks from the future may be imported later, so they're not rejected.
+					EthcoreError::Block(BlockError::TemporarilyInvalid(_)) => {},
+					EthcoreError::Block(ref err) => self.note_import_rejection(hash, ImportRejection::Basic(format!("{}", err))),
+					_ => {},
+				}
+				Err(e.into())
+			}
 		}
-		Ok(self.importer.block_queue.import(unverified)?)
 	}
 
 	fn import_block_with_receipts(&self, block_bytes: Bytes, receipts_bytes: Bytes) -> Result<H256, BlockImportError> {
@@ -1804,7 +2693,13 @@ impl BlockChainClient for Client {
 	}
 
 	fn queue_info(&self) -> BlockQueueInfo {
-		self.importer.block_queue.queue_info()
+		let mut info = self.importer.block_queue.queue_info();
+		if let Some(limit) = self.config.max_queue_memory {
+			// whatever the transaction queue uses comes out of the block queue's share.
+			let transactions = self.importer.miner.queue_mem_usage();
+			info.max_mem_use = ::std::cmp::min(info.max_mem_use, limit.saturating_sub(transactions));
+		}
+		info
 	}
 
 	fn clear_queue(&self) {
@@ -1906,9 +2801,9 @@ impl BlockChainClient for Client {
 		self.importer.miner.ready_transactions(number, timestamp)
 	}
 
-	fn queue_consensus_message(&self, message: Bytes) {
+	fn queue_consensus_message(&self, message: Bytes, peer_id: usize) {
 		let channel = self.io_channel.lock().clone();
-		if let Err(e) = channel.send(ClientIoMessage::NewMessage(message)) {
+		if let Err(e) = channel.send(ClientIoMessage::NewMessage(message, peer_id)) {
 			debug!("Ignoring the message, error queueing: {}", e);
 		}
 	}
@@ -2038,13 +2933,13 @@ impl ScheduleInfo for Client {
 impl ImportSealedBlock for Client {
 	fn import_sealed_block(&self, block: SealedBlock) -> ImportResult {
 		let h = block.header().hash();
+		let number = block.header().number();
 		let start = Instant::now();
 		let route = {
 			// scope for self.import_lock
 			let _import_lock = self.importer.import_lock.lock();
 			trace_time!("import_sealed_block");
 
-			let number = block.header().number();
 			let block_data = block.rlp_bytes();
 			let header = block.header().clone();
 
@@ -2069,6 +2964,8 @@ impl ImportSealedBlock for Client {
 				},
 			);
 		});
+		self.notify_reorg(&enacted, &retracted);
+		self.notify(|notify| notify.block_sealed(h, number));
 		self.db.read().flush().expect("DB flush failed.");
 		Ok(h)
 	}
@@ -2113,6 +3010,10 @@ impl super::traits::EngineClient for Client {
 		self.notify(|notify| notify.broadcast(message.clone()));
 	}
 
+	fn authority_status_changed(&self, is_authority: bool) {
+		self.notify(|notify| notify.authority_status_changed(is_authority));
+	}
+
 	fn epoch_transition_for(&self, parent_hash: H256) -> Option<::engines::EpochTransition> {
 		self.chain.read().epoch_transition_for(parent_hash)
 	}
@@ -18,6 +18,7 @@ use std::str::FromStr;
 use std::path::Path;
 use std::fmt::{Display, Formatter, Error as FmtError};
 
+use ethereum_types::{H256, U256};
 use mode::Mode as IpcMode;
 use verification::{VerifierType, QueueConfig};
 use journaldb;
@@ -163,11 +164,130 @@ pub struct ClientConfig {
 	pub history_mem: usize,
 	/// Check seal valididity on block import
 	pub check_seal: bool,
+	/// Number of threads state chunks are decompressed and rebuilt on during snapshot
+	/// restoration. The rebuilt chunks are still merged into the state trie one at a time.
+	/// `None` uses one per CPU, up to four.
+	pub restore_threads: Option<usize>,
+	/// Maximum number of snapshot chunks queued for restoration. 0 means unlimited.
+	pub max_pending_restore_chunks: usize,
+	/// Whether this configuration was tuned for low-memory machines.
+	pub low_memory: bool,
+	/// Warn when the IO handler spends longer than this on a single message. `None` disables the watchdog.
+	pub watchdog_threshold: Option<Duration>,
+	/// How far ahead of the local clock a block's timestamp may be for it to be imported right away.
+	/// Later blocks are held back until their time comes. The block verifier allows the same.
+	/// Zero uses the default of 15 seconds.
+	pub future_block_tolerance: Duration,
+	/// UNSAFE: open the database even if its genesis block doesn't match the spec's.
+	/// Only meant for forensic analysis with a deliberately modified spec.
+	pub skip_genesis_check: bool,
+	/// Maximum on-disk size of the client database in bytes. Once exceeded, state is pruned
+	/// down to the minimum history and no new blocks are imported until the size drops again.
+	pub max_db_size: Option<u64>,
+	/// Rebuild the state trie after every imported block and check it against the header's
+	/// state root, halting import on mismatch. Very expensive; meant to catch corruption early.
+	pub verify_state_root_on_import: bool,
+	/// Limit on the memory used by the block queues and the transaction queue together, in bytes.
+	/// Advisory only: once reached, the block queue reports itself full so that sync stops
+	/// fetching blocks, but `import_block` still accepts blocks over the limit.
+	pub max_queue_memory: Option<usize>,
+	/// Interval in blocks between periodic snapshots. `None` uses the default period.
+	pub snapshot_every_n_blocks: Option<u64>,
+	/// Periodic snapshots aren't taken until at least this many blocks past genesis
+	/// have been imported, so that fresh nodes don't take tiny snapshots while syncing.
+	pub snapshot_start_delay_blocks: u64,
+	/// Queue transactions the node submits itself (under `LOCAL_PEER_ID`) as local,
+	/// so that they are never dropped from the pool due to its limits.
+	pub retain_local_transactions: bool,
+	/// Maximum number of side-chain blocks imported within the pruning history. Once reached,
+	/// blocks which don't build on the best block are refused. `None` means no limit.
+	pub max_side_chain_blocks: Option<usize>,
+	/// Number of block verification threads. `None` uses one per CPU, up to 8.
+	pub verifier_threads: Option<usize>,
+	/// Refuse blocks which would retract more than this many canonical blocks. `None` means no limit.
+	///
+	/// This protects against cheap deep reorgs on chains with little hashpower, at the cost of
+	/// deviating from pure total-difficulty fork choice: after a long network partition the node
+	/// may stay on a minority fork, disagreeing with its peers about the canonical chain, and has
+	/// to be recovered manually, e.g. by resyncing. The limit should be well above the depth of
+	/// reorgs seen in normal operation.
+	pub max_reorg_depth: Option<u64>,
+	/// Fail a snapshot restoration once no chunk was restored for this long, so that it can
+	/// be retried. `None` waits for chunks indefinitely.
+	pub restoration_stall_timeout: Option<Duration>,
+	/// Remember the most recently used accounts when the client service stops and read them
+	/// back into the caches in the background on the next start.
+	pub warm_cache_on_start: bool,
+	/// Keep the database of a failed snapshot restoration in a timestamped directory
+	/// under the snapshot path instead of deleting it, so that the failure can be looked into.
+	pub keep_failed_restoration: bool,
+	/// Reject transactions queued for import which pay a lower gas price than this, before
+	/// they reach the transaction pool. Can be changed at runtime with `Client::set_minimal_gas_price`.
+	pub minimal_gas_price: Option<U256>,
+	/// Refuse to start unless the spec's genesis block has this hash. Checked before
+	/// anything is written to a fresh database, so that a wrong spec file is caught early.
+	pub expected_genesis: Option<H256>,
+	/// If the database is corrupt, attempt to repair it. Should that fail too, move it aside and
+	/// start over with a fresh one, restoring the latest local snapshot into it if there is one.
+	pub auto_recover_db: bool,
+	/// Only start a requested snapshot once no block was imported for this long, so that
+	/// snapshots don't compete with imports for the disk. Requests made meanwhile are
+	/// deferred, and only the most recent one is taken. `None` starts snapshots right away.
+	pub snapshot_require_idle: Option<Duration>,
+	/// Log a one-line summary of the best block, import rate, queues, database size and
+	/// received transactions under the `service` target this often. `None` disables it.
+	pub stats_log_interval: Option<Duration>,
+	/// Maximum number of distinct peers whose chunks are accepted during a snapshot
+	/// restoration. 0 means unlimited.
+	pub max_restoration_peers: usize,
+	/// Number of hashes of blocks found in the chain which are remembered, so that blocks
+	/// received again are refused without further checks. 0 disables the cache.
+	pub known_block_cache_size: usize,
+	/// Memory budget in megabytes of the database snapshot chunks are restored into,
+	/// covering its caches and write buffers, which otherwise grow with the restored
+	/// state. `None` uses `db_cache_size`.
+	pub restore_state_cache_mb: Option<usize>,
+	/// Take a snapshot at the best block when the client service is shut down with
+	/// `shutdown`, unless the latest local snapshot is only a few blocks old.
+	pub snapshot_on_shutdown: bool,
+	/// Longest the shutdown waits for the snapshot of `snapshot_on_shutdown`. The previous
+	/// snapshot is kept if it takes longer. `None` waits until it is written.
+	pub shutdown_snapshot_timeout: Option<Duration>,
+}
+
+impl ClientConfig {
+	/// Configuration for machines with little memory, e.g. single-board computers.
+	/// Shrinks every cache and queue and restores snapshots one chunk at a time.
+	pub fn low_memory() -> Self {
+		let mb = 1024 * 1024;
+		let mut config = ClientConfig::default();
+
+		// db cache size, in megabytes
+		config.db_cache_size = Some(8);
+		// in bytes
+		config.queue.max_mem_use = 8 * mb;
+		config.queue.max_queue_size = 2000;
+		config.queue.verifier_settings.num_verifiers = 1;
+		// in bytes
+		config.tracing.max_cache_size = 2 * mb;
+		config.tracing.pref_cache_size = 1 * mb;
+		// in bytes
+		config.state_cache_size = 4 * mb;
+		config.jump_table_size = 1 * mb;
+		config.history_mem = 8 * mb;
+
+		config.restore_threads = Some(1);
+		config.max_pending_restore_chunks = 4;
+		// in megabytes
+		config.restore_state_cache_mb = Some(4);
+		config.low_memory = true;
+		config
+	}
 }
 
 #[cfg(test)]
 mod test {
-	use super::{DatabaseCompactionProfile, Mode};
+	use super::{ClientConfig, DatabaseCompactionProfile, Mode};
 
 	#[test]
 	fn test_default_compaction_profile() {
@@ -185,4 +305,17 @@ mod test {
 	fn test_mode_default() {
 		assert_eq!(Mode::default(), Mode::Active);
 	}
+
+	#[test]
+	fn test_low_memory_reduces_caches() {
+		let default = ClientConfig::default();
+		let low = ClientConfig::low_memory();
+
+		assert!(low.low_memory);
+		assert!(low.queue.max_mem_use < default.queue.max_mem_use);
+		assert!(low.tracing.max_cache_size < default.tracing.max_cache_size);
+		assert_eq!(low.restore_threads, Some(1));
+		assert_eq!(low.max_pending_restore_chunks, 4);
+		assert_eq!(low.restore_state_cache_mb, Some(4));
+	}
 }
@@ -15,6 +15,7 @@
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::fmt::{Display, Formatter, Error as FmtError};
+use ethereum_types::H256;
 use util_error::UtilError;
 use kvdb;
 use trie::TrieError;
@@ -28,6 +29,20 @@ pub enum Error {
 	Database(kvdb::Error),
 	/// Util error
 	Util(UtilError),
+	/// Block with the given number is missing from the local chain.
+	MissingBlock(u64),
+	/// State of the block with the given number is not available.
+	StateUnavailable(u64),
+	/// Genesis block in the database or spec doesn't match the expected one. (expected, found)
+	GenesisMismatch(H256, H256),
+	/// Configured with zero verification threads.
+	NoVerifierThreads,
+	/// Requested block range spans more than the given number of blocks.
+	RangeTooLarge(u64),
+	/// Requested block range ends before it starts. (from, to)
+	InvalidRange(u64, u64),
+	/// Receipts of the block with the given number are not stored.
+	MissingReceipts(u64),
 }
 
 impl From<TrieError> for Error {
@@ -54,6 +69,14 @@ impl Display for Error {
 			Error::Trie(ref err) => write!(f, "{}", err),
 			Error::Util(ref err) => write!(f, "{}", err),
 			Error::Database(ref s) => write!(f, "Database error: {}", s),
+			Error::MissingBlock(n) => write!(f, "Block #{} is missing from the local chain", n),
+			Error::StateUnavailable(n) => write!(f, "State of block #{} is not available", n),
+			Error::GenesisMismatch(ref expected, ref found) =>
+				write!(f, "Genesis block {:x} doesn't match the expected genesis block {:x}", found, expected),
+			Error::NoVerifierThreads => write!(f, "At least one verification thread is required"),
+			Error::RangeTooLarge(max) => write!(f, "Block range spans more than {} blocks", max),
+			Error::InvalidRange(from, to) => write!(f, "Block range #{}..#{} ends before it starts", from, to),
+			Error::MissingReceipts(n) => write!(f, "Receipts of block #{} are not stored", n),
 		}
 	}
 }
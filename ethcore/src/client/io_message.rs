@@ -31,11 +31,13 @@ pub enum ClientIoMessage {
 	BeginRestoration(ManifestData),
 	/// Feed a state chunk to the snapshot service
 	FeedStateChunk(H256, Bytes),
+	/// Feed a state chunk received from the given peer to the snapshot service
+	FeedStateChunkFrom(usize, H256, Bytes),
 	/// Feed a block chunk to the snapshot service
 	FeedBlockChunk(H256, Bytes),
 	/// Take a snapshot for the block with given number.
 	TakeSnapshot(u64),
-	/// New consensus message received.
-	NewMessage(Bytes)
+	/// New consensus message received from the given peer.
+	NewMessage(Bytes, usize)
 }
 
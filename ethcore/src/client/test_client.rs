@@ -805,7 +805,7 @@ impl BlockChainClient for TestBlockChainClient {
 		self.miner.import_external_transactions(self, txs);
 	}
 
-	fn queue_consensus_message(&self, message: Bytes) {
+	fn queue_consensus_message(&self, message: Bytes, _peer_id: usize) {
 		self.spec.engine.handle_message(&message).unwrap();
 	}
 
@@ -885,6 +885,8 @@ impl super::traits::EngineClient for TestBlockChainClient {
 
 	fn broadcast_consensus_message(&self, _message: Bytes) {}
 
+	fn authority_status_changed(&self, _is_authority: bool) {}
+
 	fn epoch_transition_for(&self, _block_hash: H256) -> Option<::engines::EpochTransition> {
 		None
 	}
@@ -311,8 +311,8 @@ pub trait BlockChainClient : Sync + Send + AccountData + BlockChain + CallContra
 	/// Queue transactions for importing.
 	fn queue_transactions(&self, transactions: Vec<Bytes>, peer_id: usize);
 
-	/// Queue conensus engine message.
-	fn queue_consensus_message(&self, message: Bytes);
+	/// Queue conensus engine message received from the given peer.
+	fn queue_consensus_message(&self, message: Bytes, peer_id: usize);
 
 	/// List all transactions that are allowed into the next block.
 	fn ready_transactions(&self) -> Vec<PendingTransaction>;
@@ -433,6 +433,9 @@ pub trait EngineClient: Sync + Send + ChainInfo {
 	/// Broadcast a consensus message to the network.
 	fn broadcast_consensus_message(&self, message: Bytes);
 
+	/// Report that the engine's signer joined or left the set of authorities.
+	fn authority_status_changed(&self, is_authority: bool);
+
 	/// Get the transition to the epoch the given parent hash is part of
 	/// or transitions to.
 	/// This will give the epoch that any children of this parent belong to.
@@ -393,6 +393,8 @@ pub struct AuthorityRound {
 	empty_steps_transition: u64,
 	maximum_empty_steps: usize,
 	machine: EthereumMachine,
+	// whether the signer was in the validator set when last checked.
+	authority_status: Mutex<Option<bool>>,
 }
 
 // header-chain validator.
@@ -624,6 +626,7 @@ impl AuthorityRound {
 				empty_steps_transition: our_params.empty_steps_transition,
 				maximum_empty_steps: our_params.maximum_empty_steps,
 				machine: machine,
+				authority_status: Mutex::new(None),
 			});
 
 		// Do not initialize timeouts for tests.
@@ -679,6 +682,22 @@ impl AuthorityRound {
 			}
 		}
 	}
+
+	// tell the client when the signer joined or left the validator set, or when it's first known.
+	fn report_authority_status(&self, is_authority: bool) {
+		{
+			let mut status = self.authority_status.lock();
+			if *status == Some(is_authority) { return }
+			*status = Some(is_authority);
+		}
+
+		trace!(target: "engine", "authority status changed: {}", is_authority);
+		if let Some(ref weak) = *self.client.read() {
+			if let Some(c) = weak.upgrade() {
+				c.authority_status_changed(is_authority);
+			}
+		}
+	}
 }
 
 fn unix_now() -> Duration {
@@ -767,6 +786,10 @@ impl Engine<EthereumMachine> for AuthorityRound {
 		info
 	}
 
+	fn target_block_time(&self) -> Option<Duration> {
+		Some(Duration::from_secs(self.step.duration as u64))
+	}
+
 	fn maximum_uncle_count(&self, block: BlockNumber) -> usize {
 		if block >= self.maximum_uncle_count_transition {
 			self.maximum_uncle_count
@@ -877,6 +900,10 @@ impl Engine<EthereumMachine> for AuthorityRound {
 			&active_set as &_
 		};
 
+		if let Some(address) = self.signer.read().address() {
+			self.report_authority_status(validators.contains(header.parent_hash(), &address));
+		}
+
 		if is_step_proposer(validators, header.parent_hash(), step, header.author()) {
 			// this is guarded against by `can_propose` unless the block was signed
 			// on the same step (implies same key) and on a different node.
@@ -1677,6 +1704,35 @@ mod tests {
 		assert!(notify.messages.read().contains(&empty_step_rlp));
 	}
 
+	#[test]
+	fn reports_authority_status_changes() {
+		let (spec, tap, accounts) = setup_empty_steps();
+		let addr1 = accounts[0];
+		let outsider = tap.insert_account(keccak("outsider").into(), "").unwrap();
+
+		let engine = &*spec.engine;
+		let genesis_header = spec.genesis_header();
+		let db1 = spec.ensure_db_good(get_temp_state_db(), &Default::default()).unwrap();
+
+		let last_hashes = Arc::new(vec![genesis_header.hash()]);
+		let b1 = OpenBlock::new(engine, Default::default(), false, db1, &genesis_header, last_hashes.clone(), addr1, (3141562.into(), 31415620.into()), vec![], false).unwrap();
+		let b1 = b1.close_and_lock();
+
+		let client = generate_dummy_client(0);
+		let notify = Arc::new(TestNotify::default());
+		client.add_notify(notify.clone());
+		engine.register_client(Arc::downgrade(&client) as _);
+
+		engine.set_signer(tap.clone(), addr1, "1".into());
+		engine.generate_seal(b1.block(), &genesis_header);
+		engine.generate_seal(b1.block(), &genesis_header);
+		assert_eq!(*notify.authority_statuses.read(), vec![true]);
+
+		engine.set_signer(tap.clone(), outsider, "".into());
+		engine.generate_seal(b1.block(), &genesis_header);
+		assert_eq!(*notify.authority_statuses.read(), vec![true, false]);
+	}
+
 	#[test]
 	fn seal_with_empty_steps() {
 		let (spec, tap, accounts) = setup_empty_steps();
@@ -38,6 +38,7 @@ pub use self::tendermint::Tendermint;
 use std::sync::{Weak, Arc};
 use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::time::Duration;
 
 use self::epoch::PendingTransition;
 
@@ -67,6 +68,8 @@ pub enum EngineError {
 	NotAuthorized(Address),
 	/// The same author issued different votes at the same step.
 	DoubleVote(Address),
+	/// The validator signed conflicting consensus messages at the given height.
+	Equivocation(Address, u64),
 	/// The received block is from an incorrect proposer.
 	NotProposer(Mismatch<Address>),
 	/// Message was not expected.
@@ -88,6 +91,7 @@ impl fmt::Display for EngineError {
 		use self::EngineError::*;
 		let msg = match *self {
 			DoubleVote(ref address) => format!("Author {} issued too many blocks.", address),
+			Equivocation(ref address, height) => format!("Validator {} signed conflicting messages at height {}.", address, height),
 			NotProposer(ref mis) => format!("Author is not a current proposer: {}", mis),
 			NotAuthorized(ref address) => format!("Signer {} is not authorized.", address),
 			UnexpectedMessage => "This Engine should not be fed messages.".into(),
@@ -192,6 +196,9 @@ pub trait Engine<M: Machine>: Sync + Send {
 	/// The number of generations back that uncles can be.
 	fn maximum_uncle_age(&self) -> usize { 6 }
 
+	/// Time between blocks the engine aims for. `None` if it has no fixed target.
+	fn target_block_time(&self) -> Option<Duration> { None }
+
 	/// Block transformation functions, before the transactions.
 	/// `epoch_begin` set to true if this block kicks off an epoch.
 	fn on_new_block(
@@ -312,6 +319,9 @@ pub trait Engine<M: Machine>: Sync + Send {
 	/// Trigger next step of the consensus engine.
 	fn step(&self) {}
 
+	/// Periodic maintenance, called on every client tick (about every 5 seconds).
+	fn on_tick(&self, _client: &M::EngineClient) {}
+
 	/// Stops any services that the may hold the Engine and makes it safe to drop.
 	fn stop(&self) {}
 
@@ -530,7 +530,7 @@ impl Engine<EthereumMachine> for Tendermint {
 			if let Some(double) = self.votes.vote(message.clone(), sender) {
 				let height = message.vote_step.height as BlockNumber;
 				self.validators.report_malicious(&sender, height, height, ::rlp::encode(&double).into_vec());
-				return Err(EngineError::DoubleVote(sender));
+				return Err(EngineError::Equivocation(sender, height));
 			}
 			trace!(target: "engine", "Handling a valid {:?} from {}.", message, sender);
 			self.handle_valid_message(&message);
@@ -1015,6 +1015,27 @@ mod tests {
 		assert!(notify.messages.read().contains(&prevote_future));
 	}
 
+	#[test]
+	fn reports_equivocation() {
+		let (spec, tap) = setup();
+		let engine = spec.engine;
+
+		let v0 = insert_and_unlock(&tap, "0");
+		let signed = |block_hash: H256| {
+			let mi = message_info_rlp(&VoteStep::new(1, 0, Step::Prevote), Some(block_hash));
+			message_full_rlp(&tap.sign(v0, None, keccak(&mi)).unwrap().into(), &mi)
+		};
+
+		assert!(engine.handle_message(&signed(H256::from(1))).is_ok());
+		match engine.handle_message(&signed(H256::from(2))) {
+			Err(EngineError::Equivocation(validator, height)) => {
+				assert_eq!(validator, v0);
+				assert_eq!(height, 1);
+			},
+			other => panic!("expected equivocation, got {:?}", other),
+		}
+	}
+
 	#[test]
 	fn seal_submission() {
 		use ethkey::{Generator, Random};
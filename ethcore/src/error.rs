@@ -153,6 +153,8 @@ pub enum ImportError {
 	AlreadyQueued,
 	/// Already marked as bad from a previous import (could mean parent is bad).
 	KnownBad,
+	/// From the near future, held back and imported once its timestamp is reached.
+	HeldBack,
 }
 
 impl fmt::Display for ImportError {
@@ -161,6 +163,7 @@ impl fmt::Display for ImportError {
 			ImportError::AlreadyInChain => "block already in chain",
 			ImportError::AlreadyQueued => "block already in the block queue",
 			ImportError::KnownBad => "block known to be bad",
+			ImportError::HeldBack => "block from the future held back until its timestamp",
 		};
 
 		f.write_fmt(format_args!("Block import error ({})", msg))
@@ -244,6 +247,13 @@ pub enum Error {
 	Ethkey(EthkeyError),
 	/// Account Provider error.
 	AccountProvider(AccountsError),
+	/// The miner can't seal blocks for the consensus engine.
+	MinerEngineMismatch {
+		/// How the miner is set up to seal.
+		miner_kind: String,
+		/// Name of the spec's consensus engine.
+		engine: String,
+	},
 }
 
 impl fmt::Display for Error {
@@ -268,6 +278,8 @@ impl fmt::Display for Error {
 			Error::Engine(ref err) => err.fmt(f),
 			Error::Ethkey(ref err) => err.fmt(f),
 			Error::AccountProvider(ref err) => err.fmt(f),
+			Error::MinerEngineMismatch { ref miner_kind, ref engine } =>
+				f.write_fmt(format_args!("Miner set up for {} can't seal blocks for the {} engine", miner_kind, engine)),
 		}
 	}
 }
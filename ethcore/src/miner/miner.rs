@@ -34,6 +34,7 @@ use ethcore_miner::transaction_queue::{
 	PrioritizationStrategy,
 	AccountDetails,
 	TransactionOrigin,
+	PoolGcReport,
 };
 use futures_cpupool::CpuPool;
 use ethcore_miner::work_notify::{WorkPoster, NotifyWork};
@@ -349,10 +350,69 @@ impl Miner {
 		Miner::new_raw(Default::default(), GasPricer::new_fixed(20_000_000_000u64.into()), spec, None)
 	}
 
-	fn forced_sealing(&self) -> bool {
+	/// Whether sealing was forced on by configuration or work notifiers.
+	pub fn forced_sealing(&self) -> bool {
 		self.options.force_sealing || !self.notifiers.read().is_empty()
 	}
 
+	/// Name of the consensus engine the miner seals for.
+	pub fn engine_name(&self) -> &str {
+		self.engine.name()
+	}
+
+	/// The account provider the miner was created with, if any.
+	pub fn account_provider(&self) -> Option<Arc<AccountProvider>> {
+		self.accounts.clone()
+	}
+
+	/// Whether new work is pushed to external sealers.
+	pub fn notifies_work(&self) -> bool {
+		!self.notifiers.read().is_empty()
+	}
+
+	/// Approximate heap memory used by the transaction queue, in bytes.
+	pub fn queue_mem_usage(&self) -> usize {
+		self.transaction_queue.read().mem_usage()
+	}
+
+	/// Evict stale, expired, unaffordable and underpriced transactions from the queue right away,
+	/// rather than waiting for the next block. Returns how many were evicted per reason.
+	pub fn gc_transaction_pool<C: AccountData + BlockChain>(&self, chain: &C) -> PoolGcReport {
+		let fetch_account = |a: &Address| AccountDetails {
+			nonce: chain.latest_nonce(a),
+			balance: chain.latest_balance(a),
+		};
+		let time = chain.chain_info().best_block_number;
+		let report = self.transaction_queue.write().gc(&fetch_account, time);
+		debug!(target: "miner", "Transaction pool garbage collected: {:?}", report);
+		report
+	}
+
+	/// Import transactions submitted by this node through the network import path.
+	/// They are queued as local, so pool limits never push them out.
+	pub fn import_local_transactions<C: MiningBlockChainClient>(
+		&self,
+		client: &C,
+		transactions: Vec<UnverifiedTransaction>
+	) -> Vec<Result<TransactionImportResult, Error>> {
+		trace!(target: "own_tx", "Importing {} local transactions", transactions.len());
+		let results = {
+			let mut transaction_queue = self.transaction_queue.write();
+			self.add_transactions_to_queue(
+				client, transactions, TransactionOrigin::Local, None, &mut transaction_queue
+			)
+		};
+
+		// --------------------------------------------------------------------------
+		// | NOTE Code below requires transaction_queue and sealing_work locks.     |
+		// | Make sure to release the locks before calling that method.             |
+		// --------------------------------------------------------------------------
+		if results.iter().any(|r| r.is_ok()) && self.options.reseal_on_own_tx && self.tx_reseal_allowed() {
+			self.update_sealing(client);
+		}
+		results
+	}
+
 	/// Clear all pending block states
 	pub fn clear(&self) {
 		self.sealing_work.lock().queue.reset();
@@ -619,6 +679,34 @@ impl Miner {
 		}
 	}
 
+	/// Seal and import a block with the pending transactions right away, ignoring the
+	/// reseal schedule. Returns the hash of the imported block, or `None` if the engine
+	/// didn't produce a seal, e.g. because it's not this node's turn. Engines which
+	/// don't seal internally, or which only propose blocks, never seal on demand.
+	pub fn seal_block_now<C>(&self, chain: &C) -> Result<Option<H256>, Error>
+		where C: AccountData + BlockChain + BlockProducer + CallContract + SealedBlockImporter
+	{
+		if self.engine.seals_internally() != Some(true) {
+			trace!(target: "miner", "seal_block_now: engine is not sealing internally");
+			return Ok(None);
+		}
+
+		let (block, _) = self.prepare_block(chain);
+		let parent_header = match chain.block_header(BlockId::Hash(*block.header().parent_hash())) {
+			Some(hdr) => hdr.decode(),
+			None => return Ok(None),
+		};
+
+		match self.engine.generate_seal(block.block(), &parent_header) {
+			Seal::Regular(seal) => {
+				*self.next_mandatory_reseal.write() = Instant::now() + self.options.reseal_max_period;
+				let sealed = block.lock().seal(&*self.engine, seal)?;
+				Ok(Some(chain.import_sealed_block(sealed)?))
+			},
+			Seal::Proposal(_) | Seal::None => Ok(None),
+		}
+	}
+
 	/// Prepares work which has to be done to seal.
 	fn prepare_work(&self, block: ClosedBlock, original_work_hash: Option<H256>) {
 		let (work, is_new) = {
@@ -1464,6 +1552,21 @@ mod tests {
 		assert!(miner.prepare_work_sealing(&client));
 	}
 
+	#[test]
+	fn should_import_local_transactions_as_local() {
+		// given
+		let client = TestBlockChainClient::default();
+		let miner = miner();
+		let transaction = transaction();
+		let hash = transaction.hash();
+		// when
+		let res = miner.import_local_transactions(&client, vec![transaction.into()]).pop().unwrap();
+
+		// then
+		assert_eq!(res.unwrap(), TransactionImportResult::Current);
+		assert!(miner.local_transactions().contains_key(&hash));
+	}
+
 	#[test]
 	fn should_not_seal_unless_enabled() {
 		let miner = miner();
@@ -1499,6 +1602,24 @@ mod tests {
 		assert_eq!(client.chain_info().best_block_number, 4 as BlockNumber);
 	}
 
+	#[test]
+	fn seals_block_on_demand() {
+		let spec = Spec::new_instant();
+		let miner = Miner::with_spec(&spec);
+
+		let client = generate_dummy_client(2);
+		assert_eq!(miner.seal_block_now(&*client).unwrap(), None);
+
+		assert_eq!(miner.import_external_transactions(&*client, vec![transaction_with_chain_id(spec.chain_id()).into()]).pop().unwrap().unwrap(), TransactionImportResult::Current);
+
+		let hash = miner.seal_block_now(&*client).unwrap().unwrap();
+		assert_eq!(client.chain_info().best_block_number, 3 as BlockNumber);
+		assert_eq!(client.chain_info().best_block_hash, hash);
+
+		let miner = Miner::with_spec(&Spec::new_test());
+		assert_eq!(miner.seal_block_now(&*client).unwrap(), None);
+	}
+
 	#[test]
 	fn should_fail_setting_engine_signer_on_pow() {
 		let spec = Spec::new_pow_test_spec;
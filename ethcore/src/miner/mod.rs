@@ -46,6 +46,7 @@ pub use self::miner::{Miner, MinerOptions, Banning, PendingSet, GasPricer, GasPr
 pub use self::stratum::{Stratum, Error as StratumError, Options as StratumOptions};
 
 pub use ethcore_miner::local_transactions::Status as LocalTransactionStatus;
+pub use ethcore_miner::transaction_queue::PoolGcReport;
 
 use std::collections::BTreeMap;
 
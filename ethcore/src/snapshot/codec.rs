@@ -0,0 +1,131 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Chunk codecs: how snapshot chunks are compressed and hashed.
+//!
+//! Every snapshot format version maps to a codec. Restoration picks the codec
+//! by the manifest's version, and new snapshots are written with the codec
+//! registered for the version the engine currently produces.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use error::Error;
+use ethereum_types::H256;
+use hash::keccak;
+use snappy;
+
+use super::{MIN_SUPPORTED_STATE_CHUNK_VERSION, STATE_CHUNK_VERSION};
+
+/// Compression and hashing of snapshot chunks.
+pub trait ChunkCodec: Send + Sync {
+	/// Hash of a compressed chunk, as listed in the manifest.
+	fn hash(&self, chunk: &[u8]) -> H256;
+
+	/// Compress `raw` into `out`, replacing its contents.
+	fn compress(&self, raw: &[u8], out: &mut Vec<u8>);
+
+	/// Decompress `chunk` into `out`, returning the decompressed length.
+	/// Implementations must refuse chunks which decompress to more than `max_len` bytes.
+	fn decompress(&self, chunk: &[u8], out: &mut Vec<u8>, max_len: usize) -> Result<usize, Error>;
+}
+
+/// Snappy compression and keccak hashing, as used by all versions so far.
+pub struct SnappyKeccak;
+
+impl ChunkCodec for SnappyKeccak {
+	fn hash(&self, chunk: &[u8]) -> H256 {
+		keccak(chunk)
+	}
+
+	fn compress(&self, raw: &[u8], out: &mut Vec<u8>) {
+		out.resize(snappy::max_compressed_len(raw.len()), 0);
+		let len = snappy::compress_into(raw, out);
+		out.truncate(len);
+	}
+
+	fn decompress(&self, chunk: &[u8], out: &mut Vec<u8>, max_len: usize) -> Result<usize, Error> {
+		let expected_len = snappy::decompressed_len(chunk)?;
+		if expected_len > max_len {
+			trace!(target: "snapshot", "Discarding large chunk: {} vs {}", expected_len, max_len);
+			return Err(::snapshot::Error::ChunkTooLarge.into());
+		}
+
+		Ok(snappy::decompress_into(chunk, out)?)
+	}
+}
+
+/// Mapping from snapshot format version to chunk codec.
+#[derive(Clone)]
+pub struct CodecRegistry {
+	codecs: HashMap<u64, Arc<ChunkCodec>>,
+}
+
+impl CodecRegistry {
+	/// Create a registry without any codecs.
+	pub fn empty() -> Self {
+		CodecRegistry { codecs: HashMap::new() }
+	}
+
+	/// Use `codec` for snapshots of the given version, replacing any previous one.
+	pub fn register(&mut self, version: u64, codec: Arc<ChunkCodec>) {
+		self.codecs.insert(version, codec);
+	}
+
+	/// Get the codec for snapshots of the given version.
+	pub fn get(&self, version: u64) -> Result<Arc<ChunkCodec>, ::snapshot::Error> {
+		self.codecs.get(&version).cloned().ok_or(::snapshot::Error::NoChunkCodec(version))
+	}
+}
+
+impl Default for CodecRegistry {
+	fn default() -> Self {
+		let mut registry = CodecRegistry::empty();
+		let codec: Arc<ChunkCodec> = Arc::new(SnappyKeccak);
+		for version in MIN_SUPPORTED_STATE_CHUNK_VERSION..STATE_CHUNK_VERSION + 1 {
+			registry.register(version, codec.clone());
+		}
+		registry
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{ChunkCodec, CodecRegistry, SnappyKeccak};
+	use hash::keccak;
+
+	#[test]
+	fn snappy_keccak_roundtrip() {
+		let raw = vec![42u8; 1024];
+		let mut compressed = Vec::new();
+		SnappyKeccak.compress(&raw, &mut compressed);
+		assert_eq!(SnappyKeccak.hash(&compressed), keccak(&compressed));
+
+		let mut decompressed = Vec::new();
+		let len = SnappyKeccak.decompress(&compressed, &mut decompressed, raw.len()).unwrap();
+		assert_eq!(&decompressed[..len], &raw[..]);
+		assert!(SnappyKeccak.decompress(&compressed, &mut decompressed, raw.len() - 1).is_err());
+	}
+
+	#[test]
+	fn registry_selects_by_version() {
+		let registry = CodecRegistry::default();
+		assert!(registry.get(1).is_ok());
+		assert!(registry.get(2).is_ok());
+		assert!(registry.get(3).is_err());
+		assert!(CodecRegistry::empty().get(2).is_err());
+	}
+}
@@ -47,6 +47,8 @@ pub enum Error {
 	UnrecognizedCodeState(u8),
 	/// Restoration aborted.
 	RestorationAborted,
+	/// Restoration is still missing chunks. (state, block)
+	RestorationIncomplete(u32, u32),
 	/// Trie error.
 	Trie(TrieError),
 	/// Decoder error.
@@ -65,6 +67,19 @@ pub enum Error {
 	BadEpochProof(u64),
 	/// Wrong chunk format.
 	WrongChunkFormat(String),
+	/// The snapshot directory can't be written to.
+	SnapshotDirReadOnly,
+	/// Manifest has a snapshot format version outside of the supported range.
+	UnsupportedSnapshotVersion {
+		/// Version of the snapshot.
+		found: u64,
+		/// Lowest and highest supported versions.
+		supported: (u64, u64),
+	},
+	/// No chunk codec is registered for the snapshot version.
+	NoChunkCodec(u64),
+	/// Chunk doesn't hash to the hash it was fed under (expected, found).
+	ChunkHashMismatch(H256, H256),
 }
 
 impl fmt::Display for Error {
@@ -82,6 +97,8 @@ impl fmt::Display for Error {
 			Error::MissingCode(ref missing) => write!(f, "Incomplete snapshot: {} contract codes not found.", missing.len()),
 			Error::UnrecognizedCodeState(state) => write!(f, "Unrecognized code encoding ({})", state),
 			Error::RestorationAborted => write!(f, "Snapshot restoration aborted."),
+			Error::RestorationIncomplete(state, block) =>
+				write!(f, "Snapshot restoration is missing {} state and {} block chunks.", state, block),
 			Error::Io(ref err) => err.fmt(f),
 			Error::Decoder(ref err) => err.fmt(f),
 			Error::Trie(ref err) => err.fmt(f),
@@ -91,6 +108,11 @@ impl fmt::Display for Error {
 			Error::SnapshotsUnsupported => write!(f, "Snapshots unsupported by consensus engine."),
 			Error::BadEpochProof(i) => write!(f, "Bad epoch proof for transition to epoch {}", i),
 			Error::WrongChunkFormat(ref msg) => write!(f, "Wrong chunk format: {}", msg),
+			Error::SnapshotDirReadOnly => write!(f, "Snapshot directory is read-only; snapshot creation is disabled."),
+			Error::UnsupportedSnapshotVersion { found, supported: (min, max) } =>
+				write!(f, "Snapshot format version {} is not supported. Supported versions are {} to {}.", found, min, max),
+			Error::NoChunkCodec(version) => write!(f, "No chunk codec registered for snapshot version {}.", version),
+			Error::ChunkHashMismatch(ref expected, ref found) => write!(f, "Chunk has wrong hash. Expected {:?}, got {:?}", expected, found),
 		}
 	}
 }
@@ -24,6 +24,8 @@ use std::collections::HashMap;
 use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::fs::{self, File};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
 
 use bytes::Bytes;
 use ethereum_types::H256;
@@ -138,28 +140,80 @@ impl SnapshotWriter for PackedWriter {
 	}
 }
 
+/// Storage for the chunks of loose snapshots.
+/// Chunks are addressed by the directory of the snapshot they belong to and their hash,
+/// while the manifest always stays in that directory. Since chunks are named by the hash
+/// of their contents, stores keeping them elsewhere, e.g. in object storage, may ignore
+/// the directory; they are then left behind when the snapshot directory is removed.
+pub trait ChunkStore: Send + Sync {
+	/// Store a chunk of the snapshot in `dir`.
+	fn write_chunk(&self, dir: &Path, hash: H256, chunk: &[u8]) -> io::Result<()>;
+
+	/// Read a chunk of the snapshot in `dir`.
+	fn read_chunk(&self, dir: &Path, hash: H256) -> io::Result<Bytes>;
+
+	/// List the hashes of the chunks stored for the snapshot in `dir`.
+	fn list_chunks(&self, dir: &Path) -> io::Result<Vec<H256>>;
+}
+
+/// Default chunk store, keeping each chunk in a file named by its hash right in the snapshot directory.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FlatChunkStore;
+
+impl ChunkStore for FlatChunkStore {
+	fn write_chunk(&self, dir: &Path, hash: H256, chunk: &[u8]) -> io::Result<()> {
+		let mut file = File::create(dir.join(format!("{:x}", hash)))?;
+		file.write_all(chunk)
+	}
+
+	fn read_chunk(&self, dir: &Path, hash: H256) -> io::Result<Bytes> {
+		let mut buf = Vec::new();
+		let mut file = File::open(dir.join(format!("{:x}", hash)))?;
+		file.read_to_end(&mut buf)?;
+		Ok(buf)
+	}
+
+	fn list_chunks(&self, dir: &Path) -> io::Result<Vec<H256>> {
+		let mut hashes = Vec::new();
+		for entry in fs::read_dir(dir)? {
+			let name = entry?.file_name();
+			// skips the manifest and anything else stored alongside.
+			match name.to_str() {
+				Some(name) if name.len() == 64 => hashes.extend(H256::from_str(name).ok()),
+				_ => {},
+			}
+		}
+		Ok(hashes)
+	}
+}
+
 /// A "loose" writer writes chunk files into a directory.
 pub struct LooseWriter {
 	dir: PathBuf,
+	store: Arc<ChunkStore>,
 }
 
 impl LooseWriter {
 	/// Create a new LooseWriter which will write into the given directory,
 	/// creating it if it doesn't exist.
 	pub fn new(path: PathBuf) -> io::Result<Self> {
+		Self::with_store(path, Arc::new(FlatChunkStore))
+	}
+
+	/// Create a new LooseWriter which will write the manifest into the given directory,
+	/// creating it if it doesn't exist, and the chunks into `store`.
+	pub fn with_store(path: PathBuf, store: Arc<ChunkStore>) -> io::Result<Self> {
 		fs::create_dir_all(&path)?;
 
 		Ok(LooseWriter {
 			dir: path,
+			store: store,
 		})
 	}
 
 	// writing logic is the same for both kinds of chunks.
 	fn write_chunk(&mut self, hash: H256, chunk: &[u8]) -> io::Result<()> {
-		let file_path = self.dir.join(format!("{:x}", hash));
-		let mut file = File::create(file_path)?;
-		file.write_all(chunk)?;
-		Ok(())
+		self.store.write_chunk(&self.dir, hash, chunk)
 	}
 }
 
@@ -295,12 +349,19 @@ impl SnapshotReader for PackedReader {
 pub struct LooseReader {
 	dir: PathBuf,
 	manifest: ManifestData,
+	store: Arc<ChunkStore>,
 }
 
 impl LooseReader {
 	/// Create a new `LooseReader` which will read the manifest and chunk data from
 	/// the given directory.
-	pub fn new(mut dir: PathBuf) -> Result<Self, ::error::Error> {
+	pub fn new(dir: PathBuf) -> Result<Self, ::error::Error> {
+		Self::with_store(dir, Arc::new(FlatChunkStore))
+	}
+
+	/// Create a new `LooseReader` which will read the manifest from the given
+	/// directory and the chunk data from `store`.
+	pub fn with_store(mut dir: PathBuf, store: Arc<ChunkStore>) -> Result<Self, ::error::Error> {
 		let mut manifest_buf = Vec::new();
 
 		dir.push("MANIFEST");
@@ -314,6 +375,7 @@ impl LooseReader {
 		Ok(LooseReader {
 			dir: dir,
 			manifest: manifest,
+			store: store,
 		})
 	}
 }
@@ -324,11 +386,7 @@ impl SnapshotReader for LooseReader {
 	}
 
 	fn chunk(&self, hash: H256) -> io::Result<Bytes> {
-		let path = self.dir.join(format!("{:x}", hash));
-		let mut buf = Vec::new();
-		let mut file = File::open(&path)?;
-		file.read_to_end(&mut buf)?;
-		Ok(buf)
+		self.store.read_chunk(&self.dir, hash)
 	}
 }
 
@@ -338,7 +396,7 @@ mod tests {
 	use hash::keccak;
 
 	use snapshot::ManifestData;
-	use super::{SnapshotWriter, SnapshotReader, PackedWriter, PackedReader, LooseWriter, LooseReader, SNAPSHOT_VERSION};
+	use super::{SnapshotWriter, SnapshotReader, PackedWriter, PackedReader, LooseWriter, LooseReader, ChunkStore, FlatChunkStore, SNAPSHOT_VERSION};
 
 	const STATE_CHUNKS: &'static [&'static [u8]] = &[b"dog", b"cat", b"hello world", b"hi", b"notarealchunk"];
 	const BLOCK_CHUNKS: &'static [&'static [u8]] = &[b"hello!", b"goodbye!", b"abcdefg", b"hijklmnop", b"qrstuvwxy", b"and", b"z"];
@@ -421,4 +479,69 @@ mod tests {
 			reader.chunk(hash.clone()).unwrap();
 		}
 	}
+
+	#[test]
+	fn loose_write_and_read_through_store() {
+		use std::io;
+		use std::path::Path;
+		use std::sync::Arc;
+		use bytes::Bytes;
+		use ethereum_types::H256;
+
+		// keeps chunks in a subdirectory per first byte of their hash.
+		struct ShardedStore;
+		impl ChunkStore for ShardedStore {
+			fn write_chunk(&self, dir: &Path, hash: H256, chunk: &[u8]) -> io::Result<()> {
+				let shard = dir.join(format!("{:02x}", hash[0]));
+				::std::fs::create_dir_all(&shard)?;
+				FlatChunkStore.write_chunk(&shard, hash, chunk)
+			}
+
+			fn read_chunk(&self, dir: &Path, hash: H256) -> io::Result<Bytes> {
+				FlatChunkStore.read_chunk(&dir.join(format!("{:02x}", hash[0])), hash)
+			}
+
+			fn list_chunks(&self, dir: &Path) -> io::Result<Vec<H256>> {
+				let mut hashes = Vec::new();
+				for entry in ::std::fs::read_dir(dir)? {
+					let path = entry?.path();
+					if path.is_dir() {
+						hashes.extend(FlatChunkStore.list_chunks(&path)?);
+					}
+				}
+				Ok(hashes)
+			}
+		}
+
+		let tempdir = TempDir::new("").unwrap();
+		let store = Arc::new(ShardedStore);
+		let mut writer = LooseWriter::with_store(tempdir.path().into(), store.clone()).unwrap();
+
+		let state_hashes: Vec<_> = STATE_CHUNKS.iter().map(|chunk| keccak(chunk)).collect();
+		for (hash, chunk) in state_hashes.iter().zip(STATE_CHUNKS) {
+			writer.write_state_chunk(*hash, chunk).unwrap();
+		}
+
+		let manifest = ManifestData {
+			version: SNAPSHOT_VERSION,
+			state_hashes: state_hashes.clone(),
+			block_hashes: Vec::new(),
+			state_root: keccak(b"notarealroot"),
+			block_number: 12345678987654321,
+			block_hash: keccak(b"notarealblock)"),
+		};
+		writer.finish(manifest).unwrap();
+
+		let mut listed = store.list_chunks(tempdir.path()).unwrap();
+		let mut expected = state_hashes.clone();
+		listed.sort();
+		expected.sort();
+		assert_eq!(listed, expected);
+		assert!(FlatChunkStore.list_chunks(tempdir.path()).unwrap().is_empty());
+
+		let reader = LooseReader::with_store(tempdir.path().into(), store).unwrap();
+		for (hash, chunk) in state_hashes.iter().zip(STATE_CHUNKS) {
+			assert_eq!(&reader.chunk(*hash).unwrap()[..], *chunk);
+		}
+	}
 }
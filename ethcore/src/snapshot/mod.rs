@@ -31,13 +31,14 @@ use header::Header;
 use ids::BlockId;
 
 use ethereum_types::{H256, U256};
+use ethkey::{self, Public, Signature};
 use hashdb::HashDB;
 use kvdb::DBValue;
-use snappy;
 use bytes::Bytes;
 use parking_lot::Mutex;
 use journaldb::{self, Algorithm, JournalDB};
 use kvdb::KeyValueDB;
+use memorydb::MemoryDB;
 use trie::{TrieDB, TrieDBMut, Trie, TrieMut};
 use rlp::{RlpStream, UntrustedRlp};
 use bloom_journal::Bloom;
@@ -52,12 +53,13 @@ use rand::{Rng, OsRng};
 
 pub use self::error::Error;
 
+pub use self::codec::{ChunkCodec, CodecRegistry, SnappyKeccak};
 pub use self::consensus::*;
-pub use self::service::{Service, DatabaseRestore};
-pub use self::traits::SnapshotService;
+pub use self::service::{Service, DatabaseRestore, ManifestSigner};
+pub use self::traits::{SnapshotService, RestoreParallelism, PeerId};
 pub use self::watcher::Watcher;
 pub use types::snapshot_manifest::ManifestData;
-pub use types::restoration_status::RestorationStatus;
+pub use types::restoration_status::{RestorationStatus, RestorationFailure};
 pub use types::basic_account::BasicAccount;
 
 pub mod io;
@@ -65,6 +67,7 @@ pub mod service;
 
 mod account;
 mod block;
+mod codec;
 mod consensus;
 mod error;
 mod watcher;
@@ -122,13 +125,15 @@ impl Progress {
 
 }
 /// Take a snapshot using the given blockchain, starting block hash, and database, writing into the given writer.
+/// Chunks are compressed and hashed with the codec registered for the engine's current snapshot version.
 pub fn take_snapshot<W: SnapshotWriter + Send>(
 	engine: &EthEngine,
 	chain: &BlockChain,
 	block_at: H256,
 	state_db: &HashDB,
 	writer: W,
-	p: &Progress
+	p: &Progress,
+	codecs: &CodecRegistry,
 ) -> Result<(), Error> {
 	let start_header = chain.block_header(&block_at)
 		.ok_or(Error::InvalidStartingBlock(BlockId::Hash(block_at)))?;
@@ -140,10 +145,12 @@ pub fn take_snapshot<W: SnapshotWriter + Send>(
 	let writer = Mutex::new(writer);
 	let chunker = engine.snapshot_components().ok_or(Error::SnapshotsUnsupported)?;
 	let snapshot_version = chunker.current_version();
+	let codec = codecs.get(snapshot_version)?;
 	let (state_hashes, block_hashes) = scope(|scope| {
 		let writer = &writer;
-		let block_guard = scope.spawn(move || chunk_secondary(chunker, chain, block_at, writer, p));
-		let state_res = chunk_state(state_db, state_root, writer, p);
+		let codec = &*codec;
+		let block_guard = scope.spawn(move || chunk_secondary(chunker, chain, block_at, writer, p, codec));
+		let state_res = chunk_state(state_db, state_root, writer, p, codec);
 
 		state_res.and_then(|state_hashes| {
 			block_guard.join().map(|block_hashes| (state_hashes, block_hashes))
@@ -174,18 +181,17 @@ pub fn take_snapshot<W: SnapshotWriter + Send>(
 /// Secondary chunks are engine-specific, but they intend to corroborate the state data
 /// in the state chunks.
 /// Returns a list of chunk hashes, with the first having the blocks furthest from the genesis.
-pub fn chunk_secondary<'a>(mut chunker: Box<SnapshotComponents>, chain: &'a BlockChain, start_hash: H256, writer: &Mutex<SnapshotWriter + 'a>, progress: &'a Progress) -> Result<Vec<H256>, Error> {
+pub fn chunk_secondary<'a>(mut chunker: Box<SnapshotComponents>, chain: &'a BlockChain, start_hash: H256, writer: &Mutex<SnapshotWriter + 'a>, progress: &'a Progress, codec: &'a ChunkCodec) -> Result<Vec<H256>, Error> {
 	let mut chunk_hashes = Vec::new();
-	let mut snappy_buffer = vec![0; snappy::max_compressed_len(PREFERRED_CHUNK_SIZE)];
+	let mut compressed = Vec::new();
 
 	{
 		let mut chunk_sink = |raw_data: &[u8]| {
-			let compressed_size = snappy::compress_into(raw_data, &mut snappy_buffer);
-			let compressed = &snappy_buffer[..compressed_size];
-			let hash = keccak(&compressed);
+			codec.compress(raw_data, &mut compressed);
+			let hash = codec.hash(&compressed);
 			let size = compressed.len();
 
-			writer.lock().write_block_chunk(hash, compressed)?;
+			writer.lock().write_block_chunk(hash, &compressed)?;
 			trace!(target: "snapshot", "wrote secondary chunk. hash: {:x}, size: {}, uncompressed size: {}",
 				hash, size, raw_data.len());
 
@@ -210,9 +216,10 @@ struct StateChunker<'a> {
 	hashes: Vec<H256>,
 	rlps: Vec<Bytes>,
 	cur_size: usize,
-	snappy_buffer: Vec<u8>,
+	compressed: Vec<u8>,
 	writer: &'a Mutex<SnapshotWriter + 'a>,
 	progress: &'a Progress,
+	codec: &'a ChunkCodec,
 }
 
 impl<'a> StateChunker<'a> {
@@ -237,11 +244,11 @@ impl<'a> StateChunker<'a> {
 
 		let raw_data = stream.out();
 
-		let compressed_size = snappy::compress_into(&raw_data, &mut self.snappy_buffer);
-		let compressed = &self.snappy_buffer[..compressed_size];
-		let hash = keccak(&compressed);
+		self.codec.compress(&raw_data, &mut self.compressed);
+		let compressed_size = self.compressed.len();
+		let hash = self.codec.hash(&self.compressed);
 
-		self.writer.lock().write_state_chunk(hash, compressed)?;
+		self.writer.lock().write_state_chunk(hash, &self.compressed)?;
 		trace!(target: "snapshot", "wrote state chunk. size: {}, uncompressed size: {}", compressed_size, raw_data.len());
 
 		self.progress.accounts.fetch_add(num_entries, Ordering::SeqCst);
@@ -264,16 +271,17 @@ impl<'a> StateChunker<'a> {
 ///
 /// Returns a list of hashes of chunks created, or any error it may
 /// have encountered.
-pub fn chunk_state<'a>(db: &HashDB, root: &H256, writer: &Mutex<SnapshotWriter + 'a>, progress: &'a Progress) -> Result<Vec<H256>, Error> {
+pub fn chunk_state<'a>(db: &HashDB, root: &H256, writer: &Mutex<SnapshotWriter + 'a>, progress: &'a Progress, codec: &'a ChunkCodec) -> Result<Vec<H256>, Error> {
 	let account_trie = TrieDB::new(db, &root)?;
 
 	let mut chunker = StateChunker {
 		hashes: Vec::new(),
 		rlps: Vec::new(),
 		cur_size: 0,
-		snappy_buffer: vec![0; snappy::max_compressed_len(PREFERRED_CHUNK_SIZE)],
+		compressed: Vec::new(),
 		writer: writer,
 		progress: progress,
+		codec: codec,
 	};
 
 	let mut used_code = HashSet::new();
@@ -302,6 +310,58 @@ pub fn chunk_state<'a>(db: &HashDB, root: &H256, writer: &Mutex<SnapshotWriter +
 	Ok(chunker.hashes)
 }
 
+/// Estimate the size in bytes of a snapshot starting at the given block, without taking it.
+///
+/// Chunks are measured before compression, so the estimate errs on the high side.
+/// The whole state is still walked, which takes a while on large chains.
+pub fn estimate_size(engine: &EthEngine, chain: &BlockChain, block_at: H256, state_db: &HashDB) -> Result<u64, Error> {
+	let start_header = chain.block_header(&block_at)
+		.ok_or(Error::InvalidStartingBlock(BlockId::Hash(block_at)))?;
+	let mut chunker = engine.snapshot_components().ok_or(Error::SnapshotsUnsupported)?;
+
+	let mut secondary_size = 0u64;
+	{
+		let mut chunk_sink = |raw_data: &[u8]| -> ::std::io::Result<()> {
+			secondary_size += raw_data.len() as u64;
+			Ok(())
+		};
+		chunker.chunk_all(chain, block_at, &mut chunk_sink, PREFERRED_CHUNK_SIZE)?;
+	}
+
+	let state_size = estimate_state_size(state_db, start_header.state_root())?;
+	Ok(state_size + secondary_size)
+}
+
+/// Estimate the uncompressed size in bytes of the state chunks for the given root.
+pub fn estimate_state_size(db: &HashDB, root: &H256) -> Result<u64, Error> {
+	let account_trie = TrieDB::new(db, root)?;
+	let mut used_code = HashSet::new();
+	let mut size = 0u64;
+
+	for item in account_trie.iter()? {
+		let (account_key, account_data) = item?;
+		let account = ::rlp::decode(&*account_data);
+		let account_key_hash = H256::from_slice(&account_key);
+
+		let account_db = AccountDB::from_hash(db, account_key_hash);
+		let fat_rlps = account::to_fat_rlps(&account_key_hash, &account, &account_db, &mut used_code, PREFERRED_CHUNK_SIZE, PREFERRED_CHUNK_SIZE)?;
+		size += fat_rlps.iter().map(|rlp| rlp.len() as u64).sum::<u64>();
+	}
+
+	Ok(size)
+}
+
+// hash of a manifest, as signed by a `ManifestSigner`.
+fn manifest_hash(manifest: &ManifestData) -> H256 {
+	keccak(manifest.clone().into_rlp())
+}
+
+/// Check that a snapshot manifest was signed with the key of `public`, e.g. by a node
+/// distributing trusted snapshots. The chunks themselves are covered by their hashes in the manifest.
+pub fn verify_manifest_signature(manifest: &ManifestData, signature: &Signature, public: &Public) -> bool {
+	ethkey::verify_public(public, signature, &manifest_hash(manifest)).unwrap_or(false)
+}
+
 /// Used to rebuild the state trie piece by piece.
 pub struct StateRebuilder {
 	db: Box<JournalDB>,
@@ -310,6 +370,7 @@ pub struct StateRebuilder {
 	missing_code: HashMap<H256, Vec<H256>>, // maps code hashes to lists of accounts missing that code.
 	bloom: Bloom,
 	known_storage_roots: HashMap<H256, H256>, // maps account hashes to last known storage root. Only filled for last account per chunk.
+	restored_ranges: Vec<(H256, H256)>, // first and last account hashes of every chunk fed so far.
 }
 
 impl StateRebuilder {
@@ -322,29 +383,87 @@ impl StateRebuilder {
 			missing_code: HashMap::new(),
 			bloom: StateDB::load_bloom(&*db),
 			known_storage_roots: HashMap::new(),
+			restored_ranges: Vec::new(),
 		}
 	}
 
 	/// Feed an uncompressed state chunk into the rebuilder.
 	pub fn feed(&mut self, chunk: &[u8], flag: &AtomicBool) -> Result<(), ::error::Error> {
+		let prepared = StateRebuilder::prepare(chunk, flag)?;
+		self.commit(prepared, flag)
+	}
+
+	/// Rebuild the storage of the accounts in an uncompressed state chunk on their own,
+	/// so that several chunks can be prepared in parallel. Accounts on the chunk's edges
+	/// may continue in other chunks and are left for `commit`, as is code found elsewhere.
+	pub fn prepare(chunk: &[u8], flag: &AtomicBool) -> Result<PreparedChunk, ::error::Error> {
 		let rlp = UntrustedRlp::new(chunk);
+		let count = rlp.item_count()?;
+		let mut prepared = PreparedChunk {
+			storage: MemoryDB::new(),
+			accounts: Vec::with_capacity(count),
+			edges: Vec::new(),
+			status: RebuiltStatus::default(),
+		};
+
+		// the accounts inside a chunk are in no other chunk, so their storage starts out empty.
+		let known_code = HashMap::new();
+		let known_storage_roots = HashMap::new();
+		for (i, account_rlp) in rlp.iter().enumerate() {
+			if !flag.load(Ordering::SeqCst) { return Err(Error::RestorationAborted.into()) }
+
+			if i == 0 || i + 1 == count {
+				prepared.edges.push((i, account_rlp.as_raw().to_vec()));
+				prepared.accounts.push((H256::new(), Vec::new()));
+				continue;
+			}
+
+			let account = rebuild_account(&mut prepared.storage, account_rlp, &known_code, &known_storage_roots, &mut prepared.status)?;
+			prepared.accounts.push(account);
+		}
+
+		Ok(prepared)
+	}
+
+	/// Merge a prepared chunk into the state. Chunks have to be committed one at a time.
+	pub fn commit(&mut self, prepared: PreparedChunk, flag: &AtomicBool) -> Result<(), ::error::Error> {
+		let PreparedChunk { mut storage, mut accounts, edges, mut status } = prepared;
 		let empty_rlp = StateAccount::new_basic(U256::zero(), U256::zero()).rlp();
-		let mut pairs = Vec::with_capacity(rlp.item_count()?);
-
-		// initialize the pairs vector with empty values so we have slots to write into.
-		pairs.resize(rlp.item_count()?, (H256::new(), Vec::new()));
-
-		let status = rebuild_accounts(
-			self.db.as_hashdb_mut(),
-			rlp,
-			&mut pairs,
-			&self.known_code,
-			&mut self.known_storage_roots,
-			flag
-		)?;
 
+		for (key, (value, rc)) in storage.drain() {
+			for _ in 0..rc {
+				self.db.as_hashdb_mut().emplace(key, value.clone());
+			}
+		}
+
+		for (i, raw) in edges {
+			if !flag.load(Ordering::SeqCst) { return Err(Error::RestorationAborted.into()) }
+
+			accounts[i] = rebuild_account(
+				self.db.as_hashdb_mut(),
+				UntrustedRlp::new(&raw),
+				&self.known_code,
+				&self.known_storage_roots,
+				&mut status,
+			)?;
+		}
+
+		for &(ref hash, ref rlp) in accounts.first().into_iter().chain(accounts.last()) {
+			self.known_storage_roots.insert(*hash, ::rlp::decode::<BasicAccount>(rlp).storage_root);
+		}
+
+		// copy code which an earlier chunk included, the rest is filled in once it shows up.
 		for (addr_hash, code_hash) in status.missing_code {
-			self.missing_code.entry(code_hash).or_insert_with(Vec::new).push(addr_hash);
+			match self.known_code.get(&code_hash) {
+				Some(&first_with) => {
+					let code = AccountDB::from_hash(self.db.as_hashdb(), first_with)
+						.get(&code_hash)
+						.ok_or_else(|| Error::MissingCode(vec![first_with]))?;
+
+					AccountDBMut::from_hash(self.db.as_hashdb_mut(), addr_hash).emplace(code_hash, code);
+				}
+				None => self.missing_code.entry(code_hash).or_insert_with(Vec::new).push(addr_hash),
+			}
 		}
 
 		// patch up all missing code. must be done after collecting all new missing code entries.
@@ -358,6 +477,10 @@ impl StateRebuilder {
 		}
 
 		let backing = self.db.backing().clone();
+		let range = match (accounts.first(), accounts.last()) {
+			(Some(first), Some(last)) => Some((first.0, last.0)),
+			_ => None,
+		};
 
 		// batch trie writes
 		{
@@ -367,7 +490,7 @@ impl StateRebuilder {
 				TrieDBMut::new(self.db.as_hashdb_mut(), &mut self.state_root)
 			};
 
-			for (hash, thin_rlp) in pairs {
+			for (hash, thin_rlp) in accounts {
 				if !flag.load(Ordering::SeqCst) { return Err(Error::RestorationAborted.into()) }
 
 				if &thin_rlp[..] != &empty_rlp[..] {
@@ -383,9 +506,27 @@ impl StateRebuilder {
 		self.db.inject(&mut batch)?;
 		backing.write_buffered(batch);
 		trace!(target: "snapshot", "current state root: {:?}", self.state_root);
+
+		if let Some(range) = range {
+			self.restored_ranges.push(range);
+		}
 		Ok(())
 	}
 
+	/// Get an account which has been completely restored from the chunks fed so far.
+	/// The first and last accounts of a chunk may have storage in other chunks,
+	/// so they're only given once they appear inside another chunk.
+	pub fn restored_account(&self, account_hash: &H256) -> Result<Option<BasicAccount>, ::error::Error> {
+		let restored = self.restored_ranges.iter()
+			.any(|&(ref first, ref last)| first < account_hash && account_hash < last);
+		if !restored {
+			return Ok(None);
+		}
+
+		let account_trie = TrieDB::new(self.db.as_hashdb(), &self.state_root)?;
+		Ok(account_trie.get(account_hash)?.map(|thin_rlp| ::rlp::decode(&thin_rlp)))
+	}
+
 	/// Finalize the restoration. Check for accounts missing code and make a dummy
 	/// journal entry.
 	/// Once all chunks have been fed, there should be nothing missing.
@@ -404,6 +545,15 @@ impl StateRebuilder {
 	pub fn state_root(&self) -> H256 { self.state_root }
 }
 
+/// A state chunk whose accounts were rebuilt by `StateRebuilder::prepare`, waiting
+/// to be committed.
+pub struct PreparedChunk {
+	storage: MemoryDB, // storage and code of the accounts inside the chunk.
+	accounts: Vec<(H256, Bytes)>, // account hashes and thin RLP, with blanks for the edges.
+	edges: Vec<(usize, Bytes)>, // indexes and fat RLP of the accounts on the chunk's edges.
+	status: RebuiltStatus,
+}
+
 #[derive(Default)]
 struct RebuiltStatus {
 	// new code that's become available. (code_hash, code, addr_hash)
@@ -411,68 +561,50 @@ struct RebuiltStatus {
 	missing_code: Vec<(H256, H256)>, // accounts that are missing code.
 }
 
-// rebuild a set of accounts and their storage.
-// returns a status detailing newly-loaded code and accounts missing code.
-fn rebuild_accounts(
+// rebuild an account and its storage, returning its hash and thin RLP.
+// newly-loaded code and code which is missing are noted in `status`.
+fn rebuild_account(
 	db: &mut HashDB,
-	account_fat_rlps: UntrustedRlp,
-	out_chunk: &mut [(H256, Bytes)],
+	account_rlp: UntrustedRlp,
 	known_code: &HashMap<H256, H256>,
-	known_storage_roots: &mut HashMap<H256, H256>,
-	abort_flag: &AtomicBool,
-) -> Result<RebuiltStatus, ::error::Error> {
-	let mut status = RebuiltStatus::default();
-	for (account_rlp, out) in account_fat_rlps.into_iter().zip(out_chunk.iter_mut()) {
-		if !abort_flag.load(Ordering::SeqCst) { return Err(Error::RestorationAborted.into()) }
-
-		let hash: H256 = account_rlp.val_at(0)?;
-		let fat_rlp = account_rlp.at(1)?;
-
-		let thin_rlp = {
-
-			// fill out the storage trie and code while decoding.
-			let (acc, maybe_code) = {
-				let mut acct_db = AccountDBMut::from_hash(db, hash);
-				let storage_root = known_storage_roots.get(&hash).cloned().unwrap_or(H256::zero());
-				account::from_fat_rlp(&mut acct_db, fat_rlp, storage_root)?
-			};
+	known_storage_roots: &HashMap<H256, H256>,
+	status: &mut RebuiltStatus,
+) -> Result<(H256, Bytes), ::error::Error> {
+	let hash: H256 = account_rlp.val_at(0)?;
+	let fat_rlp = account_rlp.at(1)?;
+
+	// fill out the storage trie and code while decoding.
+	let (acc, maybe_code) = {
+		let mut acct_db = AccountDBMut::from_hash(db, hash);
+		let storage_root = known_storage_roots.get(&hash).cloned().unwrap_or(H256::zero());
+		account::from_fat_rlp(&mut acct_db, fat_rlp, storage_root)?
+	};
 
-			let code_hash = acc.code_hash.clone();
-			match maybe_code {
-				// new inline code
-				Some(code) => status.new_code.push((code_hash, code, hash)),
-				None => {
-					if code_hash != KECCAK_EMPTY {
-						// see if this code has already been included inline
-						match known_code.get(&code_hash) {
-							Some(&first_with) => {
-								// if so, load it from the database.
-								let code = AccountDB::from_hash(db, first_with)
-									.get(&code_hash)
-									.ok_or_else(|| Error::MissingCode(vec![first_with]))?;
-
-								// and write it again under a different mangled key
-								AccountDBMut::from_hash(db, hash).emplace(code_hash, code);
-							}
-							// if not, queue it up to be filled later
-							None => status.missing_code.push((hash, code_hash)),
-						}
+	let code_hash = acc.code_hash.clone();
+	match maybe_code {
+		// new inline code
+		Some(code) => status.new_code.push((code_hash, code, hash)),
+		None => {
+			if code_hash != KECCAK_EMPTY {
+				// see if this code has already been included inline
+				match known_code.get(&code_hash) {
+					Some(&first_with) => {
+						// if so, load it from the database.
+						let code = AccountDB::from_hash(db, first_with)
+							.get(&code_hash)
+							.ok_or_else(|| Error::MissingCode(vec![first_with]))?;
+
+						// and write it again under a different mangled key
+						AccountDBMut::from_hash(db, hash).emplace(code_hash, code);
 					}
+					// if not, queue it up to be filled later
+					None => status.missing_code.push((hash, code_hash)),
 				}
 			}
-
-			::rlp::encode(&acc).into_vec()
-		};
-
-		*out = (hash, thin_rlp);
-	}
-	if let Some(&(ref hash, ref rlp)) = out_chunk.iter().last() {
-		known_storage_roots.insert(*hash, ::rlp::decode::<BasicAccount>(rlp).storage_root);
-	}
-	if let Some(&(ref hash, ref rlp)) = out_chunk.iter().next() {
-		known_storage_roots.insert(*hash, ::rlp::decode::<BasicAccount>(rlp).storage_root);
+		}
 	}
-	Ok(status)
+
+	Ok((hash, ::rlp::encode(&acc).into_vec()))
 }
 
 /// Proportion of blocks which we will verify `PoW` for.
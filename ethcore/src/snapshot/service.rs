@@ -16,16 +16,20 @@
 
 //! Snapshot network service implementation.
 
+use std::cmp;
 use std::collections::HashSet;
-use std::io::ErrorKind;
-use std::fs;
-use std::path::PathBuf;
+use std::io::{ErrorKind, Read, Write};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use super::{ManifestData, StateRebuilder, Rebuilder, RestorationStatus, SnapshotService, MAX_CHUNK_SIZE};
-use super::io::{SnapshotReader, LooseReader, SnapshotWriter, LooseWriter};
+use super::{BasicAccount, ManifestData, PreparedChunk, StateRebuilder, Rebuilder, RestorationStatus, RestorationFailure, SnapshotService, RestoreParallelism, PeerId, MAX_CHUNK_SIZE};
+use super::codec::{ChunkCodec, CodecRegistry, SnappyKeccak};
+use super::io::{SnapshotReader, LooseReader, SnapshotWriter, LooseWriter, ChunkStore};
 
+use account_provider::AccountProvider;
 use blockchain::BlockChain;
 use client::{Client, ChainInfo, ClientIoMessage};
 use engines::EthEngine;
@@ -34,13 +38,49 @@ use ids::BlockId;
 
 use io::IoChannel;
 
-use ethereum_types::H256;
+use ethereum_types::{H256, Address};
+use ethkey::Signature;
+use hash::keccak;
 use parking_lot::{Mutex, RwLock, RwLockReadGuard};
-use util_error::UtilError;
+use util_error::{UtilError, ErrorKind as UtilErrorKind};
 use bytes::Bytes;
 use journaldb::Algorithm;
 use kvdb_rocksdb::{Database, DatabaseConfig};
-use snappy;
+use rayon::{Configuration as PoolConfiguration, ThreadPool};
+
+// Maximum amount of chunk data which should be in flight during restoration.
+const RESTORE_BUFFER_SIZE: usize = 64 * 1024 * 1024;
+// Block chunks are fed one at a time, so there's little use in requesting more.
+const BLOCK_CHUNK_PARALLELISM: usize = 2;
+// Default maximum number of restoration threads. Chunks are fed from the four IO
+// threads, so more threads wouldn't be kept busy.
+const MAX_RESTORE_THREADS: usize = 4;
+
+// Name of the file holding a snapshot's label, next to its manifest.
+const LABEL_FILE: &'static str = "LABEL";
+// Name of the file holding the signature of a snapshot's manifest.
+const SIGNATURE_FILE: &'static str = "MANIFEST_SIGNATURE";
+
+/// Account used to sign the manifests of new snapshots.
+pub struct ManifestSigner {
+	/// Accounts holding the signing key.
+	pub accounts: Arc<AccountProvider>,
+	/// Address of the signing account.
+	pub address: Address,
+	/// Password of the account, unless it's unlocked.
+	pub password: Option<String>,
+}
+
+/// A snapshot kept in the local snapshot directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalSnapshot {
+	/// The snapshot's manifest.
+	pub manifest: ManifestData,
+	/// Label given when the snapshot was taken, if any.
+	pub label: Option<String>,
+	/// Signature of the manifest, if it was signed when the snapshot was taken.
+	pub signature: Option<Signature>,
+}
 
 /// Helper for removing directories in case of error.
 struct Guard(bool, PathBuf);
@@ -62,6 +102,27 @@ impl Drop for Guard {
 	}
 }
 
+// errno for a read-only file system; the same on Linux and macOS.
+const EROFS: i32 = 30;
+
+// whether an IO error means the snapshot directory can't be written to.
+fn is_read_only_error(e: &::std::io::Error) -> bool {
+	e.kind() == ErrorKind::PermissionDenied || e.raw_os_error() == Some(EROFS)
+}
+
+// check whether files can be created in the given directory.
+fn probe_writable(dir: &Path) -> Result<bool, Error> {
+	let probe = dir.join(".write_probe");
+	match fs::File::create(&probe) {
+		Ok(_) => {
+			let _ = fs::remove_file(&probe);
+			Ok(true)
+		}
+		Err(ref e) if is_read_only_error(e) => Ok(false),
+		Err(e) => Err(e.into()),
+	}
+}
+
 /// External database restoration handler
 pub trait DatabaseRestore: Send + Sync {
 	/// Restart with a new backend. Takes ownership of passed database and moves it to a new location.
@@ -76,7 +137,8 @@ struct Restoration {
 	state: StateRebuilder,
 	secondary: Box<Rebuilder>,
 	writer: Option<LooseWriter>,
-	snappy_buffer: Bytes,
+	buffer: Bytes,
+	codec: Arc<ChunkCodec>,
 	final_state_root: H256,
 	guard: Guard,
 	db: Arc<Database>,
@@ -91,6 +153,7 @@ struct RestorationParams<'a> {
 	genesis: &'a [u8], // genesis block of the chain.
 	guard: Guard, // guard for the restoration directory.
 	engine: &'a EthEngine,
+	codec: Arc<ChunkCodec>, // codec the manifest's chunks are encoded with.
 }
 
 impl Restoration {
@@ -119,46 +182,63 @@ impl Restoration {
 			state: StateRebuilder::new(raw_db.clone(), params.pruning),
 			secondary: secondary,
 			writer: params.writer,
-			snappy_buffer: Vec::new(),
+			buffer: Vec::new(),
+			codec: params.codec,
 			final_state_root: root,
 			guard: params.guard,
 			db: raw_db,
 		})
 	}
 
+	// make sure a chunk is the one listed under `hash` in the manifest.
+	fn check_chunk_hash(&self, hash: H256, chunk: &[u8]) -> Result<(), Error> {
+		let found = self.codec.hash(chunk);
+		if found != hash {
+			return Err(::snapshot::Error::ChunkHashMismatch(hash, found).into());
+		}
+		Ok(())
+	}
+
 	// feeds a state chunk, aborts early if `flag` becomes false.
 	fn feed_state(&mut self, hash: H256, chunk: &[u8], flag: &AtomicBool) -> Result<(), Error> {
 		if self.state_chunks_left.contains(&hash) {
-			let expected_len = snappy::decompressed_len(chunk)?;
-			if expected_len > MAX_CHUNK_SIZE {
-				trace!(target: "snapshot", "Discarding large chunk: {} vs {}", expected_len, MAX_CHUNK_SIZE);
-				return Err(::snapshot::Error::ChunkTooLarge.into());
-			}
-			let len = snappy::decompress_into(chunk, &mut self.snappy_buffer)?;
+			self.check_chunk_hash(hash, chunk)?;
+			let len = decompress_chunk(&*self.codec, chunk, &mut self.buffer)?;
 
-			self.state.feed(&self.snappy_buffer[..len], flag)?;
+			self.state.feed(&self.buffer[..len], flag)?;
+			self.state_chunk_done(hash, chunk)?;
+		}
 
-			if let Some(ref mut writer) = self.writer.as_mut() {
-				writer.write_state_chunk(hash, chunk)?;
-			}
+		Ok(())
+	}
 
-			self.state_chunks_left.remove(&hash);
+	// commits a state chunk which was checked and prepared by the caller.
+	fn feed_prepared_state(&mut self, hash: H256, prepared: PreparedChunk, chunk: &[u8], flag: &AtomicBool) -> Result<(), Error> {
+		if self.state_chunks_left.contains(&hash) {
+			self.state.commit(prepared, flag)?;
+			self.state_chunk_done(hash, chunk)?;
 		}
 
 		Ok(())
 	}
 
+	// write out and mark off a state chunk which has been fed.
+	fn state_chunk_done(&mut self, hash: H256, chunk: &[u8]) -> Result<(), Error> {
+		if let Some(ref mut writer) = self.writer.as_mut() {
+			writer.write_state_chunk(hash, chunk)?;
+		}
+
+		self.state_chunks_left.remove(&hash);
+		Ok(())
+	}
+
 	// feeds a block chunk
 	fn feed_blocks(&mut self, hash: H256, chunk: &[u8], engine: &EthEngine, flag: &AtomicBool) -> Result<(), Error> {
 		if self.block_chunks_left.contains(&hash) {
-			let expected_len = snappy::decompressed_len(chunk)?;
-			if expected_len > MAX_CHUNK_SIZE {
-				trace!(target: "snapshot", "Discarding large chunk: {} vs {}", expected_len, MAX_CHUNK_SIZE);
-				return Err(::snapshot::Error::ChunkTooLarge.into());
-			}
-			let len = snappy::decompress_into(chunk, &mut self.snappy_buffer)?;
+			self.check_chunk_hash(hash, chunk)?;
+			let len = decompress_chunk(&*self.codec, chunk, &mut self.buffer)?;
 
-			self.secondary.feed(&self.snappy_buffer[..len], engine, flag)?;
+			self.secondary.feed(&self.buffer[..len], engine, flag)?;
 			if let Some(ref mut writer) = self.writer.as_mut() {
 				 writer.write_block_chunk(hash, chunk)?;
 			}
@@ -196,10 +276,28 @@ impl Restoration {
 		Ok(())
 	}
 
+	// whether the chunk with the given hash is still to be fed.
+	fn is_wanted(&self, hash: &H256, is_state: bool) -> bool {
+		match is_state {
+			true => self.state_chunks_left.contains(hash),
+			false => self.block_chunks_left.contains(hash),
+		}
+	}
+
 	// is everything done?
 	fn is_done(&self) -> bool {
 		self.block_chunks_left.is_empty() && self.state_chunks_left.is_empty()
 	}
+
+	// get an account from the state restored so far, if it's complete.
+	fn restored_account(&self, address: &Address) -> Result<Option<BasicAccount>, Error> {
+		self.state.restored_account(&keccak(address))
+	}
+}
+
+// decompress a chunk into the given buffer, rejecting chunks which would be too large.
+fn decompress_chunk(codec: &ChunkCodec, chunk: &[u8], buffer: &mut Bytes) -> Result<usize, Error> {
+	codec.decompress(chunk, buffer, MAX_CHUNK_SIZE)
 }
 
 /// Type alias for client io channel.
@@ -222,6 +320,24 @@ pub struct ServiceParams {
 	pub snapshot_root: PathBuf,
 	/// A handle for database restoration.
 	pub db_restore: Arc<DatabaseRestore>,
+	/// Number of threads state chunks are decompressed and rebuilt on during restoration.
+	/// The rebuilt chunks are still merged into the state trie one at a time.
+	/// `None` uses one per CPU, up to four.
+	pub restore_threads: Option<usize>,
+	/// Maximum number of chunks queued for restoration at once. 0 means unlimited.
+	pub max_pending_chunks: usize,
+	/// Fail an ongoing restoration once no chunk was restored for this long, checked on `tick`.
+	/// `None` lets restorations wait for chunks indefinitely.
+	pub stall_timeout: Option<Duration>,
+	/// Move the database of a failed restoration to a timestamped directory under
+	/// `snapshot_root` for inspection, instead of deleting it.
+	pub keep_failed_restoration: bool,
+	/// Maximum number of distinct peers whose chunks are accepted by `feed_state_chunk_from`
+	/// during a restoration. 0 means unlimited.
+	pub max_restoration_peers: usize,
+	/// Where the chunks of local snapshots are kept, including those recovered by restorations.
+	/// `FlatChunkStore` keeps them as files in the snapshot directories under `snapshot_root`.
+	pub chunk_store: Arc<ChunkStore>,
 }
 
 /// `SnapshotService` implementation.
@@ -231,9 +347,10 @@ pub struct Service {
 	snapshot_root: PathBuf,
 	db_config: DatabaseConfig,
 	io_channel: Mutex<Channel>,
-	pruning: Algorithm,
+	pruning: RwLock<Algorithm>,
 	status: Mutex<RestorationStatus>,
 	reader: RwLock<Option<LooseReader>>,
+	latest_manifest: RwLock<Option<ManifestData>>,
 	engine: Arc<EthEngine>,
 	genesis_block: Bytes,
 	state_chunks: AtomicUsize,
@@ -242,19 +359,46 @@ pub struct Service {
 	progress: super::Progress,
 	taking_snapshot: AtomicBool,
 	restoring_snapshot: AtomicBool,
+	restore_threads: usize,
+	restore_pool: ThreadPool,
+	max_pending_chunks: usize,
+	pending_chunks: AtomicUsize,
+	stall_timeout: Option<Duration>,
+	last_progress: Mutex<Instant>,
+	keep_failed_restoration: bool,
+	max_restoration_peers: usize,
+	// peers which contributed chunks to the ongoing restoration.
+	restoration_peers: Mutex<HashSet<PeerId>>,
+	// peers which sent malformed chunks. kept until they disconnect or a
+	// restoration completes or is aborted, so that sync can disconnect them.
+	bad_chunk_peers: Mutex<HashSet<PeerId>>,
+	chunk_store: Arc<ChunkStore>,
+	read_only: bool,
+	codecs: RwLock<CodecRegistry>,
+	manifest_signer: RwLock<Option<ManifestSigner>>,
+	// codec of the ongoing restoration, for preparing chunks outside of its lock.
+	restoration_codec: RwLock<Arc<ChunkCodec>>,
 }
 
 impl Service {
 	/// Create a new snapshot service from the given parameters.
 	pub fn new(params: ServiceParams) -> Result<Self, Error> {
+		let restore_threads = match params.restore_threads {
+			Some(threads) => cmp::max(threads, 1),
+			None => cmp::min(::num_cpus::get(), MAX_RESTORE_THREADS),
+		};
+		let restore_pool = ThreadPool::new(PoolConfiguration::new().num_threads(restore_threads))
+			.map_err(|e| ::std::io::Error::new(ErrorKind::Other, format!("Error starting restoration threads: {}", e)))?;
+
 		let mut service = Service {
 			restoration: Mutex::new(None),
 			snapshot_root: params.snapshot_root,
 			db_config: params.db_config,
 			io_channel: Mutex::new(params.channel),
-			pruning: params.pruning,
+			pruning: RwLock::new(params.pruning),
 			status: Mutex::new(RestorationStatus::Inactive),
 			reader: RwLock::new(None),
+			latest_manifest: RwLock::new(None),
 			engine: params.engine,
 			genesis_block: params.genesis_block,
 			state_chunks: AtomicUsize::new(0),
@@ -263,13 +407,40 @@ impl Service {
 			progress: Default::default(),
 			taking_snapshot: AtomicBool::new(false),
 			restoring_snapshot: AtomicBool::new(false),
+			restore_threads: restore_threads,
+			restore_pool: restore_pool,
+			max_pending_chunks: params.max_pending_chunks,
+			pending_chunks: AtomicUsize::new(0),
+			stall_timeout: params.stall_timeout,
+			last_progress: Mutex::new(Instant::now()),
+			keep_failed_restoration: params.keep_failed_restoration,
+			max_restoration_peers: params.max_restoration_peers,
+			restoration_peers: Mutex::new(HashSet::new()),
+			bad_chunk_peers: Mutex::new(HashSet::new()),
+			chunk_store: params.chunk_store,
+			read_only: false,
+			codecs: RwLock::new(CodecRegistry::default()),
+			manifest_signer: RwLock::new(None),
+			restoration_codec: RwLock::new(Arc::new(SnappyKeccak)),
 		};
 
 		// create the root snapshot dir if it doesn't exist.
-		if let Err(e) = fs::create_dir_all(&service.snapshot_root) {
-			if e.kind() != ErrorKind::AlreadyExists {
-				return Err(e.into())
-			}
+		service.read_only = match fs::create_dir_all(&service.snapshot_root) {
+			Ok(()) => !probe_writable(&service.snapshot_root)?,
+			Err(ref e) if is_read_only_error(e) => true,
+			Err(ref e) if e.kind() == ErrorKind::AlreadyExists => !probe_writable(&service.snapshot_root)?,
+			Err(e) => return Err(e.into()),
+		};
+
+		if service.read_only {
+			warn!(target: "snapshot", "Snapshot directory {} is read-only. Existing snapshots will be served, but none will be created.",
+				service.snapshot_root.display());
+
+			let reader = LooseReader::with_store(service.snapshot_dir(), service.chunk_store.clone()).ok();
+			*service.latest_manifest.get_mut() = reader.as_ref().map(|r| r.manifest().clone());
+			*service.reader.get_mut() = reader;
+
+			return Ok(service);
 		}
 
 		// delete the temporary restoration dir if it does exist.
@@ -286,7 +457,8 @@ impl Service {
 			}
 		}
 
-		let reader = LooseReader::new(service.snapshot_dir()).ok();
+		let reader = LooseReader::with_store(service.snapshot_dir(), service.chunk_store.clone()).ok();
+		*service.latest_manifest.get_mut() = reader.as_ref().map(|r| r.manifest().clone());
 		*service.reader.get_mut() = reader;
 
 		Ok(service)
@@ -335,11 +507,62 @@ impl Service {
 		Ok(())
 	}
 
+	/// Whether the snapshot directory is read-only, disabling snapshot creation.
+	pub fn is_read_only(&self) -> bool {
+		self.read_only
+	}
+
+	/// Get the manifest of the newest local snapshot, if any.
+	/// Unlike `manifest`, this doesn't contend with readers of the snapshot itself.
+	pub fn latest_manifest(&self) -> Option<ManifestData> {
+		self.latest_manifest.read().clone()
+	}
+
+	/// List the snapshots kept locally together with their labels.
+	/// Only the most recent snapshot is kept, so this holds at most one entry.
+	pub fn list_snapshots(&self) -> Vec<LocalSnapshot> {
+		let manifest = match self.latest_manifest() {
+			Some(manifest) => manifest,
+			None => return Vec::new(),
+		};
+
+		let mut label = String::new();
+		let label = match File::open(self.snapshot_dir().join(LABEL_FILE)).and_then(|mut f| f.read_to_string(&mut label)) {
+			Ok(_) => Some(label),
+			Err(_) => None,
+		};
+
+		let mut signature = Signature::default();
+		let signature = match File::open(self.snapshot_dir().join(SIGNATURE_FILE)).and_then(|mut f| f.read_exact(&mut *signature)) {
+			Ok(_) => Some(signature),
+			Err(_) => None,
+		};
+
+		vec![LocalSnapshot { manifest: manifest, label: label, signature: signature }]
+	}
+
 	/// Get a reference to the snapshot reader.
 	pub fn reader(&self) -> RwLockReadGuard<Option<LooseReader>> {
 		self.reader.read()
 	}
 
+	/// Get an account from the state restored so far by an ongoing restoration.
+	/// `None` if there's no restoration or the account hasn't been completely restored yet.
+	pub fn query_restored_state(&self, address: &Address) -> Option<BasicAccount> {
+		match *self.restoration.lock() {
+			Some(ref rest) => rest.restored_account(address).unwrap_or_else(|e| {
+				warn!(target: "snapshot", "Failed to read restored account {}: {}", address, e);
+				None
+			}),
+			None => None,
+		}
+	}
+
+	/// Whether a snapshot is being taken right now.
+	pub fn is_taking_snapshot(&self) -> bool {
+		self.taking_snapshot.load(Ordering::SeqCst)
+	}
+
 	/// Tick the snapshot service. This will log any active snapshot
 	/// being taken.
 	pub fn tick(&self) {
@@ -349,11 +572,49 @@ impl Service {
 		info!("Snapshot: {} accounts {} blocks {} bytes", p.accounts(), p.blocks(), p.size());
 	}
 
+	/// Fail the ongoing restoration if no chunk was restored within the stall timeout.
+	/// Returns whether it was failed now. Meant to be called periodically.
+	pub fn check_stalled_restoration(&self) -> bool {
+		let timeout = match self.stall_timeout {
+			Some(timeout) => timeout,
+			None => return false,
+		};
+
+		match self.status() {
+			RestorationStatus::Ongoing { .. } => {},
+			_ => return false,
+		}
+
+		let stalled_for = self.last_progress.lock().elapsed();
+		if stalled_for < timeout {
+			return false;
+		}
+
+		warn!(target: "snapshot", "No snapshot chunk was restored for {}s; failing restoration", stalled_for.as_secs());
+		self.fail_restoration_with(RestorationFailure::Stalled);
+		true
+	}
+
 	/// Take a snapshot at the block with the given number.
 	/// calling this while a restoration is in progress or vice versa
 	/// will lead to a race condition where the first one to finish will
 	/// have their produced snapshot overwritten.
 	pub fn take_snapshot(&self, client: &Client, num: u64) -> Result<(), Error> {
+		self.take_labeled_snapshot(client, num, None)
+	}
+
+	/// Estimate how much disk a snapshot at the block with the given number would take, in bytes.
+	pub fn estimate_snapshot_size(&self, client: &Client, num: u64) -> Result<u64, Error> {
+		client.estimate_snapshot_size(BlockId::Number(num))
+	}
+
+	/// Take a snapshot at the block with the given number, storing `label`
+	/// alongside its manifest.
+	pub fn take_labeled_snapshot(&self, client: &Client, num: u64, label: Option<&str>) -> Result<(), Error> {
+		if self.read_only {
+			return Err(::snapshot::Error::SnapshotDirReadOnly.into());
+		}
+
 		if self.taking_snapshot.compare_and_swap(false, true, Ordering::SeqCst) {
 			info!("Skipping snapshot at #{} as another one is currently in-progress.", num);
 			return Ok(());
@@ -367,10 +628,11 @@ impl Service {
 
 		let _ = fs::remove_dir_all(&temp_dir);
 
-		let writer = LooseWriter::new(temp_dir.clone())?;
+		let writer = LooseWriter::with_store(temp_dir.clone(), self.chunk_store.clone())?;
 
 		let guard = Guard::new(temp_dir.clone());
-		let res = client.take_snapshot(writer, BlockId::Number(num), &self.progress);
+		let codecs = self.codecs.read().clone();
+		let res = client.take_snapshot_with_codecs(writer, BlockId::Number(num), &self.progress, &codecs);
 
 		self.taking_snapshot.store(false, Ordering::SeqCst);
 		if let Err(e) = res {
@@ -388,10 +650,24 @@ impl Service {
 
 		info!("Finished taking snapshot at #{}", num);
 
+		if let Some(label) = label {
+			File::create(temp_dir.join(LABEL_FILE))?.write_all(label.as_bytes())?;
+		}
+
+		if let Some(ref signer) = *self.manifest_signer.read() {
+			let manifest = LooseReader::with_store(temp_dir.clone(), self.chunk_store.clone())?.manifest().clone();
+			// the snapshot is still usable without the signature, e.g. if the account got locked.
+			match signer.accounts.sign(signer.address, signer.password.clone(), super::manifest_hash(&manifest)) {
+				Ok(signature) => File::create(temp_dir.join(SIGNATURE_FILE))?.write_all(&*signature)?,
+				Err(e) => warn!(target: "snapshot", "Failed to sign the manifest of snapshot #{}, keeping it unsigned: {}", num, e),
+			}
+		}
+
 		let mut reader = self.reader.write();
 
 		// destroy the old snapshot reader.
 		*reader = None;
+		*self.latest_manifest.write() = None;
 
 		if snapshot_dir.exists() {
 			fs::remove_dir_all(&snapshot_dir)?;
@@ -399,21 +675,52 @@ impl Service {
 
 		fs::rename(temp_dir, &snapshot_dir)?;
 
-		*reader = Some(LooseReader::new(snapshot_dir)?);
+		*reader = Some(LooseReader::with_store(snapshot_dir, self.chunk_store.clone())?);
+		*self.latest_manifest.write() = reader.as_ref().map(|r| r.manifest().clone());
 
 		guard.disarm();
 		Ok(())
 	}
 
+	/// Set the pruning algorithm of databases built by future restorations.
+	pub fn set_pruning(&self, pruning: Algorithm) {
+		*self.pruning.write() = pruning;
+	}
+
+	/// Sign the manifests of snapshots taken from now on, storing the signature next to them,
+	/// so that they can be checked with `verify_manifest_signature`. `None` stops signing.
+	/// Snapshots whose manifest can't be signed are kept unsigned.
+	pub fn set_manifest_signer(&self, signer: Option<ManifestSigner>) {
+		*self.manifest_signer.write() = signer;
+	}
+
+	/// Use `codec` to encode and decode chunks of snapshots with the given version.
+	/// Registering a codec for the engine's current version changes how new snapshots are written.
+	pub fn register_codec(&self, version: u64, codec: Arc<ChunkCodec>) {
+		self.codecs.write().register(version, codec);
+	}
+
 	/// Initialize the restoration synchronously.
 	/// The recover flag indicates whether to recover the restored snapshot.
 	pub fn init_restore(&self, manifest: ManifestData, recover: bool) -> Result<(), Error> {
+		// reject unusable snapshots before tearing anything down.
+		match self.supported_versions() {
+			None => return Err(::snapshot::Error::SnapshotsUnsupported.into()),
+			Some((min, max)) if manifest.version < min || manifest.version > max =>
+				return Err(::snapshot::Error::UnsupportedSnapshotVersion { found: manifest.version, supported: (min, max) }.into()),
+			Some(_) => {},
+		}
+		let codec = self.codecs.read().get(manifest.version)?;
+
+		// the restored snapshot can't be kept in a read-only directory.
+		let recover = recover && !self.read_only;
 		let rest_dir = self.restoration_dir();
 
 		let mut res = self.restoration.lock();
 
 		self.state_chunks.store(0, Ordering::SeqCst);
 		self.block_chunks.store(0, Ordering::SeqCst);
+		self.restoration_peers.lock().clear();
 
 		// tear down existing restoration.
 		*res = None;
@@ -430,26 +737,29 @@ impl Service {
 
 		// make new restoration.
 		let writer = match recover {
-			true => Some(LooseWriter::new(self.temp_recovery_dir())?),
+			true => Some(LooseWriter::with_store(self.temp_recovery_dir(), self.chunk_store.clone())?),
 			false => None
 		};
 
 		let params = RestorationParams {
 			manifest: manifest,
-			pruning: self.pruning,
+			pruning: *self.pruning.read(),
 			db_path: self.restoration_db(),
 			db_config: &self.db_config,
 			writer: writer,
 			genesis: &self.genesis_block,
 			guard: Guard::new(rest_dir),
 			engine: &*self.engine,
+			codec: codec.clone(),
 		};
 
 		let state_chunks = params.manifest.state_hashes.len();
 		let block_chunks = params.manifest.block_hashes.len();
 
 		*res = Some(Restoration::new(params)?);
+		*self.restoration_codec.write() = codec;
 
+		*self.last_progress.lock() = Instant::now();
 		*self.status.lock() = RestorationStatus::Ongoing {
 			state_chunks: state_chunks as u32,
 			block_chunks: block_chunks as u32,
@@ -479,6 +789,7 @@ impl Service {
 		if recover {
 			let mut reader = self.reader.write();
 			*reader = None; // destroy the old reader if it existed.
+			*self.latest_manifest.write() = None;
 
 			let snapshot_dir = self.snapshot_dir();
 
@@ -490,23 +801,62 @@ impl Service {
 			trace!(target: "snapshot", "copying restored snapshot files over");
 			fs::rename(self.temp_recovery_dir(), &snapshot_dir)?;
 
-			*reader = Some(LooseReader::new(snapshot_dir)?);
+			*reader = Some(LooseReader::with_store(snapshot_dir, self.chunk_store.clone())?);
+			*self.latest_manifest.write() = reader.as_ref().map(|r| r.manifest().clone());
 		}
 
 		let _ = fs::remove_dir_all(self.restoration_dir());
 		*self.status.lock() = RestorationStatus::Inactive;
+		self.restoration_peers.lock().clear();
+		self.bad_chunk_peers.lock().clear();
 
 		Ok(())
 	}
 
+	// whether the ongoing restoration still needs the chunk with the given hash.
+	fn is_wanted(&self, hash: &H256, is_state: bool) -> bool {
+		match self.status() {
+			RestorationStatus::Ongoing { .. } => {},
+			_ => return false,
+		}
+
+		self.restoration.lock().as_ref().map_or(false, |r| r.is_wanted(hash, is_state))
+	}
+
+	// check, decompress and rebuild a state chunk on the restoration threads, so that
+	// several chunks are worked on at once. only committing it takes the restoration lock.
+	fn prepare_state_chunk(&self, hash: H256, chunk: &[u8]) -> Result<PreparedChunk, Error> {
+		let codec = self.restoration_codec.read().clone();
+		let flag = &self.restoring_snapshot;
+
+		self.restore_pool.install(move || {
+			let found = codec.hash(chunk);
+			if found != hash {
+				return Err(::snapshot::Error::ChunkHashMismatch(hash, found).into());
+			}
+
+			let mut buffer = Vec::new();
+			let len = decompress_chunk(&*codec, chunk, &mut buffer)?;
+			StateRebuilder::prepare(&buffer[..len], flag)
+		})
+	}
+
 	/// Feed a chunk of either kind. no-op if no restoration or status is wrong.
 	fn feed_chunk(&self, hash: H256, chunk: &[u8], is_state: bool) -> Result<(), Error> {
 		// TODO: be able to process block chunks and state chunks at same time?
+		// chunks fed before or arriving after the restoration ended aren't worth decompressing.
+		if !self.is_wanted(&hash, is_state) { return Ok(()) }
+
+		let prepared = match is_state {
+			true => Some(self.prepare_state_chunk(hash, chunk)?),
+			false => None,
+		};
+
 		let (result, db) = {
 			let mut restoration = self.restoration.lock();
 
 			match self.status() {
-				RestorationStatus::Inactive | RestorationStatus::Failed => return Ok(()),
+				RestorationStatus::Inactive | RestorationStatus::Failed(_) => return Ok(()),
 				RestorationStatus::Ongoing { .. } => {
 					let (res, db) = {
 						let rest = match *restoration {
@@ -514,14 +864,15 @@ impl Service {
 							None => return Ok(()),
 						};
 
-						(match is_state {
-							true => rest.feed_state(hash, chunk, &self.restoring_snapshot),
-							false => rest.feed_blocks(hash, chunk, &*self.engine, &self.restoring_snapshot),
+						(match prepared {
+							Some(prepared) => rest.feed_prepared_state(hash, prepared, chunk, &self.restoring_snapshot),
+							None => rest.feed_blocks(hash, chunk, &*self.engine, &self.restoring_snapshot),
 						}.map(|_| rest.is_done()), rest.db.clone())
 					};
 
 					let res = match res {
 						Ok(is_done) => {
+							*self.last_progress.lock() = Instant::now();
 							match is_state {
 								true => self.state_chunks.fetch_add(1, Ordering::SeqCst),
 								false => self.block_chunks.fetch_add(1, Ordering::SeqCst),
@@ -545,33 +896,233 @@ impl Service {
 		result.and_then(|_| db.flush().map_err(|e| UtilError::from(e).into()))
 	}
 
+	/// Restore the snapshot read from `reader` into a scratch database under `root`
+	/// and verify the result, without touching the client database or the
+	/// ongoing restoration. `root` is removed afterwards.
+	/// Returns the number of compressed chunk bytes which were fed.
+	pub fn verify_restoration<R: SnapshotReader>(&self, reader: &R, root: PathBuf) -> Result<usize, Error> {
+		let manifest = reader.manifest().clone();
+
+		fs::create_dir_all(&root)?;
+		let bytes = self.restore_into(reader, &manifest, root.join("db"), Guard::new(root.clone()))?;
+		let _ = fs::remove_dir_all(&root);
+
+		Ok(bytes)
+	}
+
+	/// Restore the local snapshot with the given manifest into a new database at `target`,
+	/// leaving the client database and any ongoing restoration untouched.
+	/// `target` must not exist yet; it is removed again if restoration fails.
+	pub fn restore_to(&self, manifest: &ManifestData, target: &Path) -> Result<(), Error> {
+		if target.exists() {
+			return Err(::std::io::Error::new(ErrorKind::AlreadyExists, "restoration target already exists").into());
+		}
+
+		let reader = self.reader.read();
+		let reader = match *reader {
+			Some(ref reader) if reader.manifest() == manifest => reader,
+			_ => return Err(::std::io::Error::new(ErrorKind::NotFound, "no local snapshot with the given manifest").into()),
+		};
+
+		fs::create_dir_all(target)?;
+		self.restore_into(reader, manifest, target.to_owned(), Guard::new(target.to_owned()))?;
+
+		info!(target: "snapshot", "Restored snapshot at #{} into {}", manifest.block_number, target.display());
+		Ok(())
+	}
+
+	// restore the snapshot read from `reader` into a fresh database at `db_path`.
+	// returns the number of compressed chunk bytes which were fed.
+	fn restore_into<R: SnapshotReader>(&self, reader: &R, manifest: &ManifestData, db_path: PathBuf, guard: Guard) -> Result<usize, Error> {
+		let flag = AtomicBool::new(true);
+
+		let params = RestorationParams {
+			manifest: manifest.clone(),
+			pruning: *self.pruning.read(),
+			db_path: db_path,
+			db_config: &self.db_config,
+			writer: None,
+			genesis: &self.genesis_block,
+			guard: guard,
+			engine: &*self.engine,
+			codec: self.codecs.read().get(manifest.version)?,
+		};
+
+		let mut restoration = Restoration::new(params)?;
+		let mut bytes = 0;
+
+		for &hash in &manifest.state_hashes {
+			let chunk = reader.chunk(hash)?;
+			bytes += chunk.len();
+			restoration.feed_state(hash, &chunk, &flag)?;
+		}
+
+		for &hash in &manifest.block_hashes {
+			let chunk = reader.chunk(hash)?;
+			bytes += chunk.len();
+			restoration.feed_blocks(hash, &chunk, &*self.engine, &flag)?;
+		}
+
+		restoration.db.flush().map_err(UtilError::from)?;
+		restoration.finalize(&*self.engine)?;
+
+		Ok(bytes)
+	}
+
+	/// Feed a state chunk which was queued by `restore_state_chunk`.
+	pub fn feed_queued_state_chunk(&self, hash: H256, chunk: &[u8]) {
+		self.pending_chunks.fetch_sub(1, Ordering::SeqCst);
+		self.feed_state_chunk(hash, chunk)
+	}
+
+	/// Feed a state chunk from `peer` which was queued by `restore_state_chunk_from`.
+	pub fn feed_queued_state_chunk_from(&self, peer: PeerId, hash: H256, chunk: &[u8]) {
+		self.pending_chunks.fetch_sub(1, Ordering::SeqCst);
+		self.feed_state_chunk_from(peer, hash, chunk)
+	}
+
+	/// Feed a block chunk which was queued by `restore_block_chunk`.
+	pub fn feed_queued_block_chunk(&self, hash: H256, chunk: &[u8]) {
+		self.pending_chunks.fetch_sub(1, Ordering::SeqCst);
+		self.feed_block_chunk(hash, chunk)
+	}
+
+	// queue a chunk message, respecting the pending chunk limit.
+	fn queue_chunk(&self, message: ClientIoMessage) {
+		if !self.can_accept_chunk() {
+			debug!(target: "snapshot", "Dropping chunk: {} chunks already pending", self.max_pending_chunks);
+			return;
+		}
+
+		self.pending_chunks.fetch_add(1, Ordering::SeqCst);
+		if let Err(e) = self.io_channel.lock().send(message) {
+			self.pending_chunks.fetch_sub(1, Ordering::SeqCst);
+			trace!("Error sending snapshot service message: {:?}", e);
+		}
+	}
+
 	/// Feed a state chunk to be processed synchronously.
 	pub fn feed_state_chunk(&self, hash: H256, chunk: &[u8]) {
-		match self.feed_chunk(hash, chunk, true) {
-			Ok(()) => (),
-			Err(e) => {
-				warn!("Encountered error during state restoration: {}", e);
-				*self.restoration.lock() = None;
-				*self.status.lock() = RestorationStatus::Failed;
-				let _ = fs::remove_dir_all(self.restoration_dir());
-			}
+		if let Err(e) = self.try_feed_state_chunk(hash, chunk) {
+			warn!("Encountered error during state restoration: {}", e);
 		}
 	}
 
 	/// Feed a block chunk to be processed synchronously.
 	pub fn feed_block_chunk(&self, hash: H256, chunk: &[u8]) {
-		match self.feed_chunk(hash, chunk, false) {
-			Ok(()) => (),
+		if let Err(e) = self.try_feed_block_chunk(hash, chunk) {
+			warn!("Encountered error during block restoration: {}", e);
+		}
+	}
+
+	/// Feed a state chunk to be processed synchronously.
+	/// On error the restoration is failed and the error returned.
+	pub fn try_feed_state_chunk(&self, hash: H256, chunk: &[u8]) -> Result<(), Error> {
+		let res = self.feed_chunk(hash, chunk, true);
+		if res.is_err() { self.fail_restoration() }
+		res
+	}
+
+	/// Feed a block chunk to be processed synchronously.
+	/// On error the restoration is failed and the error returned.
+	pub fn try_feed_block_chunk(&self, hash: H256, chunk: &[u8]) -> Result<(), Error> {
+		let res = self.feed_chunk(hash, chunk, false);
+		if res.is_err() { self.fail_restoration() }
+		res
+	}
+
+	/// Feed a state chunk received from `peer` to be processed synchronously.
+	/// Chunks from peers which sent a bad chunk before, or from new peers once
+	/// `max_restoration_peers` contributed to the restoration, are dropped.
+	/// If the chunk fails to restore, the restoration is failed; the peer is
+	/// reported by `bad_chunk_peers` only if the chunk itself was malformed, and
+	/// not for local failures like database errors.
+	pub fn feed_state_chunk_from(&self, peer: PeerId, hash: H256, chunk: &[u8]) {
+		if self.bad_chunk_peers.lock().contains(&peer) {
+			trace!(target: "snapshot", "Dropping chunk from peer {} which sent a bad chunk", peer);
+			return;
+		}
+
+		{
+			let mut peers = self.restoration_peers.lock();
+			if self.max_restoration_peers != 0 && !peers.contains(&peer) && peers.len() >= self.max_restoration_peers {
+				trace!(target: "snapshot", "Dropping chunk from peer {}: {} peers already contributing", peer, peers.len());
+				return;
+			}
+			peers.insert(peer);
+		}
+
+		match self.try_feed_state_chunk(hash, chunk) {
+			Ok(()) => {},
+			Err(ref e) if is_bad_chunk(e) => {
+				warn!(target: "snapshot", "Bad state chunk {:?} from peer {}: {}", hash, peer, e);
+				self.bad_chunk_peers.lock().insert(peer);
+			}
+			Err(e) => warn!(target: "snapshot", "Encountered error during state restoration: {}", e),
+		}
+	}
+
+	/// Check that the restoration has completed. Restorations are finalized
+	/// as soon as their last chunk is fed, so this only reports the outcome.
+	pub fn finish_restoration(&self) -> Result<(), Error> {
+		match self.status() {
+			RestorationStatus::Inactive => Ok(()),
+			RestorationStatus::Failed(_) => Err(::snapshot::Error::RestorationAborted.into()),
+			RestorationStatus::Ongoing { state_chunks, block_chunks, state_chunks_done, block_chunks_done } =>
+				Err(::snapshot::Error::RestorationIncomplete(
+					state_chunks.saturating_sub(state_chunks_done),
+					block_chunks.saturating_sub(block_chunks_done),
+				).into()),
+		}
+	}
+
+	fn fail_restoration(&self) {
+		self.fail_restoration_with(RestorationFailure::Error)
+	}
+
+	fn fail_restoration_with(&self, reason: RestorationFailure) {
+		let restoration = self.restoration.lock().take();
+		*self.status.lock() = RestorationStatus::Failed(reason);
+
+		if !self.keep_failed_restoration {
+			drop(restoration);
+			let _ = fs::remove_dir_all(self.restoration_dir());
+			return;
+		}
+
+		if let Some(restoration) = restoration {
+			// closes the database, but leaves it on disk.
+			restoration.guard.disarm();
+		}
+
+		let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+		let kept = self.snapshot_root.join(format!("failed-restoration-{}", secs));
+		match fs::rename(self.restoration_dir(), &kept) {
+			Ok(()) => warn!(target: "snapshot", "Kept the database of the failed restoration at {}", kept.display()),
 			Err(e) => {
-				warn!("Encountered error during block restoration: {}", e);
-				*self.restoration.lock() = None;
-				*self.status.lock() = RestorationStatus::Failed;
+				warn!(target: "snapshot", "Failed to keep the database of the failed restoration: {}", e);
 				let _ = fs::remove_dir_all(self.restoration_dir());
 			}
 		}
 	}
 }
 
+// whether a restoration error was caused by a malformed chunk, rather than
+// by a local failure like a database error.
+fn is_bad_chunk(err: &Error) -> bool {
+	match *err {
+		Error::Snappy(_) => true,
+		Error::Util(ref e) => match *e.kind() {
+			UtilErrorKind::Decoder(_) => true,
+			_ => false,
+		},
+		Error::Snapshot(::snapshot::Error::ChunkHashMismatch(..)) |
+		Error::Snapshot(::snapshot::Error::WrongChunkFormat(_)) |
+		Error::Snapshot(::snapshot::Error::UnrecognizedCodeState(_)) => true,
+		_ => false,
+	}
+}
+
 impl SnapshotService for Service {
 	fn manifest(&self) -> Option<ManifestData> {
 		self.reader.read().as_ref().map(|r| r.manifest().clone())
@@ -606,18 +1157,62 @@ impl SnapshotService for Service {
 		self.restoring_snapshot.store(false, Ordering::SeqCst);
 		*self.restoration.lock() = None;
 		*self.status.lock() = RestorationStatus::Inactive;
+		self.restoration_peers.lock().clear();
+		self.bad_chunk_peers.lock().clear();
 	}
 
-	fn restore_state_chunk(&self, hash: H256, chunk: Bytes) {
-		if let Err(e) = self.io_channel.lock().send(ClientIoMessage::FeedStateChunk(hash, chunk)) {
-			trace!("Error sending snapshot service message: {:?}", e);
+	fn can_accept_chunk(&self) -> bool {
+		self.max_pending_chunks == 0 || self.pending_chunks.load(Ordering::SeqCst) < self.max_pending_chunks
+	}
+
+	fn recommended_parallelism(&self) -> RestoreParallelism {
+		let mut limit = RESTORE_BUFFER_SIZE / MAX_CHUNK_SIZE;
+		if self.max_pending_chunks != 0 {
+			limit = cmp::min(limit, self.max_pending_chunks);
 		}
+		let limit = cmp::max(limit, 2);
+
+		let (state_left, block_left) = match self.status() {
+			RestorationStatus::Ongoing { state_chunks, block_chunks, state_chunks_done, block_chunks_done } =>
+				(state_chunks.saturating_sub(state_chunks_done) as usize, block_chunks.saturating_sub(block_chunks_done) as usize),
+			_ => (limit, limit),
+		};
+
+		// once the state is done, block chunks may use the whole buffer.
+		let block = match state_left {
+			0 => cmp::min(block_left, limit),
+			_ => cmp::min(block_left, BLOCK_CHUNK_PARALLELISM),
+		};
+		// keep every restore thread busy, with one chunk waiting for each.
+		let state = cmp::min(state_left, cmp::min(self.restore_threads * 2, limit - block));
+
+		RestoreParallelism { state: state, block: block }
+	}
+
+	fn chunk_hash(&self, chunk: &[u8]) -> H256 {
+		self.restoration_codec.read().hash(chunk)
+	}
+
+	fn restore_state_chunk(&self, hash: H256, chunk: Bytes) {
+		self.queue_chunk(ClientIoMessage::FeedStateChunk(hash, chunk))
+	}
+
+	fn restore_state_chunk_from(&self, peer: PeerId, hash: H256, chunk: Bytes) {
+		self.queue_chunk(ClientIoMessage::FeedStateChunkFrom(peer, hash, chunk))
 	}
 
 	fn restore_block_chunk(&self, hash: H256, chunk: Bytes) {
-		if let Err(e) = self.io_channel.lock().send(ClientIoMessage::FeedBlockChunk(hash, chunk)) {
-			trace!("Error sending snapshot service message: {:?}", e);
-		}
+		self.queue_chunk(ClientIoMessage::FeedBlockChunk(hash, chunk))
+	}
+
+	fn bad_chunk_peers(&self) -> Vec<PeerId> {
+		self.bad_chunk_peers.lock().iter().cloned().collect()
+	}
+
+	fn peer_disconnected(&self, peer: PeerId) {
+		// the id may be reused by another peer.
+		self.restoration_peers.lock().remove(&peer);
+		self.bad_chunk_peers.lock().remove(&peer);
 	}
 }
 
@@ -635,7 +1230,8 @@ mod tests {
 	use spec::Spec;
 	use journaldb::Algorithm;
 	use error::Error;
-	use snapshot::{ManifestData, RestorationStatus, SnapshotService};
+	use snapshot::{ManifestData, RestorationStatus, RestoreParallelism, SnapshotService};
+	use snapshot::io::FlatChunkStore;
 	use super::*;
 	use tempdir::TempDir;
 
@@ -646,23 +1242,33 @@ mod tests {
 		}
 	}
 
+	fn test_params(channel: IoChannel<ClientIoMessage>, snapshot_root: PathBuf) -> ServiceParams {
+		let spec = Spec::new_test();
+		ServiceParams {
+			engine: spec.engine.clone(),
+			genesis_block: spec.genesis_block(),
+			db_config: Default::default(),
+			pruning: Algorithm::Archive,
+			channel: channel,
+			snapshot_root: snapshot_root,
+			db_restore: Arc::new(NoopDBRestore),
+			restore_threads: Some(1),
+			max_pending_chunks: 0,
+			stall_timeout: None,
+			keep_failed_restoration: false,
+			max_restoration_peers: 0,
+			chunk_store: Arc::new(FlatChunkStore),
+		}
+	}
+
 	#[test]
 	fn sends_async_messages() {
 		let service = IoService::<ClientIoMessage>::start().unwrap();
-		let spec = Spec::new_test();
 
 		let tempdir = TempDir::new("").unwrap();
 		let dir = tempdir.path().join("snapshot");
 
-		let snapshot_params = ServiceParams {
-			engine: spec.engine.clone(),
-			genesis_block: spec.genesis_block(),
-			db_config: Default::default(),
-			pruning: Algorithm::Archive,
-			channel: service.channel(),
-			snapshot_root: dir,
-			db_restore: Arc::new(NoopDBRestore),
-		};
+		let snapshot_params = test_params(service.channel(), dir);
 
 		let service = Service::new(snapshot_params).unwrap();
 
@@ -679,12 +1285,194 @@ mod tests {
 			block_hash: Default::default(),
 		};
 
+		assert_eq!(service.recommended_parallelism(), RestoreParallelism { state: 2, block: 2 });
 		service.begin_restore(manifest);
 		service.abort_restore();
 		service.restore_state_chunk(Default::default(), vec![]);
 		service.restore_block_chunk(Default::default(), vec![]);
 	}
 
+	#[test]
+	fn rejects_chunks_beyond_pending_limit() {
+		let service = IoService::<ClientIoMessage>::start().unwrap();
+
+		let tempdir = TempDir::new("").unwrap();
+		let dir = tempdir.path().join("snapshot");
+
+		let snapshot_params = ServiceParams {
+			max_pending_chunks: 2,
+			..test_params(service.channel(), dir)
+		};
+
+		// no handler is registered, so queued chunks are never taken off.
+		let service = Service::new(snapshot_params).unwrap();
+
+		assert!(service.can_accept_chunk());
+		service.restore_state_chunk(Default::default(), vec![]);
+		service.restore_block_chunk(Default::default(), vec![]);
+		assert!(!service.can_accept_chunk());
+
+		service.restore_state_chunk(Default::default(), vec![]);
+		assert_eq!(service.pending_chunks.load(Ordering::SeqCst), 2);
+
+		service.feed_queued_state_chunk(Default::default(), &[]);
+		assert!(service.can_accept_chunk());
+	}
+
+	#[test]
+	fn fails_stalled_restoration() {
+		use ethereum_types::H256;
+		use snapshot::RestorationFailure;
+
+		let service = IoService::<ClientIoMessage>::start().unwrap();
+
+		let tempdir = TempDir::new("").unwrap();
+		let dir = tempdir.path().join("snapshot");
+
+		let snapshot_params = ServiceParams {
+			stall_timeout: Some(Duration::from_millis(0)),
+			..test_params(service.channel(), dir)
+		};
+
+		let service = Service::new(snapshot_params).unwrap();
+		assert!(!service.check_stalled_restoration());
+
+		let manifest = ManifestData {
+			version: 2,
+			state_hashes: vec![H256::random()],
+			block_hashes: vec![],
+			state_root: Default::default(),
+			block_number: 0,
+			block_hash: Default::default(),
+		};
+
+		service.init_restore(manifest, false).unwrap();
+		assert!(service.check_stalled_restoration());
+		assert_eq!(service.status(), RestorationStatus::Failed(RestorationFailure::Stalled));
+		assert!(!service.check_stalled_restoration());
+	}
+
+	#[test]
+	fn keeps_failed_restoration_db() {
+		use ethereum_types::H256;
+
+		let service = IoService::<ClientIoMessage>::start().unwrap();
+
+		let tempdir = TempDir::new("").unwrap();
+		let dir = tempdir.path().join("snapshot");
+
+		let snapshot_params = ServiceParams {
+			stall_timeout: Some(Duration::from_millis(0)),
+			keep_failed_restoration: true,
+			..test_params(service.channel(), dir.clone())
+		};
+
+		let service = Service::new(snapshot_params).unwrap();
+		let manifest = ManifestData {
+			version: 2,
+			state_hashes: vec![H256::random()],
+			block_hashes: vec![],
+			state_root: Default::default(),
+			block_number: 0,
+			block_hash: Default::default(),
+		};
+
+		service.init_restore(manifest, false).unwrap();
+		assert!(service.check_stalled_restoration());
+		assert!(!service.restoration_dir().exists());
+
+		let kept: Vec<_> = fs::read_dir(&dir).unwrap()
+			.map(|entry| entry.unwrap().path())
+			.filter(|path| path.file_name().unwrap().to_string_lossy().starts_with("failed-restoration-"))
+			.collect();
+		assert_eq!(kept.len(), 1);
+		assert!(kept[0].join("db").exists());
+	}
+
+	#[test]
+	fn limits_and_blames_restoration_peers() {
+		use ethereum_types::H256;
+		use snapshot::RestorationFailure;
+
+		let service = IoService::<ClientIoMessage>::start().unwrap();
+
+		let tempdir = TempDir::new("").unwrap();
+		let dir = tempdir.path().join("snapshot");
+
+		let snapshot_params = ServiceParams {
+			max_restoration_peers: 1,
+			..test_params(service.channel(), dir)
+		};
+
+		let service = Service::new(snapshot_params).unwrap();
+		let hash = H256::random();
+		let manifest = ManifestData {
+			version: 2,
+			state_hashes: vec![hash],
+			block_hashes: vec![],
+			state_root: Default::default(),
+			block_number: 0,
+			block_hash: Default::default(),
+		};
+		let bad_chunk = [1, 2, 3, 4, 5];
+
+		service.init_restore(manifest.clone(), false).unwrap();
+		service.feed_state_chunk_from(1, hash, &bad_chunk);
+		assert_eq!(service.status(), RestorationStatus::Failed(RestorationFailure::Error));
+		assert_eq!(service.bad_chunk_peers(), vec![1]);
+
+		service.init_restore(manifest, false).unwrap();
+		// dropped, since peer 1 sent a bad chunk before.
+		service.feed_state_chunk_from(1, hash, &bad_chunk);
+		// peer 2 takes the only slot with a chunk which isn't part of the snapshot.
+		service.feed_state_chunk_from(2, H256::random(), &bad_chunk);
+		// dropped, since the peer limit is reached.
+		service.feed_state_chunk_from(3, hash, &bad_chunk);
+
+		match service.status() {
+			RestorationStatus::Ongoing { .. } => {},
+			other => panic!("unexpected status {:?}", other),
+		}
+		assert_eq!(service.bad_chunk_peers(), vec![1]);
+
+		// once peer 1 disconnects its id may be reused by another peer.
+		service.peer_disconnected(1);
+		assert!(service.bad_chunk_peers().is_empty());
+	}
+
+	#[test]
+	fn refuses_chunks_with_wrong_hash() {
+		use ethereum_types::H256;
+
+		let service = IoService::<ClientIoMessage>::start().unwrap();
+
+		let tempdir = TempDir::new("").unwrap();
+		let dir = tempdir.path().join("snapshot");
+
+		let snapshot_params = test_params(service.channel(), dir);
+
+		let service = Service::new(snapshot_params).unwrap();
+		let chunk = [1, 2, 3, 4, 5];
+		let hash = H256::random();
+		let manifest = ManifestData {
+			version: 2,
+			state_hashes: vec![hash],
+			block_hashes: vec![],
+			state_root: Default::default(),
+			block_number: 0,
+			block_hash: Default::default(),
+		};
+
+		service.init_restore(manifest, false).unwrap();
+		match service.try_feed_state_chunk(hash, &chunk) {
+			Err(Error::Snapshot(::snapshot::Error::ChunkHashMismatch(expected, found))) => {
+				assert_eq!(expected, hash);
+				assert_eq!(found, service.chunk_hash(&chunk));
+			},
+			other => panic!("unexpected result {:?}", other),
+		}
+	}
+
 	#[test]
 	fn cannot_finish_with_invalid_chunks() {
 		use ethereum_types::H256;
@@ -715,6 +1503,7 @@ mod tests {
 			genesis: &gb,
 			guard: Guard::benign(),
 			engine: &*spec.engine.clone(),
+			codec: Arc::new(SnappyKeccak),
 		};
 
 		let mut restoration = Restoration::new(params).unwrap();
@@ -0,0 +1,122 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! benchmarking for state restoration, one chunk at a time against
+//! chunks prepared in parallel. should be started with:
+//! ```bash
+//! cargo bench --features benches
+//! ```
+
+extern crate test;
+
+use self::test::Bencher;
+
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+use bytes::Bytes;
+use ethereum_types::H256;
+use journaldb::Algorithm;
+use kvdb_rocksdb::{Database, DatabaseConfig};
+use memorydb::MemoryDB;
+use parking_lot::Mutex;
+use rand::{XorShiftRng, SeedableRng};
+use rayon::prelude::*;
+use rlp::{RlpStream, UntrustedRlp};
+use snapshot::{chunk_state, Progress, SnappyKeccak, StateRebuilder};
+use snapshot::io::{PackedReader, PackedWriter, SnapshotReader, SnapshotWriter};
+use tempdir::TempDir;
+use super::helpers::StateProducer;
+
+// uncompressed state chunks of 50 accounts each.
+fn state_chunks() -> Vec<Bytes> {
+	let mut producer = StateProducer::new();
+	let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+	let mut old_db = MemoryDB::new();
+
+	for _ in 0..500 {
+		producer.tick(&mut rng, &mut old_db);
+	}
+
+	let tempdir = TempDir::new("").unwrap();
+	let snap_file = tempdir.path().join("SNAP");
+
+	let state_root = producer.state_root();
+	let writer = Mutex::new(PackedWriter::new(&snap_file).unwrap());
+	let state_hashes = chunk_state(&old_db, &state_root, &writer, &Progress::default(), &SnappyKeccak).unwrap();
+
+	writer.into_inner().finish(::snapshot::ManifestData {
+		version: 2,
+		state_hashes: state_hashes,
+		block_hashes: Vec::new(),
+		state_root: state_root,
+		block_number: 1000,
+		block_hash: H256::default(),
+	}).unwrap();
+
+	let reader = PackedReader::new(&snap_file).unwrap().unwrap();
+	let mut chunks = Vec::new();
+	for chunk_hash in &reader.manifest().state_hashes {
+		let chunk = ::snappy::decompress(&reader.chunk(*chunk_hash).unwrap()).unwrap();
+		let accounts: Vec<_> = UntrustedRlp::new(&chunk).iter().map(|account| account.as_raw().to_vec()).collect();
+		for part in accounts.chunks(50) {
+			let mut stream = RlpStream::new_list(part.len());
+			for account in part {
+				stream.append_raw(account, 1);
+			}
+			chunks.push(stream.out());
+		}
+	}
+	chunks
+}
+
+fn rebuilder(tempdir: &TempDir) -> StateRebuilder {
+	let db_cfg = DatabaseConfig::with_columns(::db::NUM_COLUMNS);
+	let db = Database::open(&db_cfg, &tempdir.path().join("db").to_string_lossy()).unwrap();
+	StateRebuilder::new(Arc::new(db), Algorithm::OverlayRecent)
+}
+
+#[bench]
+fn restore_state_sequentially(b: &mut Bencher) {
+	let chunks = state_chunks();
+	let flag = AtomicBool::new(true);
+
+	b.iter(|| {
+		let tempdir = TempDir::new("").unwrap();
+		let mut rebuilder = rebuilder(&tempdir);
+		for chunk in &chunks {
+			rebuilder.feed(chunk, &flag).unwrap();
+		}
+	});
+}
+
+#[bench]
+fn restore_state_in_parallel(b: &mut Bencher) {
+	let chunks = state_chunks();
+	let flag = AtomicBool::new(true);
+
+	b.iter(|| {
+		let tempdir = TempDir::new("").unwrap();
+		let mut rebuilder = rebuilder(&tempdir);
+		let prepared: Vec<_> = chunks.par_iter()
+			.map(|chunk| StateRebuilder::prepare(chunk, &flag).unwrap())
+			.collect();
+
+		for chunk in prepared {
+			rebuilder.commit(chunk, &flag).unwrap();
+		}
+	});
+}
@@ -21,6 +21,9 @@ mod proof_of_authority;
 mod state;
 mod service;
 
+#[cfg(all(feature = "benches", test))]
+mod benches;
+
 pub mod helpers;
 
 use super::ManifestData;
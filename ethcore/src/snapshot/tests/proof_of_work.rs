@@ -23,7 +23,7 @@ use error::Error;
 
 use blockchain::generator::{BlockGenerator, BlockBuilder};
 use blockchain::BlockChain;
-use snapshot::{chunk_secondary, Error as SnapshotError, Progress, SnapshotComponents};
+use snapshot::{chunk_secondary, Error as SnapshotError, Progress, SnappyKeccak, SnapshotComponents};
 use snapshot::io::{PackedReader, PackedWriter, SnapshotReader, SnapshotWriter};
 
 use parking_lot::Mutex;
@@ -64,7 +64,8 @@ fn chunk_and_restore(amount: u64) {
 		&bc,
 		best_hash,
 		&writer,
-		&Progress::default()
+		&Progress::default(),
+		&SnappyKeccak,
 	).unwrap();
 
 	let manifest = ::snapshot::ManifestData {
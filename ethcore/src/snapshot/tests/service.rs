@@ -21,6 +21,7 @@ use std::sync::Arc;
 use tempdir::TempDir;
 use client::{Client, BlockInfo};
 use ids::BlockId;
+use snapshot::io::FlatChunkStore;
 use snapshot::service::{Service, ServiceParams};
 use snapshot::{self, ManifestData, SnapshotService};
 use spec::Spec;
@@ -70,12 +71,20 @@ fn restored_is_equivalent() {
 		channel: IoChannel::disconnected(),
 		snapshot_root: path,
 		db_restore: client2.clone(),
+		restore_threads: Some(1),
+		max_pending_chunks: 0,
+		stall_timeout: None,
+		keep_failed_restoration: false,
+		max_restoration_peers: 0,
+		chunk_store: Arc::new(FlatChunkStore),
 	};
 
 	let service = Service::new(service_params).unwrap();
+	assert!(service.latest_manifest().is_none());
 	service.take_snapshot(&client, NUM_BLOCKS as u64).unwrap();
 
 	let manifest = service.manifest().unwrap();
+	assert_eq!(service.latest_manifest(), Some(manifest.clone()));
 
 	service.init_restore(manifest.clone(), true).unwrap();
 	assert!(service.init_restore(manifest.clone(), true).is_ok());
@@ -100,6 +109,83 @@ fn restored_is_equivalent() {
 	}
 }
 
+#[test]
+fn restored_concurrently_is_equivalent() {
+	use std::thread;
+
+	const NUM_BLOCKS: u32 = 400;
+	const TX_PER: usize = 5;
+
+	let gas_prices = vec![1.into(), 2.into(), 3.into(), 999.into()];
+
+	let client = generate_dummy_client_with_spec_and_data(Spec::new_null, NUM_BLOCKS, TX_PER, &gas_prices);
+
+	let tempdir = TempDir::new("").unwrap();
+	let client_db = tempdir.path().join("client_db");
+	let path = tempdir.path().join("snapshot");
+
+	let db_config = DatabaseConfig::with_columns(::db::NUM_COLUMNS);
+	let client_db = Database::open(&db_config, client_db.to_str().unwrap()).unwrap();
+
+	let spec = Spec::new_null();
+	let client2 = Client::new(
+		Default::default(),
+		&spec,
+		Arc::new(client_db),
+		Arc::new(::miner::Miner::with_spec(&spec)),
+		IoChannel::disconnected(),
+	).unwrap();
+
+	let service_params = ServiceParams {
+		engine: spec.engine.clone(),
+		genesis_block: spec.genesis_block(),
+		db_config: db_config,
+		pruning: ::journaldb::Algorithm::Archive,
+		channel: IoChannel::disconnected(),
+		snapshot_root: path,
+		db_restore: client2.clone(),
+		restore_threads: Some(4),
+		max_pending_chunks: 0,
+		stall_timeout: None,
+		keep_failed_restoration: false,
+		max_restoration_peers: 0,
+		chunk_store: Arc::new(FlatChunkStore),
+	};
+
+	let service = Arc::new(Service::new(service_params).unwrap());
+	service.take_snapshot(&client, NUM_BLOCKS as u64).unwrap();
+
+	let manifest = service.manifest().unwrap();
+	service.init_restore(manifest.clone(), true).unwrap();
+
+	let handles: Vec<_> = manifest.state_hashes.chunks(2).map(|hashes| {
+		let service = service.clone();
+		let hashes = hashes.to_vec();
+		thread::spawn(move || for hash in hashes {
+			let chunk = service.chunk(hash).unwrap();
+			service.feed_state_chunk(hash, &chunk);
+		})
+	}).collect();
+
+	for handle in handles {
+		handle.join().unwrap();
+	}
+
+	for hash in manifest.block_hashes {
+		let chunk = service.chunk(hash).unwrap();
+		service.feed_block_chunk(hash, &chunk);
+	}
+
+	assert_eq!(service.status(), ::snapshot::RestorationStatus::Inactive);
+
+	for x in 0..NUM_BLOCKS {
+		let block1 = client.block(BlockId::Number(x as u64)).unwrap();
+		let block2 = client2.block(BlockId::Number(x as u64)).unwrap();
+
+		assert_eq!(block1, block2);
+	}
+}
+
 #[test]
 fn guards_delete_folders() {
 	let spec = Spec::new_null();
@@ -112,6 +198,12 @@ fn guards_delete_folders() {
 		channel: IoChannel::disconnected(),
 		snapshot_root: tempdir.path().to_owned(),
 		db_restore: Arc::new(NoopDBRestore),
+		restore_threads: Some(1),
+		max_pending_chunks: 0,
+		stall_timeout: None,
+		keep_failed_restoration: false,
+		max_restoration_peers: 0,
+		chunk_store: Arc::new(FlatChunkStore),
 	};
 
 	let service = Service::new(service_params).unwrap();
@@ -138,3 +230,41 @@ fn guards_delete_folders() {
 	drop(service);
 	assert!(!path.exists());
 }
+
+#[test]
+fn rejects_unsupported_snapshot_version() {
+	let spec = Spec::new_null();
+	let tempdir = TempDir::new("").unwrap();
+	let service_params = ServiceParams {
+		engine: spec.engine.clone(),
+		genesis_block: spec.genesis_block(),
+		db_config: DatabaseConfig::with_columns(::db::NUM_COLUMNS),
+		pruning: ::journaldb::Algorithm::Archive,
+		channel: IoChannel::disconnected(),
+		snapshot_root: tempdir.path().to_owned(),
+		db_restore: Arc::new(NoopDBRestore),
+		restore_threads: Some(1),
+		max_pending_chunks: 0,
+		stall_timeout: None,
+		keep_failed_restoration: false,
+		max_restoration_peers: 0,
+		chunk_store: Arc::new(FlatChunkStore),
+	};
+
+	let service = Service::new(service_params).unwrap();
+
+	let manifest = ManifestData {
+		version: 99,
+		state_hashes: vec![],
+		block_hashes: vec![],
+		block_number: 0,
+		block_hash: Default::default(),
+		state_root: Default::default(),
+	};
+
+	match service.init_restore(manifest, true) {
+		Err(::error::Error::Snapshot(snapshot::Error::UnsupportedSnapshotVersion { found: 99, .. })) => {},
+		other => panic!("unexpected result: {:?}", other),
+	}
+	assert!(!tempdir.path().join("restoration").exists());
+}
@@ -22,7 +22,7 @@ use hash::{KECCAK_NULL_RLP, keccak};
 
 use basic_account::BasicAccount;
 use snapshot::account;
-use snapshot::{chunk_state, Error as SnapshotError, Progress, StateRebuilder};
+use snapshot::{chunk_state, estimate_state_size, Error as SnapshotError, Progress, SnappyKeccak, StateRebuilder};
 use snapshot::io::{PackedReader, PackedWriter, SnapshotReader, SnapshotWriter};
 use super::helpers::{compare_dbs, StateProducer};
 
@@ -34,7 +34,99 @@ use journaldb::{self, Algorithm};
 use kvdb_rocksdb::{Database, DatabaseConfig};
 use memorydb::MemoryDB;
 use parking_lot::Mutex;
+use rlp::UntrustedRlp;
 use tempdir::TempDir;
+use trie::{Trie, TrieDB};
+
+#[test]
+fn estimates_state_size() {
+	let mut producer = StateProducer::new();
+	let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+	let mut old_db = MemoryDB::new();
+
+	for _ in 0..150 {
+		producer.tick(&mut rng, &mut old_db);
+	}
+
+	let tempdir = TempDir::new("").unwrap();
+	let snap_file = tempdir.path().join("SNAP");
+
+	let state_root = producer.state_root();
+	let writer = Mutex::new(PackedWriter::new(&snap_file).unwrap());
+	let state_hashes = chunk_state(&old_db, &state_root, &writer, &Progress::default(), &SnappyKeccak).unwrap();
+
+	writer.into_inner().finish(::snapshot::ManifestData {
+		version: 2,
+		state_hashes: state_hashes,
+		block_hashes: Vec::new(),
+		state_root: state_root,
+		block_number: 1000,
+		block_hash: H256::default(),
+	}).unwrap();
+
+	let reader = PackedReader::new(&snap_file).unwrap().unwrap();
+	let mut raw = 0;
+	for chunk_hash in &reader.manifest().state_hashes {
+		let chunk = reader.chunk(*chunk_hash).unwrap();
+		raw += ::snappy::decompress(&chunk).unwrap().len() as u64;
+	}
+
+	// only the chunks' list headers are left out.
+	let estimate = estimate_state_size(&old_db, &state_root).unwrap();
+	assert!(estimate <= raw);
+	assert!(estimate >= raw - raw / 100);
+}
+
+#[test]
+fn serves_accounts_restored_so_far() {
+	let mut producer = StateProducer::new();
+	let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+	let mut old_db = MemoryDB::new();
+	let db_cfg = DatabaseConfig::with_columns(::db::NUM_COLUMNS);
+
+	for _ in 0..150 {
+		producer.tick(&mut rng, &mut old_db);
+	}
+
+	let tempdir = TempDir::new("").unwrap();
+	let snap_file = tempdir.path().join("SNAP");
+
+	let state_root = producer.state_root();
+	let writer = Mutex::new(PackedWriter::new(&snap_file).unwrap());
+	let state_hashes = chunk_state(&old_db, &state_root, &writer, &Progress::default(), &SnappyKeccak).unwrap();
+
+	let reader = {
+		writer.into_inner().finish(::snapshot::ManifestData {
+			version: 2,
+			state_hashes: state_hashes.clone(),
+			block_hashes: Vec::new(),
+			state_root: state_root,
+			block_number: 1000,
+			block_hash: H256::default(),
+		}).unwrap();
+		PackedReader::new(&snap_file).unwrap().unwrap()
+	};
+
+	let new_db = Arc::new(Database::open(&db_cfg, &tempdir.path().join("db").to_string_lossy()).unwrap());
+	let mut rebuilder = StateRebuilder::new(new_db, Algorithm::OverlayRecent);
+	let chunk = ::snappy::decompress(&reader.chunk(state_hashes[0]).unwrap()).unwrap();
+	let hashes: Vec<H256> = UntrustedRlp::new(&chunk).iter().map(|account| account.val_at(0).unwrap()).collect();
+	assert!(hashes.len() > 2);
+
+	assert_eq!(rebuilder.restored_account(&hashes[1]).unwrap(), None);
+	rebuilder.feed(&chunk, &AtomicBool::new(true)).unwrap();
+
+	let old_trie = TrieDB::new(&old_db, &state_root).unwrap();
+	for (i, hash) in hashes.iter().enumerate() {
+		let restored = rebuilder.restored_account(hash).unwrap();
+		if i == 0 || i == hashes.len() - 1 {
+			assert_eq!(restored, None);
+		} else {
+			let expected: BasicAccount = ::rlp::decode(&old_trie.get(hash).unwrap().unwrap());
+			assert_eq!(restored, Some(expected));
+		}
+	}
+}
 
 #[test]
 fn snap_and_restore() {
@@ -53,7 +145,7 @@ fn snap_and_restore() {
 	let state_root = producer.state_root();
 	let writer = Mutex::new(PackedWriter::new(&snap_file).unwrap());
 
-	let state_hashes = chunk_state(&old_db, &state_root, &writer, &Progress::default()).unwrap();
+	let state_hashes = chunk_state(&old_db, &state_root, &writer, &Progress::default(), &SnappyKeccak).unwrap();
 
 	writer.into_inner().finish(::snapshot::ManifestData {
 		version: 2,
@@ -91,6 +183,78 @@ fn snap_and_restore() {
 	compare_dbs(&old_db, new_db.as_hashdb());
 }
 
+#[test]
+fn restores_chunks_prepared_in_parallel() {
+	use std::thread;
+	use rlp::RlpStream;
+
+	let mut producer = StateProducer::new();
+	let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+	let mut old_db = MemoryDB::new();
+	let db_cfg = DatabaseConfig::with_columns(::db::NUM_COLUMNS);
+
+	for _ in 0..150 {
+		producer.tick(&mut rng, &mut old_db);
+	}
+
+	let tempdir = TempDir::new("").unwrap();
+	let snap_file = tempdir.path().join("SNAP");
+
+	let state_root = producer.state_root();
+	let writer = Mutex::new(PackedWriter::new(&snap_file).unwrap());
+	let state_hashes = chunk_state(&old_db, &state_root, &writer, &Progress::default(), &SnappyKeccak).unwrap();
+
+	writer.into_inner().finish(::snapshot::ManifestData {
+		version: 2,
+		state_hashes: state_hashes,
+		block_hashes: Vec::new(),
+		state_root: state_root,
+		block_number: 1000,
+		block_hash: H256::default(),
+	}).unwrap();
+
+	// split the state into many small chunks, which are prepared at once and committed backwards.
+	let reader = PackedReader::new(&snap_file).unwrap().unwrap();
+	let mut chunks = Vec::new();
+	for chunk_hash in &reader.manifest().state_hashes {
+		let chunk = ::snappy::decompress(&reader.chunk(*chunk_hash).unwrap()).unwrap();
+		let accounts: Vec<_> = UntrustedRlp::new(&chunk).iter().map(|account| account.as_raw().to_vec()).collect();
+		for part in accounts.chunks(7) {
+			let mut stream = RlpStream::new_list(part.len());
+			for account in part {
+				stream.append_raw(account, 1);
+			}
+			chunks.push(stream.out());
+		}
+	}
+	assert!(chunks.len() > 2);
+
+	let handles: Vec<_> = chunks.into_iter().map(|chunk| thread::spawn(move || {
+		StateRebuilder::prepare(&chunk, &AtomicBool::new(true)).unwrap()
+	})).collect();
+	let mut prepared: Vec<_> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+	prepared.reverse();
+
+	let db_path = tempdir.path().join("db");
+	let db = {
+		let new_db = Arc::new(Database::open(&db_cfg, &db_path.to_string_lossy()).unwrap());
+		let mut rebuilder = StateRebuilder::new(new_db.clone(), Algorithm::OverlayRecent);
+		let flag = AtomicBool::new(true);
+
+		for chunk in prepared {
+			rebuilder.commit(chunk, &flag).unwrap();
+		}
+
+		assert_eq!(rebuilder.state_root(), state_root);
+		rebuilder.finalize(1000, H256::default()).unwrap();
+
+		new_db
+	};
+
+	let new_db = journaldb::new(db, Algorithm::OverlayRecent, ::db::COL_STATE);
+	compare_dbs(&old_db, new_db.as_hashdb());
+}
+
 #[test]
 fn get_code_from_prev_chunk() {
 	use std::collections::HashSet;
@@ -164,7 +328,7 @@ fn checks_flag() {
 	let state_root = producer.state_root();
 	let writer = Mutex::new(PackedWriter::new(&snap_file).unwrap());
 
-	let state_hashes = chunk_state(&old_db, &state_root, &writer, &Progress::default()).unwrap();
+	let state_hashes = chunk_state(&old_db, &state_root, &writer, &Progress::default(), &SnappyKeccak).unwrap();
 
 	writer.into_inner().finish(::snapshot::ManifestData {
 		version: 2,
@@ -18,6 +18,18 @@ use super::{ManifestData, RestorationStatus};
 use ethereum_types::H256;
 use bytes::Bytes;
 
+/// Identifier of the peer a chunk was received from.
+pub type PeerId = usize;
+
+/// How many chunks of each kind should be requested concurrently during restoration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestoreParallelism {
+	/// Number of state chunks to keep in flight.
+	pub state: usize,
+	/// Number of block chunks to keep in flight.
+	pub block: usize,
+}
+
 /// The interface for a snapshot network service.
 /// This handles:
 ///    - restoration of snapshots to temporary databases.
@@ -44,11 +56,34 @@ pub trait SnapshotService : Sync + Send {
 	/// Abort an in-progress restoration if there is one.
 	fn abort_restore(&self);
 
+	/// Whether the service has room to queue another chunk for restoration.
+	/// Chunks fed while this is `false` are dropped.
+	fn can_accept_chunk(&self) -> bool;
+
+	/// Ask how many chunks of each kind should be requested at once for the
+	/// current restoration.
+	fn recommended_parallelism(&self) -> RestoreParallelism;
+
+	/// Hash of a raw chunk as listed in the manifest of the current restoration,
+	/// which depends on the snapshot's format.
+	fn chunk_hash(&self, chunk: &[u8]) -> H256;
+
 	/// Feed a raw state chunk to the service to be processed asynchronously.
 	/// no-op if not currently restoring.
 	fn restore_state_chunk(&self, hash: H256, chunk: Bytes);
 
+	/// Feed a raw state chunk received from `peer` to the service to be processed
+	/// asynchronously. The peer is reported by `bad_chunk_peers` if the chunk is malformed.
+	/// no-op if not currently restoring.
+	fn restore_state_chunk_from(&self, peer: PeerId, hash: H256, chunk: Bytes);
+
 	/// Feed a raw block chunk to the service to be processed asynchronously.
 	/// no-op if currently restoring.
 	fn restore_block_chunk(&self, hash: H256, chunk: Bytes);
+
+	/// Peers which sent malformed chunks, so that they can be disconnected.
+	fn bad_chunk_peers(&self) -> Vec<PeerId>;
+
+	/// Forget about a peer which disconnected, since its id may be reused.
+	fn peer_disconnected(&self, peer: PeerId);
 }
@@ -77,6 +77,7 @@ pub struct Watcher {
 	broadcast: Box<Broadcast>,
 	period: u64,
 	history: u64,
+	start_delay: u64,
 }
 
 impl Watcher {
@@ -94,8 +95,15 @@ impl Watcher {
 			broadcast: Box::new(Mutex::new(channel)),
 			period: period,
 			history: history,
+			start_delay: 0,
 		}
 	}
+
+	/// Don't trigger any snapshot before a block at least `blocks` past genesis is imported.
+	pub fn with_start_delay(mut self, blocks: u64) -> Self {
+		self.start_delay = blocks;
+		self
+	}
 }
 
 impl ChainNotify for Watcher {
@@ -115,6 +123,7 @@ impl ChainNotify for Watcher {
 
 		let highest = imported.into_iter()
 			.filter_map(|h| self.oracle.to_number(h))
+			.filter(|&num| num >= self.start_delay)
 			.filter(|&num| num >= self.period + self.history)
 			.map(|num| num - self.history)
 			.filter(|num| num % self.period == 0)
@@ -158,6 +167,10 @@ mod tests {
 
 	// helper harness for tests which expect a notification.
 	fn harness(numbers: Vec<u64>, period: u64, history: u64, expected: Option<u64>) {
+		delayed_harness(numbers, period, history, 0, expected)
+	}
+
+	fn delayed_harness(numbers: Vec<u64>, period: u64, history: u64, start_delay: u64, expected: Option<u64>) {
 		let hashes: Vec<_> = numbers.clone().into_iter().map(|x| H256::from(U256::from(x))).collect();
 		let map = hashes.clone().into_iter().zip(numbers).collect();
 
@@ -166,6 +179,7 @@ mod tests {
 			broadcast: Box::new(TestBroadcast(expected)),
 			period: period,
 			history: history,
+			start_delay: start_delay,
 		};
 
 		watcher.new_blocks(
@@ -200,4 +214,10 @@ mod tests {
 	fn doesnt_fire_before_history() {
 		harness(vec![10, 11], 10, 5, None);
 	}
+
+	#[test]
+	fn doesnt_fire_before_start_delay() {
+		delayed_harness(vec![15], 10, 5, 20, None);
+		delayed_harness(vec![25], 10, 5, 20, Some(20));
+	}
 }
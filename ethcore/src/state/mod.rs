@@ -499,6 +499,11 @@ impl<B: Backend> State<B> {
 		&self.root
 	}
 
+	/// Return reference to the backing database
+	pub fn db(&self) -> &B {
+		&self.db
+	}
+
 	/// Create a new contract at address `contract`. If there is already an account at the address
 	/// it will have its code reset, ready for `init_code()`.
 	pub fn new_contract(&mut self, contract: &Address, balance: U256, nonce_offset: U256) {
@@ -374,6 +374,46 @@ impl StateDB {
 		self.cache_size
 	}
 
+	/// Change how much memory is set aside for the shared caches (in bytes), evicting the
+	/// least recently used entries if they shrink. Instances cloned from this one later
+	/// report the new size; the caches themselves are shared by all instances anyway.
+	pub fn set_cache_size(&mut self, cache_size: usize) {
+		let acc_cache_size = cache_size * ACCOUNT_CACHE_RATIO / 100;
+		let cache_items = acc_cache_size / ::std::mem::size_of::<Option<Account>>();
+		self.account_cache.lock().accounts.set_capacity(cache_items);
+		self.code_cache.lock().set_max_size(cache_size - acc_cache_size);
+		self.cache_size = cache_size;
+	}
+
+	/// Addresses of the existing accounts in the shared cache, least recently used first.
+	pub fn cached_accounts(&self) -> Vec<Address> {
+		self.account_cache.lock().accounts.iter()
+			.filter(|&(_, account)| account.is_some())
+			.map(|(address, _)| address.clone())
+			.collect()
+	}
+
+	/// Add accounts read from the state this instance was created on to the shared cache.
+	/// Nothing is added if a block was committed on top of that state in the meantime,
+	/// as the accounts may be outdated. Already cached accounts are left alone.
+	/// Returns the number of accounts added.
+	pub fn warm_account_cache(&self, accounts: Vec<(Address, Option<Account>)>) -> usize {
+		let mut cache = self.account_cache.lock();
+		let latest = cache.modifications.front().map(|m| m.hash.clone());
+		if self.parent_hash.is_none() || (latest.is_some() && latest != self.parent_hash) {
+			return 0;
+		}
+
+		let mut added = 0;
+		for (address, account) in accounts {
+			if !cache.accounts.contains_key(&address) {
+				cache.accounts.insert(address, account);
+				added += 1;
+			}
+		}
+		added
+	}
+
 	/// Check if the account can be returned from cache by matching current block parent hash against canonical
 	/// state and filtering out account modified in later blocks.
 	fn is_allowed(addr: &Address, parent_hash: &Option<H256>, modifications: &VecDeque<BlockChanges>) -> bool {
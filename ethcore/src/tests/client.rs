@@ -18,17 +18,19 @@ use std::str::FromStr;
 use std::sync::Arc;
 use hash::keccak;
 use io::IoChannel;
-use client::{BlockChainClient, Client, ClientConfig, BlockId, ChainInfo, BlockInfo, PrepareOpenBlock, ImportSealedBlock, ImportBlock};
+use client::{BlockChainClient, Client, ClientConfig, BlockId, ChainInfo, BlockInfo, PrepareOpenBlock, ImportSealedBlock, ImportBlock, ImportRejection, LOCAL_PEER_ID};
 use state::{self, State, CleanupMode};
 use executive::{Executive, TransactOptions};
 use ethereum;
 use block::IsBlock;
 use tests::helpers::{
 	generate_dummy_client, push_blocks_to_client, get_test_client_with_blocks, get_good_dummy_block_seq,
-	generate_dummy_client_with_data, get_good_dummy_block, get_bad_state_dummy_block
+	generate_dummy_client_with_data, get_good_dummy_block, get_bad_state_dummy_block, create_test_block,
+	get_good_dummy_block_hash, get_good_dummy_block_fork_seq
 };
 use types::filter::Filter;
 use ethereum_types::{U256, Address};
+use header::Header;
 use kvdb_rocksdb::{Database, DatabaseConfig};
 use miner::Miner;
 use spec::Spec;
@@ -56,6 +58,112 @@ fn imports_from_empty() {
 	client.flush_queue();
 }
 
+#[test]
+fn rejects_database_with_other_genesis() {
+	let tempdir = TempDir::new("").unwrap();
+	let spec = Spec::new_test();
+	let db_config = DatabaseConfig::with_columns(::db::NUM_COLUMNS);
+	let client_db = Arc::new(Database::open(&db_config, tempdir.path().to_str().unwrap()).unwrap());
+
+	Client::new(
+		ClientConfig::default(),
+		&spec,
+		client_db.clone(),
+		Arc::new(Miner::with_spec(&spec)),
+		IoChannel::disconnected(),
+	).unwrap();
+
+	let other_spec = Spec::new_null();
+	assert!(Client::new(
+		ClientConfig::default(),
+		&other_spec,
+		client_db.clone(),
+		Arc::new(Miner::with_spec(&other_spec)),
+		IoChannel::disconnected(),
+	).is_err());
+
+	let mut config = ClientConfig::default();
+	config.skip_genesis_check = true;
+	assert!(Client::new(
+		config,
+		&other_spec,
+		client_db,
+		Arc::new(Miner::with_spec(&other_spec)),
+		IoChannel::disconnected(),
+	).is_ok());
+}
+
+#[test]
+fn checks_expected_genesis_before_writing_it() {
+	use std::sync::Mutex;
+	use client::ChainNotify;
+	use ethereum_types::H256;
+
+	#[derive(Default)]
+	struct GenesisNotify(Mutex<Vec<H256>>);
+
+	impl ChainNotify for GenesisNotify {
+		fn genesis_written(&self, hash: H256) {
+			self.0.lock().unwrap().push(hash);
+		}
+	}
+
+	let tempdir = TempDir::new("").unwrap();
+	let spec = Spec::new_test();
+	let genesis = spec.genesis_header().hash();
+	let db_config = DatabaseConfig::with_columns(::db::NUM_COLUMNS);
+	let client_db = Arc::new(Database::open(&db_config, tempdir.path().to_str().unwrap()).unwrap());
+
+	let config = |expected_genesis| {
+		let mut config = ClientConfig::default();
+		config.expected_genesis = Some(expected_genesis);
+		config
+	};
+
+	assert!(Client::new(
+		config(H256::from(1)),
+		&spec,
+		client_db.clone(),
+		Arc::new(Miner::with_spec(&spec)),
+		IoChannel::disconnected(),
+	).is_err());
+
+	let client = Client::new(
+		config(genesis),
+		&spec,
+		client_db.clone(),
+		Arc::new(Miner::with_spec(&spec)),
+		IoChannel::disconnected(),
+	).unwrap();
+	let notify = Arc::new(GenesisNotify::default());
+	client.add_notify(notify.clone());
+	assert_eq!(*notify.0.lock().unwrap(), vec![genesis]);
+	drop(client);
+
+	let client = Client::new(
+		config(genesis),
+		&spec,
+		client_db,
+		Arc::new(Miner::with_spec(&spec)),
+		IoChannel::disconnected(),
+	).unwrap();
+	let notify = Arc::new(GenesisNotify::default());
+	client.add_notify(notify.clone());
+	assert!(notify.0.lock().unwrap().is_empty());
+}
+
+#[test]
+fn pins_available_state_only() {
+	let client = generate_dummy_client(3);
+
+	client.pin_state(1).unwrap();
+	assert!(client.pin_state(10).is_err());
+	assert_eq!(client.pinned_state(), vec![1]);
+
+	client.unpin_state(1).unwrap();
+	assert!(client.pinned_state().is_empty());
+}
+
 #[test]
 fn should_return_registrar() {
 	let tempdir = TempDir::new("").unwrap();
@@ -111,6 +219,277 @@ fn imports_good_block() {
 	assert!(!block.into_inner().is_empty());
 }
 
+#[test]
+fn imports_good_block_verifying_state_root() {
+	let tempdir = TempDir::new("").unwrap();
+	let spec = Spec::new_test();
+	let db_config = DatabaseConfig::with_columns(::db::NUM_COLUMNS);
+	let client_db = Arc::new(Database::open(&db_config, tempdir.path().to_str().unwrap()).unwrap());
+
+	let mut config = ClientConfig::default();
+	config.verify_state_root_on_import = true;
+	let client = Client::new(
+		config,
+		&spec,
+		client_db,
+		Arc::new(Miner::with_spec(&spec)),
+		IoChannel::disconnected(),
+	).unwrap();
+	client.import_block(get_good_dummy_block()).unwrap();
+	client.flush_queue();
+	client.import_verified_blocks();
+
+	assert_eq!(client.chain_info().best_block_number, 1);
+}
+
+#[test]
+fn queue_reports_full_at_memory_limit() {
+	let tempdir = TempDir::new("").unwrap();
+	let spec = Spec::new_test();
+	let db_config = DatabaseConfig::with_columns(::db::NUM_COLUMNS);
+	let client_db = Arc::new(Database::open(&db_config, tempdir.path().to_str().unwrap()).unwrap());
+
+	let mut config = ClientConfig::default();
+	config.max_queue_memory = Some(1);
+	let client = Client::new(
+		config,
+		&spec,
+		client_db,
+		Arc::new(Miner::with_spec(&spec)),
+		IoChannel::disconnected(),
+	).unwrap();
+	assert!(!client.queue_info().is_full());
+
+	client.import_block(get_good_dummy_block()).unwrap();
+	client.flush_queue();
+	assert!(client.queue_memory_usage().verified_blocks > 0);
+	assert!(client.queue_info().is_full());
+}
+
+#[test]
+fn refuses_side_chain_blocks_over_limit() {
+	let tempdir = TempDir::new("").unwrap();
+	let spec = Spec::new_test();
+	let db_config = DatabaseConfig::with_columns(::db::NUM_COLUMNS);
+	let client_db = Arc::new(Database::open(&db_config, tempdir.path().to_str().unwrap()).unwrap());
+
+	let mut config = ClientConfig::default();
+	config.max_side_chain_blocks = Some(1);
+	let client = Client::new(
+		config,
+		&spec,
+		client_db,
+		Arc::new(Miner::with_spec(&spec)),
+		IoChannel::disconnected(),
+	).unwrap();
+
+	for block in get_good_dummy_block_seq(1) {
+		client.import_block(block).unwrap();
+	}
+	client.flush_queue();
+	client.import_verified_blocks();
+	assert_eq!(client.side_chain_block_count(), 0);
+
+	// a lighter sibling of block 1.
+	client.import_block(get_good_dummy_block()).unwrap();
+	client.flush_queue();
+	client.import_verified_blocks();
+	assert_eq!(client.chain_info().best_block_number, 2);
+	assert_eq!(client.side_chain_block_count(), 1);
+
+	let mut header = Header::new();
+	header.set_gas_limit(spec.genesis_header().gas_limit().clone());
+	header.set_difficulty(U256::from(0x20000));
+	header.set_timestamp(50);
+	header.set_number(1);
+	header.set_parent_hash(spec.genesis_header().hash());
+	header.set_state_root(spec.genesis_header().state_root().clone());
+	assert!(client.import_block(create_test_block(&header)).is_err());
+}
+
+#[test]
+fn holds_back_blocks_beyond_tolerance() {
+	use std::time::{Duration, SystemTime, UNIX_EPOCH};
+	use error::{BlockImportError, ImportError};
+
+	let tempdir = TempDir::new("").unwrap();
+	let spec = Spec::new_test();
+	let db_config = DatabaseConfig::with_columns(::db::NUM_COLUMNS);
+	let client_db = Arc::new(Database::open(&db_config, tempdir.path().to_str().unwrap()).unwrap());
+
+	let mut config = ClientConfig::default();
+	config.future_block_tolerance = Duration::from_secs(60);
+	let client = Client::new(
+		config,
+		&spec,
+		client_db,
+		Arc::new(Miner::with_spec(&spec)),
+		IoChannel::disconnected(),
+	).unwrap();
+
+	let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+	let block_at = |timestamp| {
+		let mut header = Header::new();
+		header.set_gas_limit(spec.genesis_header().gas_limit().clone());
+		header.set_difficulty(U256::from(0x20000));
+		header.set_timestamp(timestamp);
+		header.set_number(1);
+		header.set_parent_hash(spec.genesis_header().hash());
+		header.set_state_root(spec.genesis_header().state_root().clone());
+		create_test_block(&header)
+	};
+
+	// further ahead than the verifier's default allows, but within the tolerance.
+	assert!(client.import_block(block_at(now + 30)).is_ok());
+
+	match client.import_block(block_at(now + 100)) {
+		Err(BlockImportError::Import(ImportError::HeldBack)) => {},
+		other => panic!("expected the block to be held back, got {:?}", other),
+	}
+	assert_eq!(client.report().future_blocks_queued, 1);
+}
+
+#[test]
+fn refuses_reorgs_deeper_than_limit() {
+	let tempdir = TempDir::new("").unwrap();
+	let spec = Spec::new_test();
+	let db_config = DatabaseConfig::with_columns(::db::NUM_COLUMNS);
+	let client_db = Arc::new(Database::open(&db_config, tempdir.path().to_str().unwrap()).unwrap());
+
+	let mut config = ClientConfig::default();
+	config.max_reorg_depth = Some(0);
+	let client = Client::new(
+		config,
+		&spec,
+		client_db,
+		Arc::new(Miner::with_spec(&spec)),
+		IoChannel::disconnected(),
+	).unwrap();
+
+	let (best_hash, best_block) = get_good_dummy_block_hash();
+	client.import_block(best_block).unwrap();
+	client.flush_queue();
+	client.import_verified_blocks();
+	assert_eq!(client.chain_info().best_block_hash, best_hash);
+
+	// a heavier sibling of block 1, which would retract it.
+	let fork = get_good_dummy_block_fork_seq(1, 0, &spec.genesis_header().hash());
+	let fork_hash = BlockView::new(&fork[0]).header().hash();
+	client.import_block(fork[0].clone()).unwrap();
+	client.flush_queue();
+	client.import_verified_blocks();
+
+	assert_eq!(client.chain_info().best_block_hash, best_hash);
+	assert_eq!(client.recent_import_rejections(), vec![(fork_hash, ImportRejection::ReorgTooDeep(1))]);
+}
+
+#[test]
+fn records_blocks_rejected_by_verification() {
+	let client = generate_dummy_client(0);
+	let spec = Spec::new_test();
+
+	let mut header = Header::new();
+	header.set_gas_limit(spec.genesis_header().gas_limit().clone());
+	header.set_difficulty(U256::from(0x20000));
+	header.set_timestamp(40);
+	header.set_number(1);
+	header.set_parent_hash(spec.genesis_header().hash());
+	header.set_state_root(spec.genesis_header().state_root().clone());
+	// longer than the spec's maximum.
+	header.set_extra_data(vec![0; 64]);
+
+	assert!(client.import_block(create_test_block(&header)).is_err());
+	let rejections = client.recent_import_rejections();
+	assert_eq!(rejections.len(), 1);
+	assert_eq!(rejections[0].0, header.hash());
+	match rejections[0].1 {
+		ImportRejection::Basic(_) => {},
+		ref other => panic!("expected a basic verification failure, got {:?}", other),
+	}
+}
+
+#[test]
+fn prefetches_accounts_into_state_cache() {
+	let client = generate_dummy_client(0);
+	let existing = Address::from(1);
+	let missing = Address::from(0x99);
+
+	assert!(client.cached_accounts().is_empty());
+	assert_eq!(client.prefetch_accounts(&[existing, missing]).unwrap(), 2);
+	assert_eq!(client.cached_accounts(), vec![existing]);
+	assert_eq!(client.prefetch_accounts(&[existing]).unwrap(), 0);
+}
+
+#[test]
+fn uses_configured_verifier_threads() {
+	let spec = Spec::new_test();
+	let db_config = DatabaseConfig::with_columns(::db::NUM_COLUMNS);
+
+	for &(threads, ok) in &[(Some(0), false), (Some(3), true)] {
+		let tempdir = TempDir::new("").unwrap();
+		let client_db = Arc::new(Database::open(&db_config, tempdir.path().to_str().unwrap()).unwrap());
+
+		let mut config = ClientConfig::default();
+		config.verifier_threads = threads;
+		let client = Client::new(
+			config,
+			&spec,
+			client_db,
+			Arc::new(Miner::with_spec(&spec)),
+			IoChannel::disconnected(),
+		);
+		assert_eq!(client.is_ok(), ok);
+	}
+}
+
+#[test]
+fn drops_blocks_rejected_by_filter() {
+	let tempdir = TempDir::new("").unwrap();
+	let spec = Spec::new_test();
+	let db_config = DatabaseConfig::with_columns(::db::NUM_COLUMNS);
+	let client_db = Arc::new(Database::open(&db_config, tempdir.path().to_str().unwrap()).unwrap());
+
+	let client = Client::new(
+		ClientConfig::default(),
+		&spec,
+		client_db,
+		Arc::new(Miner::with_spec(&spec)),
+		IoChannel::disconnected(),
+	).unwrap();
+	client.set_block_filter(Box::new(|header: &Header| header.timestamp() != 40));
+
+	client.import_block(get_good_dummy_block()).unwrap();
+	client.flush_queue();
+	client.import_verified_blocks();
+	assert!(client.block_header(BlockId::Number(1)).is_none());
+}
+
+#[test]
+fn reports_gas_stats_of_imported_blocks() {
+	use std::sync::Mutex;
+	use client::ChainNotify;
+
+	#[derive(Default)]
+	struct GasStats(Mutex<Vec<(u64, U256, U256, Option<U256>)>>);
+
+	impl ChainNotify for GasStats {
+		fn block_gas_stats(&self, number: u64, gas_used: U256, gas_limit: U256, base_fee: Option<U256>) {
+			self.0.lock().unwrap().push((number, gas_used, gas_limit, base_fee));
+		}
+	}
+
+	let client = generate_dummy_client(0);
+	let stats = Arc::new(GasStats::default());
+	client.add_notify(stats.clone());
+
+	client.import_block(get_good_dummy_block()).unwrap();
+	client.flush_queue();
+	client.import_verified_blocks();
+
+	let gas_limit = *Spec::new_test().genesis_header().gas_limit();
+	assert_eq!(*stats.0.lock().unwrap(), vec![(1, U256::zero(), gas_limit, None)]);
+}
+
 #[test]
 fn query_none_block() {
 	let tempdir = TempDir::new("").unwrap();
@@ -137,6 +516,21 @@ fn query_bad_block() {
 	assert!(bad_block.is_none());
 }
 
+#[test]
+fn records_import_rejections() {
+	let bad_block = get_bad_state_dummy_block();
+	let hash = BlockView::new(&bad_block).header().hash();
+	let client = get_test_client_with_blocks(vec![bad_block]);
+
+	let rejections = client.recent_import_rejections();
+	assert_eq!(rejections.len(), 1);
+	assert_eq!(rejections[0].0, hash);
+	match rejections[0].1 {
+		ImportRejection::Final(_) => {},
+		ref other => panic!("unexpected rejection: {:?}", other),
+	}
+}
+
 #[test]
 fn returns_chain_info() {
 	let dummy_block = get_good_dummy_block();
@@ -333,6 +727,53 @@ fn does_not_propagate_delayed_transactions() {
 	assert_eq!(2, client.miner().pending_transactions().len());
 }
 
+#[test]
+fn rejects_underpriced_queued_transactions() {
+	let key = KeyPair::from_secret(keccak("test").into()).unwrap();
+	let tx = Transaction {
+		nonce: 0.into(),
+		gas_price: 0.into(),
+		gas: 21000.into(),
+		action: Action::Call(Address::default()),
+		value: 0.into(),
+		data: Vec::new(),
+	}.sign(key.secret(), None);
+	let rlp = ::rlp::encode(&tx).into_vec();
+	let client = generate_dummy_client(0);
+
+	client.set_minimal_gas_price(Some(1.into()));
+	client.import_queued_transactions(&[rlp.clone()], 0);
+	assert_eq!(client.tx_type_stats().underpriced, 1);
+	assert_eq!(0, client.miner().pending_transactions().len());
+
+	client.set_minimal_gas_price(None);
+	client.import_queued_transactions(&[rlp], 0);
+	assert_eq!(client.tx_type_stats().underpriced, 1);
+}
+
+#[test]
+fn counts_only_network_transactions_by_type() {
+	let key = KeyPair::from_secret(keccak("test").into()).unwrap();
+	let tx = Transaction {
+		nonce: 0.into(),
+		gas_price: 0.into(),
+		gas: 21000.into(),
+		action: Action::Call(Address::default()),
+		value: 0.into(),
+		data: Vec::new(),
+	}.sign(key.secret(), None);
+	let rlp = ::rlp::encode(&tx).into_vec();
+	let client = generate_dummy_client(0);
+
+	client.import_queued_transactions(&[rlp.clone(), vec![0x80]], LOCAL_PEER_ID);
+	assert_eq!(client.tx_type_stats(), Default::default());
+
+	client.import_queued_transactions(&[rlp, vec![0x80]], 0);
+	let stats = client.tx_type_stats();
+	assert_eq!(stats.unprotected, 1);
+	assert_eq!(stats.undecodable, 1);
+}
+
 #[test]
 fn transaction_proof() {
 	use ::client::ProvingBlockChainClient;
@@ -342,10 +342,15 @@ pub fn get_bad_state_dummy_block() -> Bytes {
 #[derive(Default)]
 pub struct TestNotify {
 	pub messages: RwLock<Vec<Bytes>>,
+	pub authority_statuses: RwLock<Vec<bool>>,
 }
 
 impl ChainNotify for TestNotify {
 	fn broadcast(&self, data: Vec<u8>) {
 		self.messages.write().push(data);
 	}
+
+	fn authority_status_changed(&self, is_authority: bool) {
+		self.authority_statuses.write().push(is_authority);
+	}
 }
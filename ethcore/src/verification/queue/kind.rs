@@ -57,8 +57,9 @@ pub trait Kind: 'static + Sized + Send + Sync {
 	/// The third stage: completely verified.
 	type Verified: Sized + Send + BlockLike + HeapSizeOf;
 
-	/// Attempt to create the `Unverified` item from the input.
-	fn create(input: Self::Input, engine: &EthEngine) -> Result<Self::Unverified, Error>;
+	/// Attempt to create the `Unverified` item from the input, allowing timestamps
+	/// up to `max_drift` seconds ahead of the local clock.
+	fn create(input: Self::Input, engine: &EthEngine, max_drift: u64) -> Result<Self::Unverified, Error>;
 
 	/// Attempt to verify the `Unverified` item using the given engine.
 	fn verify(unverified: Self::Unverified, engine: &EthEngine, check_seal: bool) -> Result<Self::Verified, Error>;
@@ -85,8 +86,8 @@ pub mod blocks {
 		type Unverified = Unverified;
 		type Verified = PreverifiedBlock;
 
-		fn create(input: Self::Input, engine: &EthEngine) -> Result<Self::Unverified, Error> {
-			match verify_block_basic(&input.header, &input.bytes, engine) {
+		fn create(input: Self::Input, engine: &EthEngine, max_drift: u64) -> Result<Self::Unverified, Error> {
+			match verify_block_basic(&input.header, &input.bytes, engine, max_drift) {
 				Ok(()) => Ok(input),
 				Err(e) => {
 					warn!(target: "client", "Stage 1 block verification failed for {}: {:?}", input.hash(), e);
@@ -124,6 +125,16 @@ pub mod blocks {
 				bytes: bytes,
 			}
 		}
+
+		/// The block's header.
+		pub fn header(&self) -> &Header {
+			&self.header
+		}
+
+		/// Consume the block, returning its RLP.
+		pub fn into_bytes(self) -> Bytes {
+			self.bytes
+		}
 	}
 
 	impl HeapSizeOf for Unverified {
@@ -186,8 +197,8 @@ pub mod headers {
 		type Unverified = Header;
 		type Verified = Header;
 
-		fn create(input: Self::Input, engine: &EthEngine) -> Result<Self::Unverified, Error> {
-			verify_header_params(&input, engine, true).map(|_| input)
+		fn create(input: Self::Input, engine: &EthEngine, max_drift: u64) -> Result<Self::Unverified, Error> {
+			verify_header_params(&input, engine, true, max_drift).map(|_| input)
 		}
 
 		fn verify(unverified: Self::Unverified, engine: &EthEngine, check_seal: bool) -> Result<Self::Verified, Error> {
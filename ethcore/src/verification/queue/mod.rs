@@ -41,6 +41,8 @@ const MIN_QUEUE_LIMIT: usize = 512;
 
 // maximum possible number of verification threads.
 const MAX_VERIFIERS: usize = 8;
+// maximum number of rejections kept until they're taken.
+const MAX_REJECTED: usize = 64;
 
 /// Type alias for block queue convenience.
 pub type BlockQueue = VerificationQueue<self::kind::Blocks>;
@@ -59,6 +61,9 @@ pub struct Config {
 	pub max_mem_use: usize,
 	/// Settings for the number of verifiers and adaptation strategy.
 	pub verifier_settings: VerifierSettings,
+	/// How far ahead of the local clock timestamps may be, in seconds.
+	/// Later items are temporarily invalid.
+	pub max_timestamp_drift: u64,
 }
 
 impl Default for Config {
@@ -67,6 +72,7 @@ impl Default for Config {
 			max_queue_size: 30000,
 			max_mem_use: 50 * 1024 * 1024,
 			verifier_settings: VerifierSettings::default(),
+			max_timestamp_drift: ::verification::DEFAULT_MAX_TIMESTAMP_DRIFT,
 		}
 	}
 }
@@ -79,6 +85,8 @@ pub struct VerifierSettings {
 	pub scale_verifiers: bool,
 	/// Beginning amount of verifiers.
 	pub num_verifiers: usize,
+	/// Number of verification threads to spawn. `None` spawns one per CPU, but no more than 8.
+	pub max_verifiers: Option<usize>,
 }
 
 impl Default for VerifierSettings {
@@ -86,6 +94,7 @@ impl Default for VerifierSettings {
 		VerifierSettings {
 			scale_verifiers: false,
 			num_verifiers: MAX_VERIFIERS,
+			max_verifiers: None,
 		}
 	}
 }
@@ -150,6 +159,7 @@ pub struct VerificationQueue<K: Kind> {
 	ticks_since_adjustment: AtomicUsize,
 	max_queue_size: usize,
 	max_mem_use: usize,
+	max_timestamp_drift: u64,
 	scale_verifiers: bool,
 	verifier_handles: Vec<JoinHandle<()>>,
 	state: Arc<(Mutex<State>, Condvar)>,
@@ -202,6 +212,7 @@ struct Verification<K: Kind> {
 	verifying: Mutex<VecDeque<Verifying<K>>>,
 	verified: Mutex<VecDeque<K::Verified>>,
 	bad: Mutex<HashSet<H256>>,
+	rejected: Mutex<VecDeque<(H256, Option<String>)>>,
 	more_to_verify: SMutex<()>,
 	empty: SMutex<()>,
 	sizes: Sizes,
@@ -216,6 +227,7 @@ impl<K: Kind> VerificationQueue<K> {
 			verifying: Mutex::new(VecDeque::new()),
 			verified: Mutex::new(VecDeque::new()),
 			bad: Mutex::new(HashSet::new()),
+			rejected: Mutex::new(VecDeque::new()),
 			more_to_verify: SMutex::new(()),
 			empty: SMutex::new(()),
 			sizes: Sizes {
@@ -236,7 +248,10 @@ impl<K: Kind> VerificationQueue<K> {
 		let scale_verifiers = config.verifier_settings.scale_verifiers;
 
 		let num_cpus = ::num_cpus::get();
-		let max_verifiers = cmp::min(num_cpus, MAX_VERIFIERS);
+		let max_verifiers = match config.verifier_settings.max_verifiers {
+			Some(max) => cmp::max(1, max),
+			None => cmp::min(num_cpus, MAX_VERIFIERS),
+		};
 		let default_amount = cmp::max(1, cmp::min(max_verifiers, config.verifier_settings.num_verifiers));
 		let state = Arc::new((Mutex::new(State::Work(default_amount)), Condvar::new()));
 		let mut verifier_handles = Vec::with_capacity(max_verifiers);
@@ -282,6 +297,7 @@ impl<K: Kind> VerificationQueue<K> {
 			ticks_since_adjustment: AtomicUsize::new(0),
 			max_queue_size: cmp::max(config.max_queue_size, MIN_QUEUE_LIMIT),
 			max_mem_use: cmp::max(config.max_mem_use, MIN_MEM_LIMIT),
+			max_timestamp_drift: config.max_timestamp_drift,
 			scale_verifiers: scale_verifiers,
 			verifier_handles: verifier_handles,
 			state: state,
@@ -375,22 +391,25 @@ impl<K: Kind> VerificationQueue<K> {
 						// we're next!
 						let mut verified = verification.verified.lock();
 						let mut bad = verification.bad.lock();
-						VerificationQueue::drain_verifying(&mut verifying, &mut verified, &mut bad, &verification.sizes);
+						let mut rejected = verification.rejected.lock();
+						VerificationQueue::drain_verifying(&mut verifying, &mut verified, &mut bad, &mut rejected, &verification.sizes);
 						true
 					} else {
 						false
 					}
 				},
-				Err(_) => {
+				Err(err) => {
 					let mut verifying = verification.verifying.lock();
 					let mut verified = verification.verified.lock();
 					let mut bad = verification.bad.lock();
+					let mut rejected = verification.rejected.lock();
 
 					bad.insert(hash.clone());
+					Self::note_rejected(&mut rejected, hash.clone(), Some(format!("{}", err)));
 					verifying.retain(|e| e.hash != hash);
 
 					if verifying.front().map_or(false, |x| x.output.is_some()) {
-						VerificationQueue::drain_verifying(&mut verifying, &mut verified, &mut bad, &verification.sizes);
+						VerificationQueue::drain_verifying(&mut verifying, &mut verified, &mut bad, &mut rejected, &verification.sizes);
 						true
 					} else {
 						false
@@ -408,6 +427,7 @@ impl<K: Kind> VerificationQueue<K> {
 		verifying: &mut VecDeque<Verifying<K>>,
 		verified: &mut VecDeque<K::Verified>,
 		bad: &mut HashSet<H256>,
+		rejected: &mut VecDeque<(H256, Option<String>)>,
 		sizes: &Sizes,
 	) {
 		let mut removed_size = 0;
@@ -420,6 +440,7 @@ impl<K: Kind> VerificationQueue<K> {
 
 			if bad.contains(&output.parent_hash()) {
 				bad.insert(output.hash());
+				Self::note_rejected(rejected, output.hash(), None);
 			} else {
 				inserted_size += size;
 				verified.push_back(output);
@@ -430,6 +451,20 @@ impl<K: Kind> VerificationQueue<K> {
 		sizes.verified.fetch_add(inserted_size, AtomicOrdering::SeqCst);
 	}
 
+	fn note_rejected(rejected: &mut VecDeque<(H256, Option<String>)>, hash: H256, reason: Option<String>) {
+		if rejected.len() == MAX_REJECTED {
+			rejected.pop_front();
+		}
+		rejected.push_back((hash, reason));
+	}
+
+	/// Take the items the verifiers rejected since the last call, oldest first, with the
+	/// reason. Items rejected because their parent was are returned without one.
+	/// Only the last 64 rejections are kept.
+	pub fn take_rejected(&self) -> Vec<(H256, Option<String>)> {
+		self.verification.rejected.lock().drain(..).collect()
+	}
+
 	/// Clear the queue and stop verification activity.
 	pub fn clear(&self) {
 		let mut unverified = self.verification.unverified.lock();
@@ -486,7 +521,7 @@ impl<K: Kind> VerificationQueue<K> {
 			}
 		}
 
-		match K::create(input, &*self.engine) {
+		match K::create(input, &*self.engine, self.max_timestamp_drift) {
 			Ok(item) => {
 				self.verification.sizes.unverified.fetch_add(item.heap_size_of_children(), AtomicOrdering::SeqCst);
 
@@ -582,27 +617,42 @@ impl<K: Kind> VerificationQueue<K> {
 		result
 	}
 
-	/// Get queue status.
-	pub fn queue_info(&self) -> QueueInfo {
+	// number of items and approximate heap size in bytes of the
+	// unverified, verifying and verified queues.
+	fn stage_sizes(&self) -> ((usize, usize), (usize, usize), (usize, usize)) {
 		use std::mem::size_of;
 
-		let (unverified_len, unverified_bytes) = {
+		let unverified = {
 			let len = self.verification.unverified.lock().len();
 			let size = self.verification.sizes.unverified.load(AtomicOrdering::Acquire);
 
 			(len, size + len * size_of::<K::Unverified>())
 		};
-		let (verifying_len, verifying_bytes) = {
+		let verifying = {
 			let len = self.verification.verifying.lock().len();
 			let size = self.verification.sizes.verifying.load(AtomicOrdering::Acquire);
 			(len, size + len * size_of::<Verifying<K>>())
 		};
-		let (verified_len, verified_bytes) = {
+		let verified = {
 			let len = self.verification.verified.lock().len();
 			let size = self.verification.sizes.verified.load(AtomicOrdering::Acquire);
 			(len, size + len * size_of::<K::Verified>())
 		};
 
+		(unverified, verifying, verified)
+	}
+
+	/// Get the approximate heap memory used by the unverified, verifying
+	/// and verified queues, in bytes.
+	pub fn mem_usage(&self) -> (usize, usize, usize) {
+		let ((_, unverified), (_, verifying), (_, verified)) = self.stage_sizes();
+		(unverified, verifying, verified)
+	}
+
+	/// Get queue status.
+	pub fn queue_info(&self) -> QueueInfo {
+		let ((unverified_len, unverified_bytes), (verifying_len, verifying_bytes), (verified_len, verified_bytes)) = self.stage_sizes();
+
 		QueueInfo {
 			unverified_queue_size: unverified_len,
 			verifying_queue_size: verifying_len,
@@ -620,6 +670,11 @@ impl<K: Kind> VerificationQueue<K> {
 		self.total_difficulty.read().clone()
 	}
 
+	/// Get the number of verification threads, working or not.
+	pub fn verifier_threads(&self) -> usize {
+		self.verifier_handles.len()
+	}
+
 	/// Get the current number of working verifiers.
 	pub fn num_verifiers(&self) -> usize {
 		match *self.state.0.lock() {
@@ -38,6 +38,9 @@ use engines::EthEngine;
 use error::{BlockError, Error};
 use header::{BlockNumber, Header};
 use transaction::{SignedTransaction, UnverifiedTransaction};
+
+/// How far ahead of the local clock block timestamps may be by default, in seconds.
+pub const DEFAULT_MAX_TIMESTAMP_DRIFT: u64 = 15;
 use views::BlockView;
 
 /// Preprocessed block data gathered in `verify_block_unordered` call
@@ -59,13 +62,14 @@ impl HeapSizeOf for PreverifiedBlock {
 }
 
 /// Phase 1 quick block verification. Only does checks that are cheap. Operates on a single block
-pub fn verify_block_basic(header: &Header, bytes: &[u8], engine: &EthEngine) -> Result<(), Error> {
-	verify_header_params(&header, engine, true)?;
+/// Blocks more than `max_drift` seconds ahead of the local clock are temporarily invalid.
+pub fn verify_block_basic(header: &Header, bytes: &[u8], engine: &EthEngine, max_drift: u64) -> Result<(), Error> {
+	verify_header_params(&header, engine, true, max_drift)?;
 	verify_block_integrity(bytes, &header.transactions_root(), &header.uncles_hash())?;
 	engine.verify_block_basic(&header)?;
 	for u in UntrustedRlp::new(bytes).at(2)?.iter().map(|rlp| rlp.as_val::<Header>()) {
 		let u = u?;
-		verify_header_params(&u, engine, false)?;
+		verify_header_params(&u, engine, false, max_drift)?;
 		engine.verify_block_basic(&u)?;
 	}
 
@@ -250,8 +254,9 @@ pub fn verify_block_final(expected: &Header, got: &Header) -> Result<(), Error>
 	Ok(())
 }
 
-/// Check basic header parameters.
-pub fn verify_header_params(header: &Header, engine: &EthEngine, is_full: bool) -> Result<(), Error> {
+/// Check basic header parameters. With `is_full`, headers more than `max_drift` seconds
+/// ahead of the local clock are temporarily invalid.
+pub fn verify_header_params(header: &Header, engine: &EthEngine, is_full: bool, max_drift: u64) -> Result<(), Error> {
 	let expected_seal_fields = engine.seal_fields(header);
 	if header.seal().len() != expected_seal_fields {
 		return Err(From::from(BlockError::InvalidSealArity(
@@ -283,10 +288,9 @@ pub fn verify_header_params(header: &Header, engine: &EthEngine, is_full: bool)
 	}
 
 	if is_full {
-		const ACCEPTABLE_DRIFT_SECS: u64 = 15;
 		let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
-		let max_time = now.as_secs() + ACCEPTABLE_DRIFT_SECS;
-		let invalid_threshold = max_time + ACCEPTABLE_DRIFT_SECS * 9;
+		let max_time = now.as_secs() + max_drift;
+		let invalid_threshold = max_time + DEFAULT_MAX_TIMESTAMP_DRIFT * 9;
 		let timestamp = header.timestamp();
 
 		if timestamp > invalid_threshold {
@@ -479,7 +483,7 @@ mod tests {
 
 	fn basic_test(bytes: &[u8], engine: &EthEngine) -> Result<(), Error> {
 		let header = BlockView::new(bytes).header();
-		verify_block_basic(&header, bytes, engine)
+		verify_block_basic(&header, bytes, engine, DEFAULT_MAX_TIMESTAMP_DRIFT)
 	}
 
 	fn family_test<BC>(bytes: &[u8], engine: &EthEngine, bc: &BC) -> Result<(), Error> where BC: BlockProvider {
@@ -33,6 +33,15 @@ pub enum RestorationStatus {
 		block_chunks_done: u32,
 	},
 	/// Failed restoration.
-	Failed,
+	Failed(RestorationFailure),
+}
+
+/// Reasons for a restoration to fail.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum RestorationFailure {
+	/// A chunk couldn't be restored, e.g. because it was invalid.
+	Error,
+	/// No chunk was restored within the stall timeout.
+	Stalled,
 }
 
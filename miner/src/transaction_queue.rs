@@ -488,6 +488,11 @@ impl TransactionSet {
 			_ => U256::default(),
 		}
 	}
+
+	/// Approximate heap memory used by the transactions in this set, in bytes.
+	fn mem_usage(&self) -> usize {
+		self.by_priority.iter().map(|order| order.mem_usage).sum()
+	}
 }
 
 #[derive(Debug)]
@@ -499,6 +504,26 @@ pub struct TransactionQueueStatus {
 	pub future: usize,
 }
 
+/// Number of transactions evicted from the queue by `TransactionQueue::gc`, by reason.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PoolGcReport {
+	/// Nonce already used by a mined transaction.
+	pub stale: usize,
+	/// Queued for longer than allowed.
+	pub expired: usize,
+	/// Sender can no longer pay for the transaction.
+	pub insufficient_balance: usize,
+	/// Gas price below the queue's current minimal gas price.
+	pub underpriced: usize,
+}
+
+// reason for evicting a transaction in `remove_old_internal`.
+enum Eviction {
+	Expired,
+	InsufficientBalance,
+	Underpriced,
+}
+
 /// Details of account
 pub struct AccountDetails {
 	/// Most recent account nonce
@@ -689,6 +714,11 @@ impl TransactionQueue {
 		self.tx_gas_limit = limit;
 	}
 
+	/// Approximate heap memory used by queued transactions, in bytes.
+	pub fn mem_usage(&self) -> usize {
+		self.current.mem_usage() + self.future.mem_usage()
+	}
+
 	/// Returns current status for this queue
 	pub fn status(&self) -> TransactionQueueStatus {
 		TransactionQueueStatus {
@@ -901,33 +931,56 @@ impl TransactionQueue {
 	pub fn remove_old<F>(&mut self, fetch_account: &F, current_time: QueuingInstant) where
 		F: Fn(&Address) -> AccountDetails,
 	{
+		self.remove_old_internal(fetch_account, current_time, false);
+	}
+
+	/// Like `remove_old`, but also checks every sender's balance regardless of how long its
+	/// transactions were queued and evicts transactions priced below the minimal gas price.
+	/// Local transactions are only removed once their nonce has been used.
+	pub fn gc<F>(&mut self, fetch_account: &F, current_time: QueuingInstant) -> PoolGcReport where
+		F: Fn(&Address) -> AccountDetails,
+	{
+		self.remove_old_internal(fetch_account, current_time, true)
+	}
+
+	fn remove_old_internal<F>(&mut self, fetch_account: &F, current_time: QueuingInstant, forced: bool) -> PoolGcReport where
+		F: Fn(&Address) -> AccountDetails,
+	{
+		let mut report = PoolGcReport::default();
 		let senders = self.current.by_address.keys()
 			.chain(self.future.by_address.keys())
 			.map(|sender| (*sender, fetch_account(sender)))
 			.collect::<HashMap<_, _>>();
 
+		let queued = self.by_hash.len();
 		for (sender, details) in senders.iter() {
 			self.cull(*sender, details.nonce);
 		}
+		report.stale = queued - self.by_hash.len();
 
 		let max_time = self.max_time_in_queue;
 		let balance_check = max_time >> 3;
+		let minimal_gas_price = self.minimal_gas_price;
 		// Clear transactions occupying the queue too long
 		let invalid = self.by_hash.iter()
 			.filter(|&(_, ref tx)| !tx.origin.is_local())
 			.map(|(hash, tx)| (hash, tx, current_time.saturating_sub(tx.insertion_time)))
 			.filter_map(|(hash, tx, time_diff)| {
 				if time_diff > max_time {
-					return Some(*hash);
+					return Some((*hash, Eviction::Expired));
 				}
 
-				if time_diff > balance_check {
-					return match senders.get(&tx.sender()) {
+				if forced || time_diff > balance_check {
+					match senders.get(&tx.sender()) {
 						Some(details) if tx.cost() > details.balance => {
-							Some(*hash)
+							return Some((*hash, Eviction::InsufficientBalance));
 						},
-						_ => None,
-					};
+						_ => {},
+					}
+				}
+
+				if forced && tx.transaction.gas_price < minimal_gas_price {
+					return Some((*hash, Eviction::Underpriced));
 				}
 
 				None
@@ -936,9 +989,16 @@ impl TransactionQueue {
 		let fetch_nonce = |a: &Address| senders.get(a)
 			.expect("We fetch details for all senders from both current and future")
 			.nonce;
-		for hash in invalid {
+		for (hash, eviction) in invalid {
 			self.remove(&hash, &fetch_nonce, RemovalReason::Invalid);
+			match eviction {
+				Eviction::Expired => report.expired += 1,
+				Eviction::InsufficientBalance => report.insufficient_balance += 1,
+				Eviction::Underpriced => report.underpriced += 1,
+			}
 		}
+
+		report
 	}
 
 	/// Penalize transactions from sender of transaction with given hash.
@@ -2813,6 +2873,28 @@ pub mod test {
 		assert_eq!(txq.top_transactions().len(), 2);
 	}
 
+	#[test]
+	fn should_report_evicted_transactions_by_reason() {
+		// given
+		let mut txq = TransactionQueue::default();
+		let (tx1, tx2) = new_tx_pair_default(1.into(), 0.into());
+		let tx3 = new_tx(default_nonce() + U256::one(), 5.into());
+		let next_nonce = |_: &Address|
+			AccountDetails { nonce: default_nonce() + U256::one(), balance: !U256::zero() };
+
+		txq.add(tx1, TransactionOrigin::External, 0, None, &default_tx_provider()).unwrap();
+		txq.add(tx2, TransactionOrigin::External, 0, None, &default_tx_provider()).unwrap();
+		txq.add(tx3.clone(), TransactionOrigin::External, 0, None, &default_tx_provider().with_account_nonce(default_nonce() + U256::one())).unwrap();
+		txq.set_minimal_gas_price(2.into());
+
+		// when
+		let report = txq.gc(&next_nonce, 0);
+
+		// then
+		assert_eq!(report, PoolGcReport { stale: 1, underpriced: 1, ..Default::default() });
+		assert_eq!(txq.top_transactions(), vec![tx3]);
+	}
+
 	#[test]
 	fn should_remove_out_of_date_transactions_occupying_queue() {
 		// given
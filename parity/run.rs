@@ -595,6 +595,9 @@ pub fn execute_impl(cmd: RunCmd, can_restart: bool, logger: Arc<RotatingLogger>)
 	// set network path.
 	net_conf.net_config_path = Some(db_dirs.network_path().to_string_lossy().into_owned());
 
+	let snapshot_period = client_config.snapshot_every_n_blocks.unwrap_or(SNAPSHOT_PERIOD);
+	let snapshot_start_delay = client_config.snapshot_start_delay_blocks;
+
 	// create client service.
 	let service = ClientService::start(
 		client_config,
@@ -854,9 +857,9 @@ pub fn execute_impl(cmd: RunCmd, can_restart: bool, logger: Arc<RotatingLogger>)
 				service.client(),
 				move || is_major_importing(Some(sync.status().state), client.queue_info()),
 				service.io().channel(),
-				SNAPSHOT_PERIOD,
+				snapshot_period,
 				SNAPSHOT_HISTORY,
-			));
+			).with_start_delay(snapshot_start_delay));
 
 			service.add_notify(watcher.clone());
 			Some(watcher)
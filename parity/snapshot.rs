@@ -87,7 +87,7 @@ fn restore_using<R: SnapshotReader>(snapshot: Arc<SnapshotService>, reader: &R,
 
  	info!("Restoring state");
  	for &state_hash in &manifest.state_hashes {
- 		if snapshot.status() == RestorationStatus::Failed {
+ 		if let RestorationStatus::Failed(_) = snapshot.status() {
  			return Err("Restoration failed".into());
  		}
 
@@ -104,7 +104,7 @@ fn restore_using<R: SnapshotReader>(snapshot: Arc<SnapshotService>, reader: &R,
 
 	info!("Restoring blocks");
 	for &block_hash in &manifest.block_hashes {
-		if snapshot.status() == RestorationStatus::Failed {
+		if let RestorationStatus::Failed(_) = snapshot.status() {
 			return Err("Restoration failed".into());
 		}
 
@@ -120,7 +120,7 @@ fn restore_using<R: SnapshotReader>(snapshot: Arc<SnapshotService>, reader: &R,
 
 	match snapshot.status() {
 		RestorationStatus::Ongoing { .. } => Err("Snapshot file is incomplete and missing chunks.".into()),
-		RestorationStatus::Failed => Err("Snapshot restoration failed.".into()),
+		RestorationStatus::Failed(_) => Err("Snapshot restoration failed.".into()),
 		RestorationStatus::Inactive => {
 			info!("Restoration complete.");
 			Ok(())
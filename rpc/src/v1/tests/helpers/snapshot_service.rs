@@ -14,10 +14,11 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
-use ethcore::snapshot::{ManifestData, RestorationStatus, SnapshotService};
+use ethcore::snapshot::{ManifestData, PeerId, RestorationStatus, RestoreParallelism, SnapshotService};
 
 use bytes::Bytes;
 use ethereum_types::H256;
+use hash::keccak;
 use parking_lot::Mutex;
 
 /// Mocked snapshot service (used for sync info extensions).
@@ -47,6 +48,12 @@ impl SnapshotService for TestSnapshotService {
 	fn status(&self) -> RestorationStatus { self.status.lock().clone() }
 	fn begin_restore(&self, _manifest: ManifestData) { }
 	fn abort_restore(&self) { }
+	fn can_accept_chunk(&self) -> bool { true }
+	fn recommended_parallelism(&self) -> RestoreParallelism { RestoreParallelism { state: 1, block: 1 } }
+	fn chunk_hash(&self, chunk: &[u8]) -> H256 { keccak(chunk) }
 	fn restore_state_chunk(&self, _hash: H256, _chunk: Bytes) { }
+	fn restore_state_chunk_from(&self, _peer: PeerId, _hash: H256, _chunk: Bytes) { }
 	fn restore_block_chunk(&self, _hash: H256, _chunk: Bytes) { }
+	fn bad_chunk_peers(&self) -> Vec<PeerId> { Vec::new() }
+	fn peer_disconnected(&self, _peer: PeerId) { }
 }
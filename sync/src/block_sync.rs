@@ -510,6 +510,10 @@ impl BlockDownloader {
 					trace!(target: "sync", "Block already queued {:?}", h);
 					self.block_imported(&h, number, &parent);
 				},
+				Err(BlockImportError::Import(ImportError::HeldBack)) => {
+					trace!(target: "sync", "Block held back until its timestamp {:?}", h);
+					self.block_imported(&h, number, &parent);
+				},
 				Ok(_) => {
 					trace!(target: "sync", "Block queued {:?}", h);
 					imported.insert(h.clone());
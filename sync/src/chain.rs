@@ -919,6 +919,9 @@ impl ChainSync {
 			Err(BlockImportError::Import(ImportError::AlreadyQueued)) => {
 				trace!(target: "sync", "New block already queued {:?}", h);
 			},
+			Err(BlockImportError::Import(ImportError::HeldBack)) => {
+				trace!(target: "sync", "New block held back until its timestamp {:?}", h);
+			},
 			Ok(_) => {
 				// abort current download of the same block
 				self.complete_sync(io);
@@ -1082,12 +1085,12 @@ impl ChainSync {
 		// check service status
 		let status = io.snapshot_service().status();
 		match status {
-			RestorationStatus::Inactive | RestorationStatus::Failed => {
+			RestorationStatus::Inactive | RestorationStatus::Failed(_) => {
 				trace!(target: "sync", "{}: Snapshot restoration aborted", peer_id);
 				self.state = SyncState::WaitingPeers;
 
 				// only note bad if restoration failed.
-				if let (Some(hash), RestorationStatus::Failed) = (self.snapshot.snapshot_hash(), status) {
+				if let (Some(hash), RestorationStatus::Failed(_)) = (self.snapshot.snapshot_hash(), status) {
 					trace!(target: "sync", "Noting snapshot hash {} as bad", hash);
 					self.snapshot.note_bad(hash);
 				}
@@ -1101,15 +1104,23 @@ impl ChainSync {
 			},
 		}
 
+		// leave the chunk to be requested again once the service has caught up.
+		if !io.snapshot_service().can_accept_chunk() {
+			debug!(target: "sync", "{}: Snapshot service is busy, dropping chunk", peer_id);
+			self.continue_sync(io);
+			return Ok(());
+		}
+
 		let snapshot_data: Bytes = r.val_at(0)?;
-		match self.snapshot.validate_chunk(&snapshot_data) {
+		let hash = io.snapshot_service().chunk_hash(&snapshot_data);
+		match self.snapshot.validate_chunk(hash) {
 			Ok(ChunkType::Block(hash)) => {
 				trace!(target: "sync", "{}: Processing block chunk", peer_id);
 				io.snapshot_service().restore_block_chunk(hash, snapshot_data);
 			}
 			Ok(ChunkType::State(hash)) => {
 				trace!(target: "sync", "{}: Processing state chunk", peer_id);
-				io.snapshot_service().restore_state_chunk(hash, snapshot_data);
+				io.snapshot_service().restore_state_chunk_from(peer_id, hash, snapshot_data);
 			}
 			Err(()) => {
 				trace!(target: "sync", "{}: Got bad snapshot chunk", peer_id);
@@ -1134,6 +1145,7 @@ impl ChainSync {
 	pub fn on_peer_aborting(&mut self, io: &mut SyncIo, peer: PeerId) {
 		trace!(target: "sync", "== Disconnecting {}: {}", peer, io.peer_info(peer));
 		self.handshaking_peers.remove(&peer);
+		io.snapshot_service().peer_disconnected(peer);
 		if self.peers.contains_key(&peer) {
 			debug!(target: "sync", "Disconnected {}", peer);
 			self.clear_peer_download(peer);
@@ -1256,6 +1268,12 @@ impl ChainSync {
 							return;
 						}
 					}
+					// requesting more would only have the chunks dropped on arrival.
+					if !io.snapshot_service().can_accept_chunk() {
+						debug!(target: "sync", "Snapshot service saturated, pausing chunk requests");
+						self.state = SyncState::SnapshotWaiting;
+						return;
+					}
 					if peer_snapshot_hash.is_some() && peer_snapshot_hash == self.snapshot.snapshot_hash() {
 						self.request_snapshot_data(io, peer_id);
 					}
@@ -1797,6 +1815,13 @@ impl ChainSync {
 				aborting.push(*peer_id);
 			}
 		}
+		for peer_id in io.snapshot_service().bad_chunk_peers() {
+			if self.peers.contains_key(&peer_id) && !aborting.contains(&peer_id) {
+				trace!(target:"sync", "Bad snapshot chunk from {}", peer_id);
+				io.disconnect_peer(peer_id);
+				aborting.push(peer_id);
+			}
+		}
 		for p in aborting {
 			self.on_peer_aborting(io, p);
 		}
@@ -1823,13 +1848,13 @@ impl ChainSync {
 					self.continue_sync(io);
 				},
 				RestorationStatus::Ongoing { state_chunks_done, block_chunks_done, .. } => {
-					if !self.snapshot.is_complete() && self.snapshot.done_chunks() - (state_chunks_done + block_chunks_done) as usize <= MAX_SNAPSHOT_CHUNKS_DOWNLOAD_AHEAD {
+					if !self.snapshot.is_complete() && self.snapshot.done_chunks() - (state_chunks_done + block_chunks_done) as usize <= MAX_SNAPSHOT_CHUNKS_DOWNLOAD_AHEAD && io.snapshot_service().can_accept_chunk() {
 						trace!(target:"sync", "Resuming snapshot sync");
 						self.state = SyncState::SnapshotData;
 						self.continue_sync(io);
 					}
 				},
-				RestorationStatus::Failed => {
+				RestorationStatus::Failed(_) => {
 					trace!(target: "sync", "Snapshot restoration aborted");
 					self.state = SyncState::WaitingPeers;
 					self.snapshot.clear();
@@ -2186,7 +2211,7 @@ impl ChainSync {
 	/// Called when peer sends us new consensus packet
 	fn on_consensus_packet(io: &mut SyncIo, peer_id: PeerId, r: &UntrustedRlp) -> Result<(), PacketDecodeError> {
 		trace!(target: "sync", "Received consensus packet from {:?}", peer_id);
-		io.chain().queue_consensus_message(r.as_raw().to_vec());
+		io.chain().queue_consensus_message(r.as_raw().to_vec(), peer_id);
 		Ok(())
 	}
 
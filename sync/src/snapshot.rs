@@ -14,7 +14,6 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
-use hash::keccak;
 use ethereum_types::H256;
 use std::collections::HashSet;
 use ethcore::snapshot::ManifestData;
@@ -69,9 +68,8 @@ impl Snapshot {
 		self.snapshot_hash = Some(hash.clone());
 	}
 
-	/// Validate chunk and mark it as downloaded
-	pub fn validate_chunk(&mut self, chunk: &[u8]) -> Result<ChunkType, ()> {
-		let hash = keccak(chunk);
+	/// Validate a chunk by its hash and mark it as downloaded
+	pub fn validate_chunk(&mut self, hash: H256) -> Result<ChunkType, ()> {
 		if self.completed_chunks.contains(&hash) {
 			trace!(target: "sync", "Ignored proccessed chunk: {:x}", hash);
 			return Err(());
@@ -181,7 +179,7 @@ mod test {
 		let (manifest, mhash, state_chunks, block_chunks) = test_manifest();
 		snapshot.reset_to(&manifest, &mhash);
 		assert_eq!(snapshot.done_chunks(), 0);
-		assert!(snapshot.validate_chunk(&H256::random().to_vec()).is_err());
+		assert!(snapshot.validate_chunk(keccak(&H256::random().to_vec())).is_err());
 
 		let requested: Vec<H256> = (0..40).map(|_| snapshot.needed_chunk().unwrap()).collect();
 		assert!(snapshot.needed_chunk().is_none());
@@ -189,23 +187,23 @@ mod test {
 		assert_eq!(&requested[20..40], &manifest.block_hashes[..]);
 		assert_eq!(snapshot.downloading_chunks.len(), 40);
 
-		assert_eq!(snapshot.validate_chunk(&state_chunks[4]), Ok(ChunkType::State(manifest.state_hashes[4].clone())));
+		assert_eq!(snapshot.validate_chunk(keccak(&state_chunks[4])), Ok(ChunkType::State(manifest.state_hashes[4].clone())));
 		assert_eq!(snapshot.completed_chunks.len(), 1);
 		assert_eq!(snapshot.downloading_chunks.len(), 39);
 
-		assert_eq!(snapshot.validate_chunk(&block_chunks[10]), Ok(ChunkType::Block(manifest.block_hashes[10].clone())));
+		assert_eq!(snapshot.validate_chunk(keccak(&block_chunks[10])), Ok(ChunkType::Block(manifest.block_hashes[10].clone())));
 		assert_eq!(snapshot.completed_chunks.len(), 2);
 		assert_eq!(snapshot.downloading_chunks.len(), 38);
 
 		for (i, data) in state_chunks.iter().enumerate() {
 			if i != 4 {
-				assert!(snapshot.validate_chunk(data).is_ok());
+				assert!(snapshot.validate_chunk(keccak(data)).is_ok());
 			}
 		}
 
 		for (i, data) in block_chunks.iter().enumerate() {
 			if i != 10 {
-				assert!(snapshot.validate_chunk(data).is_ok());
+				assert!(snapshot.validate_chunk(keccak(data)).is_ok());
 			}
 		}
 
@@ -20,10 +20,11 @@ use hash::keccak;
 use ethereum_types::H256;
 use parking_lot::Mutex;
 use bytes::Bytes;
-use ethcore::snapshot::{SnapshotService, ManifestData, RestorationStatus};
+use ethcore::snapshot::{SnapshotService, ManifestData, PeerId, RestorationStatus, RestoreParallelism};
 use ethcore::header::BlockNumber;
 use ethcore::client::{EachBlockWith};
 use super::helpers::*;
+use chain::SyncState;
 use SyncConfig;
 
 pub struct TestSnapshotService {
@@ -33,6 +34,7 @@ pub struct TestSnapshotService {
 	restoration_manifest: Mutex<Option<ManifestData>>,
 	state_restoration_chunks: Mutex<HashMap<H256, Bytes>>,
 	block_restoration_chunks: Mutex<HashMap<H256, Bytes>>,
+	saturated: Mutex<bool>,
 }
 
 impl TestSnapshotService {
@@ -43,6 +45,7 @@ impl TestSnapshotService {
 			restoration_manifest: Mutex::new(None),
 			state_restoration_chunks: Mutex::new(HashMap::new()),
 			block_restoration_chunks: Mutex::new(HashMap::new()),
+			saturated: Mutex::new(false),
 		}
 	}
 
@@ -67,6 +70,7 @@ impl TestSnapshotService {
 			restoration_manifest: Mutex::new(None),
 			state_restoration_chunks: Mutex::new(HashMap::new()),
 			block_restoration_chunks: Mutex::new(HashMap::new()),
+			saturated: Mutex::new(false),
 		}
 	}
 }
@@ -110,17 +114,40 @@ impl SnapshotService for TestSnapshotService {
 		self.block_restoration_chunks.lock().clear();
 	}
 
+	fn can_accept_chunk(&self) -> bool {
+		!*self.saturated.lock()
+	}
+
+	fn recommended_parallelism(&self) -> RestoreParallelism {
+		RestoreParallelism { state: 1, block: 1 }
+	}
+
+	fn chunk_hash(&self, chunk: &[u8]) -> H256 {
+		keccak(chunk)
+	}
+
 	fn restore_state_chunk(&self, hash: H256, chunk: Bytes) {
 		if self.restoration_manifest.lock().as_ref().map_or(false, |m| m.state_hashes.iter().any(|h| h == &hash)) {
 			self.state_restoration_chunks.lock().insert(hash, chunk);
 		}
 	}
 
+	fn restore_state_chunk_from(&self, _peer: PeerId, hash: H256, chunk: Bytes) {
+		self.restore_state_chunk(hash, chunk)
+	}
+
 	fn restore_block_chunk(&self, hash: H256, chunk: Bytes) {
 		if self.restoration_manifest.lock().as_ref().map_or(false, |m| m.block_hashes.iter().any(|h| h == &hash)) {
 			self.block_restoration_chunks.lock().insert(hash, chunk);
 		}
 	}
+
+	fn bad_chunk_peers(&self) -> Vec<PeerId> {
+		Vec::new()
+	}
+
+	fn peer_disconnected(&self, _peer: PeerId) {
+	}
 }
 
 #[test]
@@ -139,3 +166,25 @@ fn snapshot_sync() {
 	assert_eq!(net.peer(4).snapshot_service.block_restoration_chunks.lock().len(), net.peer(0).snapshot_service.manifest.as_ref().unwrap().block_hashes.len());
 }
 
+#[test]
+fn snapshot_sync_pauses_while_service_saturated() {
+	::env_logger::init().ok();
+	let mut config = SyncConfig::default();
+	config.warp_sync = true;
+	let mut net = TestNet::new_with_config(5, config);
+	let snapshot_service = Arc::new(TestSnapshotService::new_with_snapshot(16, H256::new(), 500000));
+	for i in 0..4 {
+		net.peer_mut(i).snapshot_service = snapshot_service.clone();
+		net.peer(i).chain.add_blocks(1, EachBlockWith::Nothing);
+	}
+	*net.peer(4).snapshot_service.saturated.lock() = true;
+	net.sync_steps(50);
+	assert_eq!(net.peer(4).sync.read().status().state, SyncState::SnapshotWaiting);
+	assert!(net.peer(4).snapshot_service.state_restoration_chunks.lock().is_empty());
+	assert!(net.peer(4).snapshot_service.block_restoration_chunks.lock().is_empty());
+
+	*net.peer(4).snapshot_service.saturated.lock() = false;
+	net.sync_steps(50);
+	assert_eq!(net.peer(4).snapshot_service.state_restoration_chunks.lock().len(), net.peer(0).snapshot_service.manifest.as_ref().unwrap().state_hashes.len());
+	assert_eq!(net.peer(4).snapshot_service.block_restoration_chunks.lock().len(), net.peer(0).snapshot_service.manifest.as_ref().unwrap().block_hashes.len());
+}
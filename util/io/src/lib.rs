@@ -80,6 +80,8 @@ pub enum IoError {
 	Mio(::std::io::Error),
 	/// Error concerning the Rust standard library's IO subsystem.
 	StdIo(::std::io::Error),
+	/// The handler already has a timer registered with this token.
+	TimerInUse(TimerToken),
 }
 
 impl fmt::Display for IoError {
@@ -89,6 +91,7 @@ impl fmt::Display for IoError {
 		match *self {
 			IoError::Mio(ref std_err) => std_err.fmt(f),
 			IoError::StdIo(ref std_err) => std_err.fmt(f),
+			IoError::TimerInUse(token) => write!(f, "Timer token {} is already in use", token),
 		}
 	}
 }
@@ -141,6 +144,7 @@ pub use service::IoContext;
 pub use service::IoService;
 pub use service::IoChannel;
 pub use service::IoManager;
+pub use service::TimerInfo;
 pub use service::TOKENS_PER_HANDLER;
 
 #[cfg(test)]
@@ -175,4 +179,40 @@ mod tests {
 		let service = IoService::<MyMessage>::start().expect("Error creating network service");
 		service.register_handler(Arc::new(MyHandler)).unwrap();
 	}
+
+	#[test]
+	fn lists_registered_timers() {
+		use std::thread;
+		use std::time::Duration;
+
+		let service = IoService::<MyMessage>::start().expect("Error creating network service");
+		service.register_handler(Arc::new(MyHandler)).unwrap();
+		thread::sleep(Duration::from_millis(100));
+
+		let timers = service.timers();
+		assert_eq!(timers.len(), 1);
+		assert_eq!(timers[0].token, 0);
+		assert_eq!(timers[0].interval, Duration::from_millis(1000));
+		assert!(!timers[0].once);
+
+		service.stop();
+		assert!(service.timers().is_empty());
+	}
+
+	#[test]
+	fn rejects_timer_token_in_use() {
+		use std::thread;
+		use std::time::Duration;
+
+		let service = IoService::<MyMessage>::start().expect("Error creating network service");
+		service.register_handler(Arc::new(MyHandler)).unwrap();
+		thread::sleep(Duration::from_millis(100));
+
+		let io = IoContext::new(service.channel(), 0);
+		match io.register_unique_timer(0, 1000) {
+			Err(IoError::TimerInUse(0)) => {},
+			other => panic!("expected token 0 to be in use, got {:?}", other),
+		}
+		assert!(io.register_unique_timer(1, 1000).is_ok());
+	}
 }
@@ -26,7 +26,7 @@ use {IoError, IoHandler};
 use worker::{Worker, Work, WorkType};
 use parking_lot::{RwLock, Mutex};
 use std::sync::{Condvar as SCondvar, Mutex as SMutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 /// Timer ID
 pub type TimerToken = usize;
@@ -103,6 +103,17 @@ impl<Message> IoContext<Message> where Message: Send + Clone + Sync + 'static {
 		Ok(())
 	}
 
+	/// Register a new recurring IO timer like `register_timer`, but fail with `IoError::TimerInUse`
+	/// instead of replacing a timer this handler already registered with the same token.
+	/// Tokens are per handler, so other handlers' timers never collide. Registrations which
+	/// the event loop hasn't processed yet aren't detected.
+	pub fn register_unique_timer(&self, token: TimerToken, ms: u64) -> Result<(), IoError> {
+		if self.channel.has_timer(token + self.handler * TOKENS_PER_HANDLER) {
+			return Err(IoError::TimerInUse(token));
+		}
+		self.register_timer(token, ms)
+	}
+
 	/// Register a new IO timer once. 'IoHandler::timeout' will be called with the token.
 	pub fn register_timer_once(&self, token: TimerToken, ms: u64) -> Result<(), IoError> {
 		self.channel.send_io(IoMessage::AddTimer {
@@ -176,11 +187,30 @@ struct UserTimer {
 	delay: u64,
 	timeout: Timeout,
 	once: bool,
+	// when the timer was last (re-)armed.
+	armed_at: Instant,
+}
+
+/// A timer registered with the IO service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimerInfo {
+	/// Handler the timer belongs to.
+	pub handler_id: HandlerId,
+	/// Token the handler registered the timer with.
+	pub token: TimerToken,
+	/// Delay between firings.
+	pub interval: Duration,
+	/// Whether the timer fires only once.
+	pub once: bool,
+	/// Estimate of when the timer fires next.
+	pub next_fire: Instant,
 }
 
+type Timers = Arc<RwLock<HashMap<HandlerId, UserTimer>>>;
+
 /// Root IO handler. Manages user handlers, messages and IO timers.
 pub struct IoManager<Message> where Message: Send + Sync {
-	timers: Arc<RwLock<HashMap<HandlerId, UserTimer>>>,
+	timers: Timers,
 	handlers: Arc<RwLock<Slab<Arc<IoHandler<Message>>, HandlerId>>>,
 	workers: Vec<Worker>,
 	worker_channel: chase_lev::Worker<Work<Message>>,
@@ -191,24 +221,26 @@ impl<Message> IoManager<Message> where Message: Send + Sync + Clone + 'static {
 	/// Creates a new instance and registers it with the event loop.
 	pub fn start(
 		event_loop: &mut EventLoop<IoManager<Message>>,
-		handlers: Arc<RwLock<Slab<Arc<IoHandler<Message>>, HandlerId>>>
+		handlers: Arc<RwLock<Slab<Arc<IoHandler<Message>>, HandlerId>>>,
+		timers: Timers,
 	) -> Result<(), IoError> {
 		let (worker, stealer) = chase_lev::deque();
 		let num_workers = 4;
+		let weak_timers = Arc::downgrade(&timers);
 		let work_ready_mutex =  Arc::new(SMutex::new(()));
 		let work_ready = Arc::new(SCondvar::new());
 		let workers = (0..num_workers).map(|i|
 			Worker::new(
 				i,
 				stealer.clone(),
-				IoChannel::new(event_loop.channel(), Arc::downgrade(&handlers)),
+				IoChannel::new(event_loop.channel(), Arc::downgrade(&handlers), weak_timers.clone()),
 				work_ready.clone(),
 				work_ready_mutex.clone(),
 			)
 		).collect();
 
 		let mut io = IoManager {
-			timers: Arc::new(RwLock::new(HashMap::new())),
+			timers: timers,
 			handlers: handlers,
 			worker_channel: worker,
 			workers: workers,
@@ -249,10 +281,14 @@ impl<Message> Handler for IoManager<Message> where Message: Send + Clone + Sync
 			let maybe_timer = self.timers.read().get(&token.0).cloned();
 			if let Some(timer) = maybe_timer {
 				if timer.once {
-					self.timers.write().remove(&token_id);
+					self.timers.write().remove(&token.0);
 					event_loop.clear_timeout(&timer.timeout);
 				} else {
-					event_loop.timeout(token, Duration::from_millis(timer.delay)).expect("Error re-registering user timer");
+					let timeout = event_loop.timeout(token, Duration::from_millis(timer.delay)).expect("Error re-registering user timer");
+					if let Some(timer) = self.timers.write().get_mut(&token.0) {
+						timer.timeout = timeout;
+						timer.armed_at = Instant::now();
+					}
 				}
 				self.worker_channel.push(Work { work_type: WorkType::Timeout, token: token_id, handler: handler.clone(), handler_id: handler_index });
 				self.work_ready.notify_all();
@@ -284,7 +320,14 @@ impl<Message> Handler for IoManager<Message> where Message: Send + Clone + Sync
 			IoMessage::AddTimer { handler_id, token, delay, once } => {
 				let timer_id = token + handler_id * TOKENS_PER_HANDLER;
 				let timeout = event_loop.timeout(Token(timer_id), Duration::from_millis(delay)).expect("Error registering user timer");
-				self.timers.write().insert(timer_id, UserTimer { delay: delay, timeout: timeout, once: once });
+				let replaced = self.timers.write().insert(timer_id, UserTimer { delay: delay, timeout: timeout, once: once, armed_at: Instant::now() });
+				if let Some(replaced) = replaced {
+					// the old timeout would otherwise keep firing alongside the new one.
+					event_loop.clear_timeout(&replaced.timeout);
+					if !replaced.once && !once {
+						warn!(target: "io", "Handler {} registered timer {} twice; replacing the first one", handler_id, token);
+					}
+				}
 			},
 			IoMessage::RemoveTimer { handler_id, token } => {
 				let timer_id = token + handler_id * TOKENS_PER_HANDLER;
@@ -337,7 +380,7 @@ enum Handlers<Message> where Message: Send + Clone {
 pub struct IoChannel<Message> where Message: Send + Clone{
 	channel: Option<Sender<IoMessage<Message>>>,
 	handlers: Handlers<Message>,
-
+	timers: Weak<RwLock<HashMap<HandlerId, UserTimer>>>,
 }
 
 impl<Message> Clone for IoChannel<Message> where Message: Send + Clone + Sync + 'static {
@@ -345,6 +388,7 @@ impl<Message> Clone for IoChannel<Message> where Message: Send + Clone + Sync +
 		IoChannel {
 			channel: self.channel.clone(),
 			handlers: self.handlers.clone(),
+			timers: self.timers.clone(),
 		}
 	}
 }
@@ -393,6 +437,7 @@ impl<Message> IoChannel<Message> where Message: Send + Clone + Sync + 'static {
 		IoChannel {
 			channel: None,
 			handlers: Handlers::SharedCollection(Weak::default()),
+			timers: Weak::new(),
 		}
 	}
 
@@ -401,14 +446,21 @@ impl<Message> IoChannel<Message> where Message: Send + Clone + Sync + 'static {
 		IoChannel {
 			channel: None,
 			handlers: Handlers::Single(handler),
+			timers: Weak::new(),
 		}
 	}
-	fn new(channel: Sender<IoMessage<Message>>, handlers: Weak<RwLock<Slab<Arc<IoHandler<Message>>, HandlerId>>>) -> IoChannel<Message> {
+	fn new(channel: Sender<IoMessage<Message>>, handlers: Weak<RwLock<Slab<Arc<IoHandler<Message>>, HandlerId>>>, timers: Weak<RwLock<HashMap<HandlerId, UserTimer>>>) -> IoChannel<Message> {
 		IoChannel {
 			channel: Some(channel),
 			handlers: Handlers::SharedCollection(handlers),
+			timers: timers,
 		}
 	}
+
+	// whether the event loop has a timer with the given id.
+	fn has_timer(&self, timer_id: usize) -> bool {
+		self.timers.upgrade().map_or(false, |timers| timers.read().contains_key(&timer_id))
+	}
 }
 
 /// General IO Service. Starts an event loop and dispatches IO requests.
@@ -417,6 +469,7 @@ pub struct IoService<Message> where Message: Send + Sync + Clone + 'static {
 	thread: Mutex<Option<JoinHandle<()>>>,
 	host_channel: Mutex<Sender<IoMessage<Message>>>,
 	handlers: Arc<RwLock<Slab<Arc<IoHandler<Message>>, HandlerId>>>,
+	timers: Timers,
 }
 
 impl<Message> IoService<Message> where Message: Send + Sync + Clone + 'static {
@@ -427,29 +480,61 @@ impl<Message> IoService<Message> where Message: Send + Sync + Clone + 'static {
 		let mut event_loop = config.build().expect("Error creating event loop");
 		let channel = event_loop.channel();
 		let handlers = Arc::new(RwLock::new(Slab::new(MAX_HANDLERS)));
+		let timers = Arc::new(RwLock::new(HashMap::new()));
 		let h = handlers.clone();
+		let t = timers.clone();
 		let thread = thread::spawn(move || {
-			IoManager::<Message>::start(&mut event_loop, h).expect("Error starting IO service");
+			IoManager::<Message>::start(&mut event_loop, h, t).expect("Error starting IO service");
 		});
 		Ok(IoService {
 			thread: Mutex::new(Some(thread)),
 			host_channel: Mutex::new(channel),
 			handlers: handlers,
+			timers: timers,
 		})
 	}
 
 	pub fn stop(&self) {
+		self.try_stop().unwrap_or_else(|e| warn!("Error on IO service shutdown: {:?}", e));
+	}
+
+	/// Stop the service like `stop`, but report a failure to signal the event loop.
+	/// Does nothing if the service was stopped before.
+	pub fn try_stop(&self) -> Result<(), IoError> {
 		trace!(target: "shutdown", "[IoService] Closing...");
 		// Clear handlers so that shared pointers are not stuck on stack
 		// in Channel::send_sync
 		self.handlers.write().clear();
-		self.host_channel.lock().send(IoMessage::Shutdown).unwrap_or_else(|e| warn!("Error on IO service shutdown: {:?}", e));
-		if let Some(thread) = self.thread.lock().take() {
-			thread.join().unwrap_or_else(|e| {
-				debug!(target: "shutdown", "Error joining IO service event loop thread: {:?}", e);
-			});
-		}
+		// the event loop is gone already if the service was stopped before.
+		let result = match self.thread.lock().take() {
+			Some(thread) => {
+				let sent = self.host_channel.lock().send(IoMessage::Shutdown).map_err(IoError::from);
+				thread.join().unwrap_or_else(|e| {
+					debug!(target: "shutdown", "Error joining IO service event loop thread: {:?}", e);
+				});
+				sent
+			},
+			None => Ok(()),
+		};
+		self.timers.write().clear();
 		trace!(target: "shutdown", "[IoService] Closed.");
+		result
+	}
+
+	/// List the registered timers of all handlers, ordered by handler and token.
+	pub fn timers(&self) -> Vec<TimerInfo> {
+		let mut timers: Vec<_> = self.timers.read().iter().map(|(&timer_id, timer)| {
+			let interval = Duration::from_millis(timer.delay);
+			TimerInfo {
+				handler_id: timer_id / TOKENS_PER_HANDLER,
+				token: timer_id % TOKENS_PER_HANDLER,
+				interval: interval,
+				once: timer.once,
+				next_fire: timer.armed_at + interval,
+			}
+		}).collect();
+		timers.sort_by_key(|timer| (timer.handler_id, timer.token));
+		timers
 	}
 
 	/// Regiter an IO handler with the event loop.
@@ -468,7 +553,7 @@ impl<Message> IoService<Message> where Message: Send + Sync + Clone + 'static {
 
 	/// Create a new message channel
 	pub fn channel(&self) -> IoChannel<Message> {
-		IoChannel::new(self.host_channel.lock().clone(), Arc::downgrade(&self.handlers))
+		IoChannel::new(self.host_channel.lock().clone(), Arc::downgrade(&self.handlers), Arc::downgrade(&self.timers))
 	}
 }
 
@@ -38,6 +38,8 @@ use rocksdb::{
 	DB, Writable, WriteBatch, WriteOptions, IteratorMode, DBIterator,
 	Options, BlockBasedOptions, Direction, Cache, Column, ReadOptions
 };
+
+pub use rocksdb::MergeOperands;
 use interleaved_ordered::{interleave_ordered, InterleaveOrdered};
 
 use elastic_array::ElasticArray32;
@@ -149,6 +151,19 @@ impl CompactionProfile {
 	}
 }
 
+/// Merge function of a column: computes the new value of a key from its
+/// existing value, if any, and the operands merged into it since.
+pub type MergeFn = fn(&[u8], Option<&[u8]>, &mut MergeOperands) -> Vec<u8>;
+
+/// Merge operator of a column, used by `Database::merge`.
+#[derive(Clone)]
+pub struct MergeOperator {
+	/// Name of the operator. Must stay the same as long as the column holds values merged with it.
+	pub name: String,
+	/// The merge function.
+	pub merge: MergeFn,
+}
+
 /// Database configuration
 #[derive(Clone)]
 pub struct DatabaseConfig {
@@ -162,6 +177,8 @@ pub struct DatabaseConfig {
 	pub columns: Option<u32>,
 	/// Should we keep WAL enabled?
 	pub wal: bool,
+	/// Merge operators of columns, by column index.
+	pub merge_operators: HashMap<u32, MergeOperator>,
 }
 
 impl DatabaseConfig {
@@ -190,6 +207,7 @@ impl Default for DatabaseConfig {
 			compaction: CompactionProfile::default(),
 			columns: None,
 			wal: true,
+			merge_operators: HashMap::new(),
 		}
 	}
 }
@@ -270,6 +288,15 @@ fn is_corrupted(s: &str) -> bool {
 	s.starts_with("Corruption:") || s.starts_with("Invalid argument: You have to open all column families")
 }
 
+/// Whether a database error is due to corrupt contents, as opposed to e.g. another
+/// process holding the database lock.
+pub fn is_corruption(e: &kvdb::Error) -> bool {
+	match *e.kind() {
+		kvdb::ErrorKind::Msg(ref s) => is_corrupted(s),
+		_ => false,
+	}
+}
+
 impl Database {
 	const CORRUPTION_FILE_NAME: &'static str = "CORRUPTED";
 
@@ -278,6 +305,13 @@ impl Database {
 		Database::open(&DatabaseConfig::default(), path)
 	}
 
+	/// Repair the database at the given path, salvaging as much of it as possible.
+	/// The database must not be open.
+	pub fn repair(path: &str) -> Result<()> {
+		DB::repair(&Options::new(), path)?;
+		Ok(())
+	}
+
 	/// Open database file. Creates if it does not exist.
 	pub fn open(config: &DatabaseConfig, path: &str) -> Result<Database> {
 		let mut opts = Options::new();
@@ -316,8 +350,12 @@ impl Database {
 		let cfnames: Vec<_> = (0..columns).map(|c| format!("col{}", c)).collect();
 		let cfnames: Vec<&str> = cfnames.iter().map(|n| n as &str).collect();
 
-		for _ in 0 .. config.columns.unwrap_or(0) {
-			cf_options.push(col_config(&config, &block_opts)?);
+		for c in 0 .. config.columns.unwrap_or(0) {
+			let mut col_opts = col_config(&config, &block_opts)?;
+			if let Some(operator) = config.merge_operators.get(&c) {
+				col_opts.add_merge_operator(&operator.name, operator.merge);
+			}
+			cf_options.push(col_opts);
 		}
 
 		let mut write_opts = WriteOptions::new();
@@ -497,6 +535,39 @@ impl Database {
 		}
 	}
 
+	/// Merge `operand` into the value of `key` with the column's merge operator.
+	/// Like `write`, this goes to the database directly. A buffered change to the key is
+	/// written along with it, so that the operand is merged into the buffered value.
+	/// Fails for columns without a merge operator.
+	pub fn merge(&self, col: Option<u32>, key: &[u8], operand: &[u8]) -> Result<()> {
+		// a flush in progress would overwrite the merged value with its buffered one.
+		let _flushing = self.flushing_lock.lock();
+		match *self.db.read() {
+			Some(DBAndColumns { ref db, ref cfs }) => {
+				let mut ekey = ElasticArray32::new();
+				ekey.append_slice(key);
+				let buffered = self.overlay.write()[Self::to_overlay_column(col)].remove(&ekey);
+
+				let batch = WriteBatch::new();
+				match buffered {
+					Some(KeyState::Insert(value)) => {
+						col.map_or_else(|| batch.put(key, &value), |c| batch.put_cf(cfs[c as usize], key, &value))?
+					},
+					Some(KeyState::Delete) => {
+						col.map_or_else(|| batch.delete(key), |c| batch.delete_cf(cfs[c as usize], key))?
+					},
+					None => {},
+				}
+				col.map_or_else(|| batch.merge(key, operand), |c| batch.merge_cf(cfs[c as usize], key, operand))?;
+
+				check_for_corruption(
+					&self.path,
+					db.write_opt(batch, &self.write_opts)).map_err(Into::into)
+			},
+			None => Err("Database is closed".into())
+		}
+	}
+
 	/// Get value by key.
 	pub fn get(&self, col: Option<u32>, key: &[u8]) -> Result<Option<DBValue>> {
 		match *self.db.read() {
@@ -857,4 +928,59 @@ mod tests {
 
 		assert_eq!(db.get(None, b"foo").unwrap().unwrap().as_ref(), b"baz");
 	}
+
+	fn add_counters(_key: &[u8], existing: Option<&[u8]>, operands: &mut MergeOperands) -> Vec<u8> {
+		let mut total = existing.map_or(0, |value| value[0]);
+		for operand in operands {
+			total += operand[0];
+		}
+		vec![total]
+	}
+
+	#[test]
+	fn merges_with_column_operator() {
+		let tempdir = TempDir::new("").unwrap();
+		let mut config = DatabaseConfig::with_columns(Some(2));
+		config.merge_operators.insert(1, MergeOperator { name: "add_counters".into(), merge: add_counters });
+		let db = Database::open(&config, tempdir.path().to_str().unwrap()).unwrap();
+
+		let mut batch = db.transaction();
+		batch.put(Some(1), b"count", &[1]);
+		db.write_buffered(batch);
+
+		db.merge(Some(1), b"count", &[2]).unwrap();
+		db.merge(Some(1), b"count", &[3]).unwrap();
+		db.merge(Some(1), b"other", &[4]).unwrap();
+		assert_eq!(db.get(Some(1), b"count").unwrap().unwrap().as_ref(), &[6]);
+		assert_eq!(db.get(Some(1), b"other").unwrap().unwrap().as_ref(), &[4]);
+
+		// no operator for this column.
+		assert!(db.merge(Some(0), b"count", &[1]).is_err());
+	}
+
+	#[test]
+	fn tells_corruption() {
+		use std::io;
+
+		assert!(is_corruption(&"Corruption: bad block contents".to_owned().into()));
+		assert!(!is_corruption(&"IO error: lock hold by current process".to_owned().into()));
+		assert!(!is_corruption(&io::Error::new(io::ErrorKind::Other, "Corruption: not from rocksdb").into()));
+	}
+
+	#[test]
+	fn repairs_closed_database() {
+		let tempdir = TempDir::new("").unwrap();
+		let path = tempdir.path().to_str().unwrap();
+		let config = DatabaseConfig::with_columns(Some(1));
+		{
+			let db = Database::open(&config, path).unwrap();
+			let mut batch = db.transaction();
+			batch.put(Some(0), b"key", b"value");
+			db.write(batch).unwrap();
+		}
+
+		Database::repair(path).unwrap();
+		let db = Database::open(&config, path).unwrap();
+		assert_eq!(db.get(Some(0), b"key").unwrap().unwrap().as_ref(), b"value");
+	}
 }
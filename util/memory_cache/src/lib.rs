@@ -67,7 +67,17 @@ impl<K: Eq + Hash, V: HeapSizeOf> MemoryLruCache<K, V> {
 			self.cur_size -= heap_size_of(&lru);
 		}
 
-		// remove elements until we are below the memory target.
+		self.shrink();
+	}
+
+	/// Change the maximum size in bytes, evicting least recently used items if it shrinks.
+	pub fn set_max_size(&mut self, max_size: usize) {
+		self.max_size = max_size;
+		self.shrink();
+	}
+
+	// remove elements until we are below the memory target.
+	fn shrink(&mut self) {
 		while self.cur_size > self.max_size {
 			match self.inner.remove_lru() {
 				Some((_, v)) => self.cur_size -= heap_size_of(&v),
@@ -110,4 +120,18 @@ mod tests {
 
 		assert_eq!(cache.current_size(), size2);
 	}
+
+	#[test]
+	fn shrinking_evicts() {
+		let mut cache = MemoryLruCache::new(1024);
+		let val = vec![0u8; 100];
+		let size = heap_size_of(&val);
+		cache.insert("hello", val.clone());
+		cache.insert("world", val);
+
+		cache.set_max_size(size);
+		assert!(cache.get_mut(&"hello").is_none());
+		assert!(cache.get_mut(&"world").is_some());
+		assert_eq!(cache.current_size(), size);
+	}
 }
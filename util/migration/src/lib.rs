@@ -273,6 +273,7 @@ impl Manager {
 			compaction: config.compaction_profile,
 			columns: columns,
 			wal: true,
+			merge_operators: Default::default(),
 		};
 
 		let db_root = database_path(old_path);